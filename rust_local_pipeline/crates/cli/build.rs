@@ -0,0 +1,25 @@
+//! Embeds the git commit the binary is built from as `PIPELINE_GIT_COMMIT`
+//! (recorded in the run ledger). `GIT_COMMIT` overrides it, e.g. in builds
+//! without a `.git` directory; otherwise it is `unknown`.
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+    };
+    if let Some(dir) = git(&["rev-parse", "--git-dir"]) {
+        println!("cargo:rerun-if-changed={dir}/HEAD");
+        println!("cargo:rerun-if-changed={dir}/refs");
+    }
+    let commit = std::env::var("GIT_COMMIT")
+        .ok()
+        .or_else(|| git(&["rev-parse", "--short=12", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=PIPELINE_GIT_COMMIT={commit}");
+}
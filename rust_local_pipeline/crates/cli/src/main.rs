@@ -1,27 +1,61 @@
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{anyhow, Context, Result};
+use metadata::runs::{Artifact, RunEntry, RunStatus};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use clap::{Parser, Subcommand};
+use time::macros::format_description;
+use time::Date;
 use tracing_subscriber::EnvFilter;
 
 #[derive(Parser, Debug)]
 #[command(name = "pipeline", version, about = "Local DVF pipeline CLI")]
 struct Cli {
+    /// Directory holding the dataset descriptors ({dataset}.toml).
+    #[arg(long, global = true, default_value = metadata::DEFAULT_CONFIG_DIR)]
+    config_dir: PathBuf,
     #[command(subcommand)]
     cmd: Commands,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// CSV -> Bronze IPC
+    /// CSV, Parquet, NDJSON or XLSX (descriptor `[source] format`) -> Bronze IPC
     Ingest {
         #[arg(long)]
         dataset: String,
+        /// Source file, directory of source files, or quoted glob (e.g. "./raw/dvf_*.csv"),
+        /// or an object URL / prefix / glob (s3://, gs://, az://, https://)
         #[arg(long)]
         source: PathBuf,
         #[arg(long, value_name = "YYYY-MM-DD")]
         ingest_date: String,
         #[arg(long, default_value = "./data")]
         root: PathBuf,
+        /// Source character encoding (e.g. windows-1252); overrides the descriptor.
+        #[arg(long)]
+        encoding: Option<String>,
+        /// Roll over to a new Bronze part after this many rows (0 = unbounded).
+        #[arg(long, default_value_t = ingest::DEFAULT_MAX_ROWS_PER_PART)]
+        max_rows_per_part: u64,
+        /// Roll over to a new Bronze part after this many bytes of field data (0 = unbounded).
+        #[arg(long, default_value_t = ingest::DEFAULT_MAX_BYTES_PER_PART)]
+        max_bytes_per_part: u64,
+        /// Fail when more than this share of records is quarantined to bronze_errors.
+        #[arg(long, default_value_t = ingest::DEFAULT_MAX_ERROR_RATIO)]
+        max_error_ratio: f64,
+        /// Ingest even if the same source content was already ingested under another date.
+        #[arg(long)]
+        force: bool,
+        /// Fail (instead of skipping) when every source's content was already ingested.
+        #[arg(long)]
+        fail_on_unchanged: bool,
+        /// CSV parser threads (0 = one per core, 1 = sequential reader).
+        #[arg(long, default_value_t = ingest::DEFAULT_PARSE_THREADS)]
+        parse_threads: usize,
+        /// Continue an interrupted ingest of this date from its last completed part.
+        #[arg(long)]
+        resume: bool,
     },
     /// Bronze IPC -> Silver IPC (+ Rejects)
     Validate {
@@ -31,6 +65,15 @@ enum Commands {
         ingest_date: String,
         #[arg(long, default_value = "./data")]
         root: PathBuf,
+        /// Write a column profile next to Silver, even if the descriptor does not enable it.
+        #[arg(long)]
+        profile: bool,
+        /// Reference date for `today` date bounds of range rules (default: the system date, UTC).
+        #[arg(long, value_name = "YYYY-MM-DD", value_parser = parse_date)]
+        today: Option<Date>,
+        /// Validation threads (0 = one per core).
+        #[arg(long, default_value_t = validate::DEFAULT_THREADS)]
+        threads: usize,
     },
     /// Silver IPC -> Gold Parquet + manifests
     Curate {
@@ -59,7 +102,91 @@ enum Commands {
         #[arg(long, default_value = "./data")]
         root: PathBuf,
     },
+    /// Ingest -> Validate -> Curate -> DuckDB refresh, stopping on the first failure.
+    Run {
+        #[arg(long)]
+        dataset: String,
+        /// Source file, directory of source files, or quoted glob (e.g. "./raw/dvf_*.csv"),
+        /// or an object URL / prefix / glob (s3://, gs://, az://, https://)
+        #[arg(long)]
+        source: PathBuf,
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        ingest_date: String,
+        /// Where to place the Gold snapshot. Defaults to the ingest date.
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        snapshot_date: Option<String>,
+        #[arg(long, default_value = "./metabase/warehouse.duckdb")]
+        db: PathBuf,
+        #[arg(long, default_value = "./data")]
+        root: PathBuf,
+        /// Source character encoding (e.g. windows-1252); overrides the descriptor.
+        #[arg(long)]
+        encoding: Option<String>,
+        /// Roll over to a new Bronze part after this many rows (0 = unbounded).
+        #[arg(long, default_value_t = ingest::DEFAULT_MAX_ROWS_PER_PART)]
+        max_rows_per_part: u64,
+        /// Roll over to a new Bronze part after this many bytes of field data (0 = unbounded).
+        #[arg(long, default_value_t = ingest::DEFAULT_MAX_BYTES_PER_PART)]
+        max_bytes_per_part: u64,
+        /// Fail when more than this share of records is quarantined to bronze_errors.
+        #[arg(long, default_value_t = ingest::DEFAULT_MAX_ERROR_RATIO)]
+        max_error_ratio: f64,
+        /// Ingest even if the same source content was already ingested under another date.
+        #[arg(long)]
+        force: bool,
+        /// Fail (instead of skipping) when every source's content was already ingested.
+        #[arg(long)]
+        fail_on_unchanged: bool,
+        /// CSV parser threads (0 = one per core, 1 = sequential reader).
+        #[arg(long, default_value_t = ingest::DEFAULT_PARSE_THREADS)]
+        parse_threads: usize,
+        /// Continue an interrupted ingest of this date from its last completed part.
+        #[arg(long)]
+        resume: bool,
+        /// Write a column profile next to Silver, even if the descriptor does not enable it.
+        #[arg(long)]
+        profile: bool,
+        /// Reference date for `today` date bounds of range rules (default: the system date, UTC).
+        #[arg(long, value_name = "YYYY-MM-DD", value_parser = parse_date)]
+        today: Option<Date>,
+        /// Validation threads (0 = one per core).
+        #[arg(long, default_value_t = validate::DEFAULT_THREADS)]
+        validate_threads: usize,
+    },
+    /// Run ledger: every stage execution, with its inputs, outputs and counts.
+    Runs {
+        #[command(subcommand)]
+        cmd: RunsCmd,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RunsCmd {
+    /// One line per stage execution, newest first.
+    List {
+        #[arg(long)]
+        dataset: Option<String>,
+        /// Only the runs that wrote (or refreshed views over) this Gold snapshot.
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        snapshot_date: Option<String>,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        #[arg(long, default_value = "./data")]
+        root: PathBuf,
+    },
+    /// The stages of one run (id or unique prefix) and their lineage back to the source files.
+    Show {
+        run_id: String,
+        #[arg(long, default_value = "./data")]
+        root: PathBuf,
+    },
+}
 
+/// One line of the `run` report.
+struct StageReport {
+    stage: &'static str,
+    elapsed: Duration,
+    summary: String,
 }
 
 #[tokio::main]
@@ -71,69 +198,548 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
     let start = std::time::Instant::now();
+    let config_dir = cli.config_dir;
     match cli.cmd {
-        Commands::Ingest { dataset, source, ingest_date, root } => {
-            let cfg = ingest::IngestConfig {
-                slug: dataset,
-                source: ingest::SourceKind::LocalFile,
-                ingest_date,
-                storage_root: root,
-                bronze_dir: "bronze".to_string(),
-            };
-            let stats = ingest::ingest_dataset(cfg, &source.as_path()).await?;
-            println!(
-                "INGEST OK rows_in={} bytes_in={} out={}",
-                stats.rows_in, stats.bytes_in, stats.out_path.display()
-            );
+        Commands::Ingest {
+            dataset,
+            source,
+            ingest_date,
+            root,
+            encoding,
+            max_rows_per_part,
+            max_bytes_per_part,
+            max_error_ratio,
+            force,
+            fail_on_unchanged,
+            parse_threads,
+            resume,
+        } => {
+            let mut desc = metadata::load_descriptor_from(&config_dir, &dataset)?;
+            if let Some(e) = encoding {
+                desc.source.encoding = e;
+            }
+            let mut cfg = ingest::IngestConfig::from_descriptor(&desc, ingest_date, root)?;
+            cfg.max_rows_per_part = max_rows_per_part;
+            cfg.max_bytes_per_part = max_bytes_per_part;
+            cfg.max_error_ratio = max_error_ratio;
+            cfg.source = ingest::SourceKind::for_source(&source.to_string_lossy())?;
+            set_unchanged_policy(&mut cfg, force, fail_on_unchanged);
+            cfg.parse_threads = parse_threads;
+            cfg.resume = resume;
+            let run = StageRun::start(&cfg.storage_root, &cfg.run_id, "ingest", &dataset)
+                .dates(Some(&cfg.ingest_date), None);
+            let stats = run.finish(ingest::ingest_dataset(cfg, &source).await, describe_ingest)?;
+            if stats.resumed_parts > 0 {
+                println!("  resumed after {} completed parts", stats.resumed_parts);
+            }
+            for s in &stats.skipped {
+                println!(
+                    "  {} unchanged blake3={} ingest_date={}",
+                    s.path.display(),
+                    s.blake3,
+                    s.ingested_on
+                );
+            }
+            if stats.unchanged() {
+                println!("INGEST UNCHANGED {}", ingest_summary(&stats));
+            } else {
+                for f in &stats.files {
+                    let delimiter = f.dialect.map_or("-".to_string(), |d| format!("{:?}", d.delimiter as char));
+                    println!(
+                        "  {} rows={} quarantined={} bytes={} uncompressed={} compression={} delimiter={}",
+                        f.path.display(),
+                        f.rows_in,
+                        f.quarantined,
+                        f.bytes_in,
+                        f.bytes_uncompressed,
+                        f.compression,
+                        delimiter
+                    );
+                }
+                if let Some(d) = &stats.drift {
+                    println!("  drift {}", d.summary());
+                }
+                println!("INGEST OK {}", ingest_summary(&stats));
+            }
         }
-        Commands::Validate { dataset, ingest_date, root } => {
-            let cfg = validate::ValidateConfig {
-                slug: dataset,
-                ingest_date,
-                storage_root: root,
-                bronze_dir: "bronze".to_string(),
-                silver_dir: "silver".to_string(),
-                rejects_dir: "rejects".to_string(),
-            };
-            let st = validate::validate_dataset(cfg).await?;
-            println!(
-                "VALIDATE OK rows_in={} rows_out={} rejects={} silver={} rejects={}",
-                st.rows_in, st.rows_out, st.rejects, st.silver_out.display(), st.rejects_out.display()
-            );
+        Commands::Validate { dataset, ingest_date, root, profile, today, threads } => {
+            let desc = metadata::load_descriptor_from(&config_dir, &dataset)?;
+            let mut cfg = validate::ValidateConfig::from_descriptor(&desc, ingest_date, root);
+            cfg.profile.enabled |= profile;
+            ValidateOverrides { today, threads }.apply(&mut cfg);
+            let run = StageRun::start(&cfg.storage_root, &cfg.run_id, "validate", &dataset)
+                .dates(Some(&cfg.ingest_date), None);
+            let st = run.finish(validate::validate_dataset(cfg).await, describe_validate)?;
+            for c in &st.profile_changes {
+                println!("  profile change {}", c.summary());
+            }
+            println!("VALIDATE OK {}", validate_summary(&st));
         }
         Commands::Curate { dataset, ingest_date, snapshot_date, root } => {
-            let cfg = curate::CurateConfig {
-                slug: dataset,
-                ingest_date,
-                snapshot_date,
-                storage_root: root,
-                silver_dir: "silver".to_string(),
-                gold_dir: "gold".to_string(),
-                manifests_dir: "manifests".to_string(),
-            };
-            let st = curate::write_gold_snapshot(cfg).await?;
-            println!(
-                "CURATE OK files_written={} rows_written={} snapshot_dir={} commit={}",
-                st.files_written,
-                st.rows_written,
-                st.snapshot_dir.display(),
-                st.commit_path.display()
-            );
+            let desc = metadata::load_descriptor_from(&config_dir, &dataset)?;
+            let cfg = curate::CurateConfig::from_descriptor(&desc, ingest_date, snapshot_date, root);
+            let run = StageRun::start(&cfg.storage_root, &cfg.run_id, "curate", &dataset)
+                .dates(Some(&cfg.ingest_date), Some(&cfg.snapshot_date));
+            let st = run.finish(curate::write_gold_snapshot(cfg).await, describe_curate)?;
+            println!("CURATE OK {}", curate_summary(&st));
         }
         Commands::DuckdbRefresh { dataset, snapshot_date, db, root } => {
-            let cfg = duckdb_catalog::RefreshCfg {
-                slug: dataset,
-                storage_root: root,
-                manifests_dir: "manifests".to_string(),
-                gold_dir: "gold".to_string(),
-                snapshot_date,
-                duckdb_path: db,
-            };
-            duckdb_catalog::refresh_duckdb(cfg)?;
+            let desc = metadata::load_descriptor_from(&config_dir, &dataset)?;
+            let run = StageRun::start(&root, &metadata::staging::new_run_id(), "duckdb-refresh", &dataset)
+                .dates(None, snapshot_date.as_deref());
+            let cfg = duckdb_catalog::RefreshCfg::from_descriptor(&desc, snapshot_date, db.clone(), root);
+            run.finish(duckdb_catalog::refresh_duckdb(cfg), |_, e| describe_refresh(&db, e))?;
             println!("DUCKDB REFRESH OK");
         }
+        Commands::Run {
+            dataset,
+            source,
+            ingest_date,
+            snapshot_date,
+            db,
+            root,
+            encoding,
+            max_rows_per_part,
+            max_bytes_per_part,
+            max_error_ratio,
+            force,
+            fail_on_unchanged,
+            parse_threads,
+            resume,
+            profile,
+            today,
+            validate_threads,
+        } => {
+            let mut desc = metadata::load_descriptor_from(&config_dir, &dataset)?;
+            if let Some(e) = encoding {
+                desc.source.encoding = e;
+            }
+            desc.profile.enabled |= profile;
+            let snapshot_date = snapshot_date.unwrap_or_else(|| ingest_date.clone());
+            println!(
+                "RUN dataset={} ingest_date={} snapshot_date={}",
+                dataset, ingest_date, snapshot_date
+            );
+            let mut cfg = ingest::IngestConfig::from_descriptor(&desc, ingest_date, root)?;
+            cfg.max_rows_per_part = max_rows_per_part;
+            cfg.max_bytes_per_part = max_bytes_per_part;
+            cfg.max_error_ratio = max_error_ratio;
+            cfg.source = ingest::SourceKind::for_source(&source.to_string_lossy())?;
+            set_unchanged_policy(&mut cfg, force, fail_on_unchanged);
+            cfg.parse_threads = parse_threads;
+            cfg.resume = resume;
+            let mut report: Vec<StageReport> = Vec::new();
+            let overrides = ValidateOverrides { today, threads: validate_threads };
+            let res = run_all(&desc, cfg, &source, &snapshot_date, &db, overrides, &mut report).await;
+            for r in &report {
+                println!(
+                    "  {:<15} {:>12}  {}",
+                    r.stage,
+                    humantime::format_duration(round_ms(r.elapsed)).to_string(),
+                    r.summary
+                );
+            }
+            if res? {
+                println!("RUN OK stages={}", report.len());
+            } else {
+                println!("RUN UNCHANGED sources already ingested, later stages skipped");
+            }
+        }
+        Commands::Runs { cmd: RunsCmd::List { dataset, snapshot_date, limit, root } } => {
+            let entries = metadata::runs::load(&root)?;
+            for e in entries
+                .iter()
+                .rev()
+                .filter(|e| dataset.as_ref().is_none_or(|d| &e.dataset == d))
+                .filter(|e| snapshot_date.is_none() || e.snapshot_date == snapshot_date)
+                .take(limit)
+            {
+                println!("{}", run_line(e));
+            }
+        }
+        Commands::Runs { cmd: RunsCmd::Show { run_id, root } } => {
+            let entries = metadata::runs::load(&root)?;
+            show_run(&entries, &run_id)?;
+        }
     }
     let duration_pretty = humantime::format_duration(start.elapsed());
     println!("DONE in {}", duration_pretty);
     Ok(())
 }
+
+/// Command-line settings for the validate stage.
+struct ValidateOverrides {
+    today: Option<Date>,
+    threads: usize,
+}
+
+impl ValidateOverrides {
+    fn apply(self, cfg: &mut validate::ValidateConfig) {
+        if let Some(d) = self.today {
+            cfg.today = d;
+        }
+        cfg.threads = self.threads;
+    }
+}
+
+/// Runs the four stages in order; each finished stage is appended to `report`.
+/// Returns false when ingest found nothing new and the later stages were skipped.
+async fn run_all(
+    desc: &metadata::DatasetDescriptor,
+    ingest_cfg: ingest::IngestConfig,
+    source: &Path,
+    snapshot_date: &str,
+    db: &Path,
+    overrides: ValidateOverrides,
+    report: &mut Vec<StageReport>,
+) -> Result<bool> {
+    let ingest_date = ingest_cfg.ingest_date.clone();
+    let root = ingest_cfg.storage_root.clone();
+    let slug = desc.slug.as_str();
+
+    let t = Instant::now();
+    let run = StageRun::start(&root, &ingest_cfg.run_id, "ingest", slug).dates(Some(&ingest_date), None);
+    let st = run
+        .finish(ingest::ingest_dataset(ingest_cfg, source).await, describe_ingest)
+        .context("stage ingest failed")?;
+    report.push(StageReport { stage: "ingest", elapsed: t.elapsed(), summary: ingest_summary(&st) });
+    if st.unchanged() {
+        return Ok(false);
+    }
+    // one run id for the whole run (the interrupted run's when ingest resumed one):
+    // every stage stages its outputs and records itself under it
+    let run_id = st.run_id;
+
+    let t = Instant::now();
+    let mut cfg = validate::ValidateConfig::from_descriptor(desc, ingest_date.clone(), root.clone());
+    cfg.run_id = run_id.clone();
+    overrides.apply(&mut cfg);
+    let run = StageRun::start(&root, &run_id, "validate", slug).dates(Some(&ingest_date), None);
+    let st = run
+        .finish(validate::validate_dataset(cfg).await, describe_validate)
+        .context("stage validate failed")?;
+    report.push(StageReport { stage: "validate", elapsed: t.elapsed(), summary: validate_summary(&st) });
+
+    let t = Instant::now();
+    let mut cfg = curate::CurateConfig::from_descriptor(
+        desc,
+        ingest_date.clone(),
+        snapshot_date.to_string(),
+        root.clone(),
+    );
+    cfg.run_id = run_id.clone();
+    let run = StageRun::start(&root, &run_id, "curate", slug).dates(Some(&ingest_date), Some(snapshot_date));
+    let st = run
+        .finish(curate::write_gold_snapshot(cfg).await, describe_curate)
+        .context("stage curate failed")?;
+    report.push(StageReport { stage: "curate", elapsed: t.elapsed(), summary: curate_summary(&st) });
+
+    let t = Instant::now();
+    let cfg = duckdb_catalog::RefreshCfg::from_descriptor(
+        desc,
+        Some(snapshot_date.to_string()),
+        db.to_path_buf(),
+        root.clone(),
+    );
+    let run = StageRun::start(&root, &run_id, "duckdb-refresh", slug).dates(None, Some(snapshot_date));
+    run.finish(duckdb_catalog::refresh_duckdb(cfg), |_, e| describe_refresh(db, e))
+        .context("stage duckdb-refresh failed")?;
+    report.push(StageReport {
+        stage: "duckdb-refresh",
+        elapsed: t.elapsed(),
+        summary: format!("db={}", db.display()),
+    });
+    Ok(true)
+}
+
+// ---------- run ledger
+
+/// A stage execution, appended to the run ledger (`<root>/_runs/runs.jsonl`) when it ends.
+struct StageRun {
+    root: PathBuf,
+    entry: RunEntry,
+    t: Instant,
+}
+
+impl StageRun {
+    fn start(root: &Path, run_id: &str, stage: &str, dataset: &str) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            entry: RunEntry::start(run_id, stage, dataset, &version()),
+            t: Instant::now(),
+        }
+    }
+
+    fn dates(mut self, ingest_date: Option<&str>, snapshot_date: Option<&str>) -> Self {
+        self.entry.ingest_date = ingest_date.map(str::to_string);
+        self.entry.snapshot_date = snapshot_date.map(str::to_string);
+        self
+    }
+
+    /// Records the stage's outcome: what `describe` reads from its stats, or the error.
+    /// The entry is appended even when `describe` fails (e.g. an output vanished before it
+    /// was hashed): the stage is then recorded as failed with that error.
+    fn finish<T>(mut self, res: Result<T>, describe: impl FnOnce(&T, &mut RunEntry) -> Result<()>) -> Result<T> {
+        self.entry.duration_ms = self.t.elapsed().as_millis() as u64;
+        let res = res.and_then(|st| {
+            describe(&st, &mut self.entry).context("record stage outputs")?;
+            Ok(st)
+        });
+        if let Err(e) = &res {
+            self.entry.status = RunStatus::Failed;
+            self.entry.error = Some(format!("{e:#}"));
+        }
+        metadata::runs::append(&self.root, &self.entry)?;
+        res
+    }
+}
+
+/// Crate version and the git commit the binary was built from (see build.rs).
+fn version() -> String {
+    format!("{} (git {})", env!("CARGO_PKG_VERSION"), env!("PIPELINE_GIT_COMMIT"))
+}
+
+fn describe_ingest(st: &ingest::IngestStats, e: &mut RunEntry) -> Result<()> {
+    e.run_id = st.run_id.clone();
+    e.inputs = st
+        .files
+        .iter()
+        .map(|f| Artifact {
+            path: f.path.to_string_lossy().to_string(),
+            blake3: f.blake3.clone(),
+            bytes: f.bytes_in,
+        })
+        .collect();
+    if st.unchanged() {
+        e.status = RunStatus::Unchanged;
+    } else {
+        let errors = part_files(&st.errors_out)?;
+        e.outputs = hash_files(st.parts.iter().chain(&errors))?;
+    }
+    e.counts.extend([
+        ("rows_in".to_string(), st.rows_in),
+        ("quarantined".to_string(), st.quarantined),
+        ("bytes_in".to_string(), st.bytes_in),
+        ("bytes_uncompressed".to_string(), st.bytes_uncompressed),
+        ("parts".to_string(), st.parts_written as u64),
+        ("skipped".to_string(), st.skipped.len() as u64),
+    ]);
+    Ok(())
+}
+
+fn describe_validate(st: &validate::ValidationStats, e: &mut RunEntry) -> Result<()> {
+    e.inputs = hash_files(&st.bronze_parts)?;
+    let outputs = [&st.silver_out, &st.rejects_out, &st.duplicates_out].into_iter();
+    e.outputs = hash_files(outputs.chain(&st.mutations_out).chain(&st.profile_out))?;
+    e.counts.extend([
+        ("rows_in".to_string(), st.rows_in),
+        ("rows_out".to_string(), st.rows_out),
+        ("rejects".to_string(), st.rejects),
+        ("prior_duplicates".to_string(), st.prior_duplicates),
+        ("duplicates".to_string(), st.duplicates),
+    ]);
+    for (code, n) in &st.fixes {
+        e.counts.insert(format!("fixed.{code}"), *n);
+    }
+    if st.mutations_out.is_some() {
+        e.counts.insert("mutations".to_string(), st.mutations);
+    }
+    if st.profile_out.is_some() {
+        e.counts.insert("profile_changes".to_string(), st.profile_changes.len() as u64);
+    }
+    Ok(())
+}
+
+fn describe_curate(st: &curate::CurateStats, e: &mut RunEntry) -> Result<()> {
+    e.inputs = hash_files(&st.silver_parts)?;
+    e.outputs = hash_files(st.files.iter().chain([&st.commit_path]))?;
+    e.counts.extend([
+        ("files_written".to_string(), st.files_written as u64),
+        ("rows_written".to_string(), st.rows_written),
+    ]);
+    if st.mutation_rows_written > 0 {
+        e.counts.insert("mutation_rows_written".to_string(), st.mutation_rows_written);
+    }
+    Ok(())
+}
+
+fn describe_refresh(db: &Path, e: &mut RunEntry) -> Result<()> {
+    e.outputs = hash_files([db])?;
+    Ok(())
+}
+
+fn hash_files<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<Vec<Artifact>> {
+    paths.into_iter().map(|p| Artifact::hash_file(p.as_ref())).collect()
+}
+
+/// `part-*` files of a partition directory, in part order.
+fn part_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut parts = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.file_name().is_some_and(|n| n.to_string_lossy().starts_with("part-")) {
+            parts.push(path);
+        }
+    }
+    parts.sort();
+    Ok(parts)
+}
+
+/// One line of `runs list`.
+fn run_line(e: &RunEntry) -> String {
+    let counts: Vec<String> = e.counts.iter().map(|(k, v)| format!("{k}={v}")).collect();
+    format!(
+        "{}  {:<14} {:<9} {} ingest_date={} snapshot_date={} started={} {} {}",
+        e.run_id,
+        e.stage,
+        e.status,
+        e.dataset,
+        e.ingest_date.as_deref().unwrap_or("-"),
+        e.snapshot_date.as_deref().unwrap_or("-"),
+        e.started_at,
+        humantime::format_duration(Duration::from_millis(e.duration_ms)),
+        counts.join(" ")
+    )
+}
+
+/// `runs show`: every stage of the run, then where its inputs came from.
+fn show_run(entries: &[RunEntry], run_id: &str) -> Result<()> {
+    let ids: HashSet<&str> = entries
+        .iter()
+        .map(|e| e.run_id.as_str())
+        .filter(|id| id.starts_with(run_id))
+        .collect();
+    let id = match ids.len() {
+        0 => return Err(anyhow!("no run {run_id} in the run ledger")),
+        1 => ids.into_iter().next().unwrap_or_default(),
+        n => return Err(anyhow!("{n} runs start with {run_id}, give more of the id")),
+    };
+    let run: Vec<&RunEntry> = entries.iter().filter(|e| e.run_id == id).collect();
+    println!("RUN {id}");
+    for e in &run {
+        println!(
+            "  {} {} started={} duration={} version={}",
+            e.stage,
+            e.status,
+            e.started_at,
+            humantime::format_duration(Duration::from_millis(e.duration_ms)),
+            e.version
+        );
+        println!(
+            "    dataset={} ingest_date={} snapshot_date={}",
+            e.dataset,
+            e.ingest_date.as_deref().unwrap_or("-"),
+            e.snapshot_date.as_deref().unwrap_or("-")
+        );
+        if let Some(err) = &e.error {
+            println!("    error: {err}");
+        }
+        if !e.counts.is_empty() {
+            let counts: Vec<String> = e.counts.iter().map(|(k, v)| format!("{k}={v}")).collect();
+            println!("    {}", counts.join(" "));
+        }
+        for a in &e.inputs {
+            println!("    in  {} blake3={} bytes={}", a.path, a.blake3, a.bytes);
+        }
+        for a in &e.outputs {
+            println!("    out {} blake3={} bytes={}", a.path, a.blake3, a.bytes);
+        }
+    }
+    // Lineage of the most downstream stage that read something
+    if let Some(last) = run.iter().rev().find(|e| !e.inputs.is_empty()) {
+        println!("LINEAGE");
+        print_lineage(entries, last, 1, &mut HashSet::new());
+    }
+    Ok(())
+}
+
+fn print_lineage<'a>(entries: &'a [RunEntry], e: &'a RunEntry, depth: usize, seen: &mut HashSet<(&'a str, &'a str)>) {
+    let pad = "  ".repeat(depth);
+    let dates = [("ingest_date", &e.ingest_date), ("snapshot_date", &e.snapshot_date)]
+        .iter()
+        .filter_map(|(k, v)| v.as_ref().map(|v| format!(" {k}={v}")))
+        .collect::<String>();
+    println!("{pad}{} {}{dates}", e.stage, e.run_id);
+    if !seen.insert((e.run_id.as_str(), e.stage.as_str())) {
+        return;
+    }
+    let upstream = metadata::runs::upstream(entries, e);
+    if upstream.is_empty() {
+        // the start of the chain: ingest's source files, or inputs nobody recorded writing
+        let label = if e.stage == "ingest" { "source" } else { "unrecorded input" };
+        for a in &e.inputs {
+            println!("{pad}  {label} {} blake3={}", a.path, a.blake3);
+        }
+    }
+    for u in upstream {
+        print_lineage(entries, u, depth + 1, seen);
+    }
+}
+
+fn set_unchanged_policy(cfg: &mut ingest::IngestConfig, force: bool, fail_on_unchanged: bool) {
+    cfg.force = force;
+    if fail_on_unchanged {
+        cfg.on_unchanged = ingest::OnUnchanged::Fail;
+    }
+}
+
+fn ingest_summary(st: &ingest::IngestStats) -> String {
+    format!(
+        "rows_in={} quarantined={} bytes_in={} uncompressed={} files={} skipped={} parts={} out={}",
+        st.rows_in,
+        st.quarantined,
+        st.bytes_in,
+        st.bytes_uncompressed,
+        st.files.len(),
+        st.skipped.len(),
+        st.parts_written,
+        st.out_dir.display()
+    )
+}
+
+fn validate_summary(st: &validate::ValidationStats) -> String {
+    let mut s = format!(
+        "rows_in={} rows_out={} rejects={} (prior_ingest={}) duplicates={} silver={} rejects={} duplicates={}",
+        st.rows_in,
+        st.rows_out,
+        st.rejects,
+        st.prior_duplicates,
+        st.duplicates,
+        st.silver_out.display(),
+        st.rejects_out.display(),
+        st.duplicates_out.display()
+    );
+    if !st.fixes.is_empty() {
+        let fixes: Vec<String> = st.fixes.iter().map(|(code, n)| format!("{code}:{n}")).collect();
+        s.push_str(&format!(" fixes={}", fixes.join(",")));
+    }
+    if let Some(p) = &st.mutations_out {
+        s.push_str(&format!(" mutations={} ({})", st.mutations, p.display()));
+    }
+    if let Some(p) = &st.profile_out {
+        s.push_str(&format!(" profile={} profile_changes={}", p.display(), st.profile_changes.len()));
+    }
+    s
+}
+
+fn curate_summary(st: &curate::CurateStats) -> String {
+    let mut s = format!(
+        "files_written={} rows_written={} snapshot_dir={} commit={}",
+        st.files_written,
+        st.rows_written,
+        st.snapshot_dir.display(),
+        st.commit_path.display()
+    );
+    if st.mutation_rows_written > 0 {
+        s.push_str(&format!(" mutation_rows_written={}", st.mutation_rows_written));
+    }
+    s
+}
+
+fn parse_date(s: &str) -> Result<Date, String> {
+    Date::parse(s, format_description!("[year]-[month]-[day]")).map_err(|e| format!("expected YYYY-MM-DD: {e}"))
+}
+
+fn round_ms(d: Duration) -> Duration {
+    Duration::from_millis(d.as_millis() as u64)
+}
@@ -1,17 +1,18 @@
 use anyhow::{anyhow, Context, Result};
-use arrow::array::{Array, ArrayRef, Int16Array, StringArray, UInt32Array};
-use arrow::compute::take;
-use arrow::datatypes::Schema;
+use arrow::array::{Array, ArrayRef, StringArray, UInt32Array};
+use arrow::compute::{cast, take};
+use arrow::datatypes::{DataType, Schema};
 use arrow::ipc::reader::FileReader as IpcReader;
 use arrow::record_batch::RecordBatch;
 use parquet::arrow::ArrowWriter;
 use parquet::basic::{Compression, ZstdLevel};
 use parquet::file::properties::WriterProperties;
+use metadata::staging::{self, StagedPartition};
+use metadata::{DatasetDescriptor, PartitionSpec};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs::{create_dir_all, File};
-use std::io::Write;
-use std::path::PathBuf;
+use std::fs::{create_dir_all, read_dir, File};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 #[derive(Debug, Default, Clone)]
@@ -20,17 +21,46 @@ pub struct CurateStats {
     pub rows_written: u64,
     pub snapshot_dir: PathBuf,
     pub commit_path: PathBuf,
+    pub silver_parts: Vec<PathBuf>, // inputs: every Silver ingest up to ingest_date
+    pub files: Vec<PathBuf>,        // Gold Parquet files, one per partition
+    pub mutation_rows_written: u64, // gold/<slug>_mutations, when curated
 }
 
 #[derive(Debug, Clone)]
 pub struct CurateConfig {
     pub slug: String,
-    pub ingest_date: String,
+    pub ingest_date: String, // latest Silver ingest in the snapshot, e.g. "2024-10-01"
     pub snapshot_date: String,
     pub storage_root: PathBuf,
     pub silver_dir: String,
     pub gold_dir: String,
     pub manifests_dir: String,
+    pub partitions: Vec<PartitionSpec>, // outermost first, e.g. year_mutation -> year=
+    pub mutations: bool, // also curate silver/<slug>_mutations (descriptor [mutations])
+    pub run_id: String, // e.g. "20241017T093012Z-1f3a9c2e"; names the staging directory
+}
+
+impl CurateConfig {
+    /// Defaults taken from the dataset descriptor (Gold partitioning).
+    pub fn from_descriptor(
+        desc: &DatasetDescriptor,
+        ingest_date: String,
+        snapshot_date: String,
+        storage_root: PathBuf,
+    ) -> Self {
+        Self {
+            slug: desc.slug.clone(),
+            ingest_date,
+            snapshot_date,
+            storage_root,
+            silver_dir: "silver".to_string(),
+            gold_dir: "gold".to_string(),
+            manifests_dir: "manifests".to_string(),
+            partitions: desc.partitions.clone(),
+            mutations: desc.mutations.is_some(),
+            run_id: staging::new_run_id(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -47,71 +77,151 @@ struct CommitJson {
 }
 
 pub async fn write_gold_snapshot(cfg: CurateConfig) -> Result<CurateStats> {
-    // Locate Silver IPC
-    let silver_path = cfg
-        .storage_root
-        .join(&cfg.silver_dir)
-        .join(&cfg.slug)
-        .join(format!("ingest_date={}", cfg.ingest_date))
-        .join("part-000000.arrow");
-    if !silver_path.exists() {
-        return Err(anyhow!("Silver file not found: {}", silver_path.display()));
+    let partition = format!("snapshot_date={}", cfg.snapshot_date);
+    let gold_dataset = cfg.storage_root.join(&cfg.gold_dir).join(&cfg.slug);
+    let snapshot_dir = gold_dataset.join(&partition);
+
+    // The mutation table first: the row-level snapshot's _SUCCESS marker comes last.
+    // Its Silver partition already covers the earlier ingests (validate aggregates them too)
+    let mut silver_parts = Vec::new();
+    let mut files: Vec<CommitFile> = Vec::new();
+    let mut mutation_rows_written = 0u64;
+    if cfg.mutations {
+        let slug = metadata::mutations_slug(&cfg.slug);
+        let written = write_partitions(&cfg, &slug, false)?;
+        mutation_rows_written = written.files.iter().map(|f| f.rows).sum();
+        silver_parts.extend(written.silver_parts);
+        files.extend(written.files);
+    }
+    // Each row-level Silver partition only holds the rows new in its drop (validate rejects
+    // keys kept by earlier ingests), so the snapshot reads all of them
+    let written = write_partitions(&cfg, &cfg.slug, true)?;
+    let files_written = written.files.len() as u32;
+    let rows_written = written.files.iter().map(|f| f.rows).sum();
+    silver_parts.extend(written.silver_parts);
+    files.extend(written.files);
+
+    // Write commit.json
+    let manifests_dir = cfg.storage_root.join(&cfg.manifests_dir).join(&cfg.slug);
+    let commit_path = manifests_dir.join(&partition).join("commit.json");
+    let gold_files: Vec<PathBuf> = files.iter().map(|f| PathBuf::from(&f.path)).collect();
+    let commit = CommitJson {
+        dataset: cfg.slug.clone(),
+        snapshot_date: cfg.snapshot_date.clone(),
+        files,
+    };
+    staging::write_file_atomic(&commit_path, serde_json::to_string_pretty(&commit)?.as_bytes())?;
+
+    // Update latest.json atomically (temp file + rename; never a partial copy)
+    let latest_path = manifests_dir.join("latest.json");
+    let json = format!(r#"{{"snapshot_date":"{}"}}"#, cfg.snapshot_date);
+    staging::write_file_atomic(&latest_path, json.as_bytes())?;
+
+    Ok(CurateStats {
+        files_written,
+        rows_written,
+        snapshot_dir,
+        commit_path,
+        silver_parts,
+        files: gold_files,
+        mutation_rows_written,
+    })
+}
+
+struct Written {
+    silver_parts: Vec<PathBuf>,
+    files: Vec<CommitFile>,
+}
+
+/// Curates the Silver partition of dataset directory `slug` into its committed Gold snapshot;
+/// with `earlier`, the committed partitions of earlier ingests are curated along with it.
+fn write_partitions(cfg: &CurateConfig, slug: &str, earlier: bool) -> Result<Written> {
+    // Locate Silver IPC parts (of completed validations only)
+    let silver_dataset = cfg.storage_root.join(&cfg.silver_dir).join(slug);
+    let silver_dir = silver_dataset.join(format!("ingest_date={}", cfg.ingest_date));
+    staging::require_committed(&silver_dir)?;
+    let current_parts = list_parts(&silver_dir, "arrow")?;
+    if current_parts.is_empty() {
+        return Err(anyhow!("no Silver parts found in {}", silver_dir.display()));
+    }
+    let mut silver_parts = Vec::new();
+    if earlier {
+        for (_, dir) in staging::committed_before(&silver_dataset, &cfg.ingest_date)? {
+            silver_parts.extend(list_parts(&dir, "arrow")?);
+        }
     }
+    silver_parts.extend(current_parts);
 
-    // Prepare snapshot output dir
-    let snapshot_dir = cfg
-        .storage_root
-        .join(&cfg.gold_dir)
-        .join(&cfg.slug)
-        .join(format!("snapshot_date={}", cfg.snapshot_date));
-    create_dir_all(&snapshot_dir)
-        .with_context(|| format!("mkdir -p {}", snapshot_dir.display()))?;
+    // Prepare snapshot output dir: written under _staging/<run_id>/, moved into place at the end
+    let gold_dataset = cfg.storage_root.join(&cfg.gold_dir).join(slug);
+    let snapshot_stage = StagedPartition::create(
+        &gold_dataset,
+        &format!("snapshot_date={}", cfg.snapshot_date),
+        &cfg.run_id,
+    )?;
+    let snapshot_dir = snapshot_stage.target().to_path_buf();
 
-    // Read Silver IPC
-    let f = File::open(&silver_path).with_context(|| format!("open {}", silver_path.display()))?;
-    let reader = IpcReader::try_new(f, None)?;
-    let schema: Arc<Schema> = reader.schema();
+    // Read Silver IPC (every part, one schema)
+    let mut readers = Vec::with_capacity(silver_parts.len());
+    for path in &silver_parts {
+        let f = File::open(path).with_context(|| format!("open {}", path.display()))?;
+        readers.push(IpcReader::try_new(f, None)?);
+    }
+    let schema: Arc<Schema> = readers[0].schema();
+    if let Some(i) = readers.iter().position(|r| r.schema() != schema) {
+        return Err(anyhow!("Silver schema differs in {}", silver_parts[i].display()));
+    }
 
     // Column indexes needed for partitioning
-    let year_idx = schema
-        .index_of("year_mutation")
-        .context("missing 'year_mutation' in Silver")?;
-    let dept_idx = schema
-        .index_of("code_departement")
-        .context("missing 'code_departement' in Silver")?;
-
-    // Partition buffers: key -> Vec<RecordBatch>
-    type Key = (i16, String);
+    let part_idx: Vec<usize> = cfg
+        .partitions
+        .iter()
+        .map(|p| {
+            schema
+                .index_of(&p.column)
+                .with_context(|| format!("missing partition column '{}' in Silver", p.column))
+        })
+        .collect::<Result<_>>()?;
+
+    // Partition buffers: key (one rendered value per partition column) -> Vec<RecordBatch>
+    type Key = Vec<String>;
     let mut parts: HashMap<Key, Vec<RecordBatch>> = HashMap::new();
 
-    for maybe_batch in reader {
+    for maybe_batch in readers.into_iter().flatten() {
         let batch = maybe_batch?;
         let n = batch.num_rows();
         if n == 0 {
             continue;
         }
 
-        let year_arr = batch
-            .column(year_idx)
-            .as_any()
-            .downcast_ref::<Int16Array>()
-            .ok_or_else(|| anyhow!("year_mutation must be Int16"))?;
-        let dept_arr = batch
-            .column(dept_idx)
-            .as_any()
-            .downcast_ref::<StringArray>()
-            .ok_or_else(|| anyhow!("code_departement must be Utf8"))?;
+        // Render partition values as strings (Int16 year, Utf8 dept, ...)
+        let part_arrs: Vec<ArrayRef> = part_idx
+            .iter()
+            .map(|&i| cast(batch.column(i), &DataType::Utf8))
+            .collect::<Result<_, _>>()?;
+        let part_strs: Vec<&StringArray> = part_arrs
+            .iter()
+            .map(|a| {
+                a.as_any()
+                    .downcast_ref::<StringArray>()
+                    .ok_or_else(|| anyhow!("partition column must cast to Utf8"))
+            })
+            .collect::<Result<_>>()?;
 
         // Build index vectors per partition in this batch
         let mut idx_map: HashMap<Key, Vec<u32>> = HashMap::new();
         for row in 0..n {
-            let year = year_arr.value(row);
-            let dept = if dept_arr.is_null(row) || dept_arr.value(row).trim().is_empty() {
-                "UNK".to_string()
-            } else {
-                dept_arr.value(row).trim().to_string()
-            };
-            idx_map.entry((year, dept)).or_default().push(row as u32);
+            let key: Key = part_strs
+                .iter()
+                .map(|a| {
+                    if a.is_null(row) || a.value(row).trim().is_empty() {
+                        "UNK".to_string()
+                    } else {
+                        a.value(row).trim().to_string()
+                    }
+                })
+                .collect();
+            idx_map.entry(key).or_default().push(row as u32);
         }
 
         // For each key, slice the batch by indices and push to partition buffers
@@ -137,16 +247,19 @@ pub async fn write_gold_snapshot(cfg: CurateConfig) -> Result<CurateStats> {
 
     // Write one Parquet file per partition
     let mut files: Vec<CommitFile> = Vec::new();
-    let mut files_written = 0u32;
-    let mut rows_written = 0u64;
 
-    for ((year, dept), batches) in parts.into_iter() {
+    for (key, batches) in parts.into_iter() {
         // directory: .../year=YYYY/dept=CC/
-        let dir = snapshot_dir
-            .join(format!("year={}", year))
-            .join(format!("dept={}", dept));
-        create_dir_all(&dir)?;
-        let file_path = dir.join("part-000000.parquet");
+        let rel = cfg
+            .partitions
+            .iter()
+            .zip(&key)
+            .fold(PathBuf::new(), |d, (p, v)| d.join(format!("{}={}", p.dir, v)))
+            .join("part-000000.parquet");
+        let file_path = snapshot_stage.dir().join(&rel);
+        if let Some(dir) = file_path.parent() {
+            create_dir_all(dir)?;
+        }
         let mut file =
             File::create(&file_path).with_context(|| format!("create {}", file_path.display()))?;
         let mut writer = ArrowWriter::try_new(&mut file, schema.clone(), Some(props.clone()))?;
@@ -157,56 +270,39 @@ pub async fn write_gold_snapshot(cfg: CurateConfig) -> Result<CurateStats> {
             writer.write(&b)?;
         }
         writer.close()?;
-        // Ensure flushed to disk
-        file.flush()?;
+        // Ensure on disk before the snapshot is committed
+        file.sync_all()?;
 
         files.push(CommitFile {
-            path: file_path.to_string_lossy().to_string(),
+            path: snapshot_dir.join(&rel).to_string_lossy().to_string(),
             rows: part_rows,
         });
-        files_written += 1;
-        rows_written += part_rows;
     }
 
-    // Write commit.json
-    let commit_dir = cfg
-        .storage_root
-        .join(&cfg.manifests_dir)
-        .join(&cfg.slug)
-        .join(format!("snapshot_date={}", cfg.snapshot_date));
-    create_dir_all(&commit_dir)?;
-    let commit_path = commit_dir.join("commit.json");
-    let commit = CommitJson {
-        dataset: cfg.slug.clone(),
-        snapshot_date: cfg.snapshot_date.clone(),
-        files,
-    };
-    {
-        let mut out = File::create(&commit_path)?;
-        let json = serde_json::to_string_pretty(&commit)?;
-        out.write_all(json.as_bytes())?;
-        out.flush()?;
-    }
+    // Move the snapshot into place
+    snapshot_stage.commit()?;
 
-    // Update latest.json atomically
-    let latest_dir = cfg.storage_root.join(&cfg.manifests_dir).join(&cfg.slug);
-    create_dir_all(&latest_dir)?;
-    let latest_tmp = latest_dir.join("latest.json.tmp");
-    let latest_path = latest_dir.join("latest.json");
-    {
-        let mut out = File::create(&latest_tmp)?;
-        let json = format!(r#"{{"snapshot_date":"{}"}}"#, cfg.snapshot_date);
-        out.write_all(json.as_bytes())?;
-        out.flush()?;
-    }
-    // best-effort atomic replace
-    std::fs::rename(&latest_tmp, &latest_path)
-        .or_else(|_| std::fs::copy(&latest_tmp, &latest_path).map(|_| ()))?;
+    Ok(Written { silver_parts, files })
+}
 
-    Ok(CurateStats {
-        files_written,
-        rows_written,
-        snapshot_dir,
-        commit_path,
-    })
+// ---------- small helpers
+
+/// `part-*.<ext>` files of a partition directory, in part order.
+fn list_parts(dir: &Path, ext: &str) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut parts = Vec::new();
+    for entry in read_dir(dir).with_context(|| format!("read {}", dir.display()))? {
+        let path = entry?.path();
+        let is_part = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("part-"));
+        if is_part && path.extension().is_some_and(|e| e == ext) {
+            parts.push(path);
+        }
+    }
+    parts.sort();
+    Ok(parts)
 }
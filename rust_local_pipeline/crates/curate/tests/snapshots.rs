@@ -0,0 +1,91 @@
+//! Gold snapshots across DVF drops: each drop republishes the earlier rows, validate keeps
+//! only the new ones in Silver, and every snapshot must still hold the whole dataset.
+use arrow::array::{Array, AsArray, RecordBatch};
+use arrow::compute::{cast, concat_batches};
+use arrow::datatypes::{DataType, Float64Type, Int32Type};
+use curate::{CurateConfig, CurateStats};
+use metadata::DatasetDescriptor;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use testkit::{Line, descriptor};
+
+/// One DVF line: a local of type `local` (`Appartement`, `Maison`, `Dépendance`) on `parcel`.
+fn line(mutation: &str, parcel: &str, local: &str, surface: &str, price: &str) -> Line {
+    let code = if local == "Dépendance" { "3" } else { "2" };
+    Line::sale(mutation)
+        .set("valeur_fonciere", price)
+        .set("id_parcelle", parcel)
+        .set("code_type_local", code)
+        .set("type_local", local)
+        .set("surface_reelle_bati", surface)
+}
+
+/// Ingests, validates and curates `lines` as the drop of `ingest_date`.
+async fn drop(desc: &DatasetDescriptor, root: &Path, ingest_date: &str, lines: &[Line]) -> CurateStats {
+    testkit::validate(desc, root, ingest_date, lines).await;
+    let cfg = CurateConfig::from_descriptor(desc, ingest_date.to_string(), ingest_date.to_string(), root.to_path_buf());
+    curate::write_gold_snapshot(cfg).await.unwrap()
+}
+
+/// Every Gold row of the table (`dvf` or `dvf_mutations`) listed by the snapshot.
+fn gold(stats: &CurateStats, table: &str) -> RecordBatch {
+    let files: Vec<&PathBuf> = stats
+        .files
+        .iter()
+        .filter(|f| f.components().any(|c| c.as_os_str() == table))
+        .collect();
+    let mut batches = Vec::new();
+    for f in files {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(f).unwrap()).unwrap().build().unwrap();
+        batches.extend(reader.map(Result::unwrap));
+    }
+    concat_batches(&batches[0].schema(), &batches).unwrap()
+}
+
+fn texts(batch: &RecordBatch, column: &str) -> Vec<String> {
+    let col = cast(batch.column_by_name(column).unwrap(), &DataType::Utf8).unwrap();
+    let mut v: Vec<String> = col.as_string::<i32>().iter().map(|s| s.unwrap_or_default().to_string()).collect();
+    v.sort();
+    v
+}
+
+#[tokio::test]
+async fn later_snapshots_keep_the_rows_of_earlier_drops() {
+    let root = tempfile::tempdir().unwrap();
+    let desc = descriptor();
+    let first = [
+        line("M1", "P1", "Appartement", "50", "300000"),
+        line("M1", "P2", "Dépendance", "", "300000"),
+        line("M2", "P3", "Maison", "100", "200000"),
+    ];
+    // the next drop republishes the first one, adds a line to M2 and a new mutation
+    let mut second = first.to_vec();
+    second.push(line("M2", "P4", "Maison", "20", "200000"));
+    second.push(line("M3", "P5", "Appartement", "40", "160000"));
+
+    let s1 = drop(&desc, root.path(), "2024-01-01", &first).await;
+    assert_eq!(s1.rows_written, 3);
+    let s2 = drop(&desc, root.path(), "2024-04-01", &second).await;
+    assert_eq!(s2.rows_written, 5);
+    assert_eq!(texts(&gold(&s2, "dvf"), "id_parcelle"), ["P1", "P2", "P3", "P4", "P5"]);
+
+    // the mutation table covers both drops: M2 is one row with both of its lines
+    let mutations = gold(&s2, "dvf_mutations");
+    assert_eq!(s2.mutation_rows_written, 3);
+    assert_eq!(texts(&mutations, "id_mutation"), ["M1", "M2", "M3"]);
+    let ids = mutations.column_by_name("id_mutation").unwrap().as_string::<i32>();
+    let m2 = (0..mutations.num_rows()).find(|&i| ids.value(i) == "M2").unwrap();
+    let rows = mutations.column_by_name("nb_lignes").unwrap().as_primitive::<Int32Type>();
+    let surface = mutations.column_by_name("surface_bati").unwrap().as_primitive::<Float64Type>();
+    let prix_m2 = mutations.column_by_name("prix_m2").unwrap().as_primitive::<Float64Type>();
+    assert_eq!(rows.value(m2), 2);
+    assert_eq!(surface.value(m2), 120.0);
+    assert!(prix_m2.is_valid(m2));
+    assert!((prix_m2.value(m2) - 200000.0 / 120.0).abs() < 1e-9);
+
+    // re-curating the first drop still gives its own, earlier view
+    let cfg = CurateConfig::from_descriptor(&desc, "2024-01-01".into(), "2024-01-02".into(), root.path().to_path_buf());
+    let again = curate::write_gold_snapshot(cfg).await.unwrap();
+    assert_eq!(texts(&gold(&again, "dvf"), "id_parcelle"), ["P1", "P2", "P3"]);
+}
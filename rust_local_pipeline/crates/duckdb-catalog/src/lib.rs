@@ -1,4 +1,5 @@
 use anyhow::{anyhow, Context, Result};
+use metadata::DatasetDescriptor;
 use serde::Deserialize;
 use std::fs::{read_to_string, write};
 use std::path::PathBuf;
@@ -17,6 +18,29 @@ pub struct RefreshCfg {
     pub gold_dir: String,
     pub snapshot_date: Option<String>,
     pub duckdb_path: PathBuf,
+    pub partition_dirs: Vec<String>, // hive columns added by read_parquet, e.g. ["year", "dept"]
+    pub mutations: bool, // the snapshot has a gold/<slug>_mutations counterpart (descriptor [mutations])
+}
+
+impl RefreshCfg {
+    /// Defaults taken from the dataset descriptor (Gold partition directories).
+    pub fn from_descriptor(
+        desc: &DatasetDescriptor,
+        snapshot_date: Option<String>,
+        duckdb_path: PathBuf,
+        storage_root: PathBuf,
+    ) -> Self {
+        Self {
+            slug: desc.slug.clone(),
+            storage_root,
+            manifests_dir: "manifests".to_string(),
+            gold_dir: "gold".to_string(),
+            snapshot_date,
+            duckdb_path,
+            partition_dirs: desc.partitions.iter().map(|p| p.dir.clone()).collect(),
+            mutations: desc.mutations.is_some(),
+        }
+    }
 }
 
 
@@ -37,18 +61,32 @@ pub fn refresh_duckdb(cfg: RefreshCfg) -> Result<()> {
         }
     };
 
-    // 2) Build glob to Parquet files
-    let parquet_glob_path = cfg
+    // 2) Build glob to Parquet files (of a committed snapshot only)
+    let snapshot_dir = cfg
         .storage_root
         .join(&cfg.gold_dir)
         .join(&cfg.slug)
-        .join(format!("snapshot_date={}", snapshot))
-        .join("**")
-        .join("*.parquet");
+        .join(format!("snapshot_date={}", snapshot));
+    metadata::staging::require_committed(&snapshot_dir)?;
+    let parquet_glob_path = snapshot_dir.join("**").join("*.parquet");
 
     let parquet_glob_norm = normalize_for_duckdb_path(&parquet_glob_path.to_string_lossy());
     let parquet_glob_sql = escape_single_quotes(&parquet_glob_norm);
 
+    // Mutation-level snapshot, curated alongside
+    let mutations_glob_sql = if cfg.mutations {
+        let dir = cfg
+            .storage_root
+            .join(&cfg.gold_dir)
+            .join(metadata::mutations_slug(&cfg.slug))
+            .join(format!("snapshot_date={}", snapshot));
+        metadata::staging::require_committed(&dir)?;
+        let glob = normalize_for_duckdb_path(&dir.join("**").join("*.parquet").to_string_lossy());
+        Some(escape_single_quotes(&glob))
+    } else {
+        None
+    };
+
     let ref_depts_path = cfg
         .storage_root
         .join(&cfg.gold_dir)
@@ -58,6 +96,12 @@ pub fn refresh_duckdb(cfg: RefreshCfg) -> Result<()> {
     let ref_depts_sql = escape_single_quotes(&ref_depts_norm);
 
 
+    let select_latest = if cfg.partition_dirs.is_empty() {
+        "*".to_string()
+    } else {
+        format!("* EXCLUDE ({})", cfg.partition_dirs.join(", "))
+    };
+
     let schema_name = cfg.gold_dir; // e.g. "gold"
     let view_latest = format!("{}_latest", cfg.slug);
     let view_transaction_latest = format!("{}_transaction_latest", cfg.slug);
@@ -71,7 +115,7 @@ pub fn refresh_duckdb(cfg: RefreshCfg) -> Result<()> {
     let view_points_ll = format!("{}_points_ll", cfg.slug); // lon/lat projection
     let view_dept_join = format!("{}_by_dept", cfg.slug); // choropleth-friendly agg
 
-    let sql = format!(
+    let mut sql = format!(
         r#"
 PRAGMA disable_progress_bar;
 INSTALL spatial; LOAD spatial;
@@ -83,7 +127,7 @@ SELECT * FROM read_parquet('{ref_depts}');
 CREATE SCHEMA IF NOT EXISTS {schema};
 
 CREATE OR REPLACE VIEW {schema}.{view_latest} AS
-SELECT * EXCLUDE (year, dept)
+SELECT {select_latest}
 FROM read_parquet('{glob}', union_by_name=true);
 
 
@@ -223,8 +267,13 @@ GROUP BY 1,2;
         view_dept_join = view_dept_join,
         glob = parquet_glob_sql,
         ref_depts = ref_depts_sql,
+        select_latest = select_latest,
     );
 
+    if let Some(glob) = &mutations_glob_sql {
+        sql.push_str(&mutation_views(&schema_name, &cfg.slug, glob, &select_latest));
+    }
+
     let init_sql = cfg.duckdb_path.with_extension("init.sql");
     write(&init_sql, sql.as_bytes()).with_context(|| format!("write {}", init_sql.display()))?;
 
@@ -242,6 +291,54 @@ GROUP BY 1,2;
     Ok(())
 }
 
+/// Views over the mutation table: one row per mutation, `prix_m2` over its summed built surface.
+fn mutation_views(schema: &str, slug: &str, glob: &str, select_latest: &str) -> String {
+    let view_mutations = format!("{slug}_mutations_latest");
+    let view_residential = format!("{slug}_mutation_residential_latest");
+    let view_metrics = format!("{slug}_mutation_price_metrics");
+    format!(
+        r#"
+-- 9) Mutations (une ligne par mutation ; prix_m2 = valeur_fonciere / surface bâtie totale)
+CREATE OR REPLACE VIEW {schema}.{view_mutations} AS
+SELECT {select_latest}
+FROM read_parquet('{glob}', union_by_name=true);
+
+-- 10) Mutations résidentielles + winsorisation p01–p99
+CREATE OR REPLACE VIEW {schema}.{view_residential} AS
+WITH base AS (
+  SELECT *
+  FROM {schema}.{view_mutations}
+  WHERE type_local_dominant IN ('APPARTEMENT','MAISON')
+    AND prix_m2 IS NOT NULL AND prix_m2 > 0
+),
+q AS (
+  SELECT
+    year_mutation, code_departement, type_local_dominant,
+    quantile_cont(prix_m2, 0.01) AS p01,
+    quantile_cont(prix_m2, 0.99) AS p99
+  FROM base
+  GROUP BY 1,2,3
+)
+SELECT
+  b.*,
+  GREATEST(LEAST(b.prix_m2, q.p99), q.p01) AS prix_m2_winsor
+FROM base b
+JOIN q USING (year_mutation, code_departement, type_local_dominant);
+
+CREATE OR REPLACE VIEW {schema}.{view_metrics} AS
+SELECT
+  year_mutation, code_departement, type_local_dominant AS type_local,
+  COUNT(*)::BIGINT                     AS n_mutations,
+  COUNT(*) FILTER (WHERE multi_parcelles)::BIGINT AS n_multi_parcelles,
+  median(valeur_fonciere)::DOUBLE      AS median_valeur_fonciere,
+  median(prix_m2)::DOUBLE              AS median_prix_m2,
+  median(prix_m2_winsor)::DOUBLE       AS median_prix_m2_w
+FROM {schema}.{view_residential}
+GROUP BY 1,2,3;
+"#
+    )
+}
+
 fn escape_single_quotes(s: &str) -> String {
     s.replace('\'', "''")
 }
@@ -0,0 +1,73 @@
+//! CSV -> Bronze throughput: the sequential reader (`parse_threads = 1`) against
+//! chunked parsing on 2, 4 and all cores, over a synthetic file laid out like the
+//! DVF descriptor. `BENCH_ROWS` sets its size (default 200_000 rows).
+//!
+//!     cargo bench -p ingest --bench csv_ingest
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use metadata::{ColumnSpec, ColumnType};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+fn write_source(path: &Path, columns: &[ColumnSpec], rows: usize) -> u64 {
+    let mut w = BufWriter::new(std::fs::File::create(path).expect("create bench CSV"));
+    let header: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+    writeln!(w, "{}", header.join(",")).unwrap();
+    for i in 0..rows {
+        let fields: Vec<String> = columns
+            .iter()
+            .enumerate()
+            .map(|(j, c)| match c.ty {
+                ColumnType::Date => format!("2023-{:02}-{:02}", i % 12 + 1, i % 28 + 1),
+                ColumnType::Decimal { .. } => format!("{}.{:02}", 50_000 + i * 7 % 900_000, i % 100),
+                ColumnType::Float64 => format!("{:.6}", 2.0 + (i % 10_000) as f64 / 1e4),
+                ColumnType::Int16 | ColumnType::Int32 | ColumnType::Int64 => (i % 1000).to_string(),
+                // some quoted fields with delimiters inside, as in the real files
+                _ if j % 7 == 0 => format!("\"RUE {i}, BAT {}\"", i % 5),
+                _ if i % 11 == 0 => String::new(),
+                _ => format!("{}-{}", c.name.to_uppercase(), i % 97),
+            })
+            .collect();
+        writeln!(w, "{}", fields.join(",")).unwrap();
+    }
+    w.flush().unwrap();
+    std::fs::metadata(path).unwrap().len()
+}
+
+fn csv_ingest(c: &mut Criterion) {
+    let config_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../config/datasets");
+    let desc = metadata::load_descriptor_from(&config_dir, "dvf").expect("load dvf descriptor");
+    let rows: usize = std::env::var("BENCH_ROWS").ok().and_then(|v| v.parse().ok()).unwrap_or(200_000);
+
+    let root: PathBuf = std::env::temp_dir().join(format!("ingest-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let source = root.join("dvf.csv");
+    let bytes = write_source(&source, &desc.columns, rows);
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("csv_ingest");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(bytes));
+    for threads in [1, 2, 4, 0] {
+        let id = match threads {
+            1 => "sequential".to_string(),
+            0 => "all_cores".to_string(),
+            n => format!("{n}_threads"),
+        };
+        group.bench_with_input(BenchmarkId::from_parameter(id), &threads, |b, &threads| {
+            b.iter(|| {
+                let mut cfg = ingest::IngestConfig::from_descriptor(&desc, "2024-01-01".into(), root.clone())
+                    .expect("ingest config");
+                cfg.parse_threads = threads;
+                cfg.force = true; // same content every iteration
+                let st = rt.block_on(ingest::ingest_dataset(cfg, &source)).expect("ingest");
+                assert_eq!(st.rows_in, rows as u64);
+            })
+        });
+    }
+    group.finish();
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+criterion_group!(benches, csv_ingest);
+criterion_main!(benches);
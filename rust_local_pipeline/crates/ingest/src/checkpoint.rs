@@ -0,0 +1,83 @@
+//! Ingest progress, saved each time a Bronze part is completed so an interrupted
+//! ingest can be resumed (`IngestConfig::resume`) instead of started over.
+//!
+//! Layout: `_checkpoint.json` in the staging directory of the Bronze partition
+//! (`bronze/<slug>/_staging/<run_id>/ingest_date=.../`), removed before the
+//! partition is committed.
+//! It lists the completed parts and streams, and where the stream being read stands:
+//! the byte offset, line and record number of the first record not yet in a
+//! completed part. Offsets are in the decoded (UTF-8) stream.
+use crate::drift::DriftReport;
+use crate::source::Dialect;
+use crate::FileStats;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{remove_file, rename, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub(crate) const CHECKPOINT_FILE: &str = "_checkpoint.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Checkpoint {
+    pub ingest_date: String,
+    pub format: String,
+    pub sources: Vec<String>, // BLAKE3 of each source being ingested, in order
+    pub parts: usize,         // completed Bronze parts
+    pub error_parts: usize,   // completed bronze_errors parts
+    pub headers: Vec<String>, // Bronze source columns (after drift renames)
+    pub dialect: Option<Dialect>,
+    pub renames: HashMap<String, String>,
+    pub drift: Option<DriftReport>,
+    pub files: Vec<FileStats>, // streams read to the end, all rows in completed parts
+    pub stream: Option<StreamPos>,
+}
+
+/// The first record of a stream that is not in a completed part.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct StreamPos {
+    pub label: String,
+    pub headers: Vec<String>, // as read from the source (before drift renames)
+    pub dialect: Option<Dialect>,
+    pub byte: u64,
+    pub line: u64,
+    pub record: i64,      // records before it, quarantined ones included
+    pub quarantined: u64, // of those records
+}
+
+pub(crate) fn path(out_dir: &Path) -> PathBuf {
+    out_dir.join(CHECKPOINT_FILE)
+}
+
+pub(crate) fn load(out_dir: &Path) -> Result<Option<Checkpoint>> {
+    let path = path(out_dir);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let f = File::open(&path).with_context(|| format!("open {}", path.display()))?;
+    let cp = serde_json::from_reader(f).with_context(|| format!("parse {}", path.display()))?;
+    Ok(Some(cp))
+}
+
+/// Replaces the checkpoint atomically (temp file + rename).
+pub(crate) fn save(out_dir: &Path, cp: &Checkpoint) -> Result<()> {
+    let path = path(out_dir);
+    let tmp = path.with_extension("json.tmp");
+    {
+        let mut out = BufWriter::new(File::create(&tmp).with_context(|| format!("create {}", tmp.display()))?);
+        serde_json::to_writer_pretty(&mut out, cp)?;
+        out.flush()?;
+        out.get_ref().sync_all()?;
+    }
+    rename(&tmp, &path).with_context(|| format!("rename {}", tmp.display()))?;
+    Ok(())
+}
+
+pub(crate) fn remove(out_dir: &Path) -> Result<()> {
+    let path = path(out_dir);
+    if path.exists() {
+        remove_file(&path).with_context(|| format!("remove {}", path.display()))?;
+    }
+    Ok(())
+}
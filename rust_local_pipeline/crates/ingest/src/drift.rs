@@ -0,0 +1,213 @@
+//! Header drift between consecutive ingests: added, removed, renamed-looking and
+//! reordered source columns, compared with the previous Bronze partition (or the
+//! descriptor when there is none).
+use anyhow::{Context, Result};
+use arrow::ipc::reader::FileReader;
+use metadata::staging;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{read_dir, remove_file, File};
+use std::path::{Path, PathBuf};
+
+/// Bronze columns added by ingest, not part of the source header.
+pub(crate) const LINEAGE_COLUMNS: [&str; 4] = ["ingest_date", "source_file", "source_blake3", "row_number"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DriftReport {
+    /// What the header was compared with: `ingest_date=YYYY-MM-DD` or `descriptor`.
+    pub baseline: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub renamed: Vec<Rename>,
+    /// Columns present in both whose position changed.
+    pub reordered: Vec<String>,
+    /// Renames were mapped back to the baseline names in Bronze (`alias` policy).
+    pub mapped: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rename {
+    pub from: String, // baseline name
+    pub to: String,   // name in the new header
+    pub similarity: f64,
+    pub by_alias: bool, // declared in the descriptor rather than matched by name
+}
+
+impl DriftReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.renamed.is_empty() && self.reordered.is_empty()
+    }
+
+    /// Drift that changes which columns Bronze holds (reordering alone does not).
+    pub fn is_breaking(&self) -> bool {
+        !self.added.is_empty() || !self.removed.is_empty() || (!self.renamed.is_empty() && !self.mapped)
+    }
+
+    pub fn summary(&self) -> String {
+        let renamed: Vec<String> = self.renamed.iter().map(|r| format!("{}->{}", r.from, r.to)).collect();
+        format!(
+            "vs {}: added=[{}] removed=[{}] renamed=[{}] reordered=[{}]",
+            self.baseline,
+            self.added.join(","),
+            self.removed.join(","),
+            renamed.join(","),
+            self.reordered.join(",")
+        )
+    }
+}
+
+/// Source columns of the latest committed Bronze partition before `ingest_date`, if any.
+pub(crate) fn previous_header(dataset_dir: &Path, ingest_date: &str) -> Result<Option<(String, Vec<String>)>> {
+    if !dataset_dir.exists() {
+        return Ok(None);
+    }
+    let mut dates: Vec<(String, PathBuf)> = Vec::new();
+    for entry in read_dir(dataset_dir).with_context(|| format!("read {}", dataset_dir.display()))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(d) = name.strip_prefix("ingest_date=")
+            && d < ingest_date
+            && staging::is_committed(&entry.path())
+        {
+            dates.push((d.to_string(), entry.path()));
+        }
+    }
+    // newest first; partitions without parts (e.g. an unchanged ingest) are passed over
+    dates.sort();
+    for (d, dir) in dates.into_iter().rev() {
+        let Some(part) = first_part(&dir)? else { continue };
+        let f = File::open(&part).with_context(|| format!("open {}", part.display()))?;
+        let schema = FileReader::try_new(f, None)?.schema();
+        let header = schema
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .filter(|n| !LINEAGE_COLUMNS.contains(&n.as_str()))
+            .collect();
+        return Ok(Some((format!("ingest_date={d}"), header)));
+    }
+    Ok(None)
+}
+
+fn first_part(dir: &Path) -> Result<Option<PathBuf>> {
+    let mut parts: Vec<PathBuf> = read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|p| {
+            p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("part-"))
+                && p.extension().is_some_and(|e| e == "arrow")
+        })
+        .collect();
+    parts.sort();
+    Ok(parts.into_iter().next())
+}
+
+/// Compares `header` with `baseline`. `aliases` maps alternative names to baseline
+/// names; otherwise an added/removed pair at least `min_similarity` alike is a rename.
+pub(crate) fn compare(
+    baseline_name: &str,
+    baseline: &[String],
+    header: &[String],
+    aliases: &HashMap<String, String>,
+    min_similarity: f64,
+) -> DriftReport {
+    let mut added: Vec<String> = header.iter().filter(|h| !baseline.contains(h)).cloned().collect();
+    let mut removed: Vec<String> = baseline.iter().filter(|b| !header.contains(b)).cloned().collect();
+
+    // Declared aliases first, then the closest names, each column used once
+    let mut renamed: Vec<Rename> = Vec::new();
+    for a in added.clone() {
+        if let Some(to) = aliases.get(&a)
+            && let Some(i) = removed.iter().position(|r| r == to)
+        {
+            let from = removed.remove(i);
+            renamed.push(Rename { similarity: similarity(&from, &a), from, to: a.clone(), by_alias: true });
+            added.retain(|x| *x != a);
+        }
+    }
+    let mut pairs: Vec<(f64, String, String)> = added
+        .iter()
+        .flat_map(|a| removed.iter().map(move |r| (similarity(r, a), r.clone(), a.clone())))
+        .filter(|(s, _, _)| *s >= min_similarity)
+        .collect();
+    pairs.sort_by(|x, y| y.0.total_cmp(&x.0));
+    for (s, from, to) in pairs {
+        if removed.contains(&from) && added.contains(&to) {
+            removed.retain(|x| *x != from);
+            added.retain(|x| *x != to);
+            renamed.push(Rename { from, to, similarity: s, by_alias: false });
+        }
+    }
+
+    // Relative order of the columns both headers share (renames count as shared)
+    let renamed_to: HashMap<&str, &str> = renamed.iter().map(|r| (r.to.as_str(), r.from.as_str())).collect();
+    let new_order: Vec<&str> = header
+        .iter()
+        .map(|h| renamed_to.get(h.as_str()).copied().unwrap_or(h))
+        .filter(|h| baseline.iter().any(|b| b == h))
+        .collect();
+    let old_order: Vec<&str> = baseline
+        .iter()
+        .map(String::as_str)
+        .filter(|b| new_order.contains(b))
+        .collect();
+    let reordered = old_order
+        .iter()
+        .zip(&new_order)
+        .filter(|(o, n)| o != n)
+        .map(|(_, n)| n.to_string())
+        .collect();
+
+    DriftReport {
+        baseline: baseline_name.to_string(),
+        added,
+        removed,
+        renamed,
+        reordered,
+        mapped: false,
+    }
+}
+
+/// Writes the report next to the other side outputs, or clears a stale one.
+pub(crate) fn write_report(path: &Path, report: Option<&DriftReport>) -> Result<()> {
+    match report {
+        Some(r) => {
+            staging::write_file_atomic(path, &serde_json::to_vec_pretty(r)?)?;
+        }
+        None if path.exists() => remove_file(path).with_context(|| format!("remove {}", path.display()))?,
+        None => {}
+    }
+    Ok(())
+}
+
+/// 1 - normalised edit distance between the names, ignoring case and punctuation
+/// (`Valeur fonciere` and `valeur_fonciere` are identical).
+fn similarity(a: &str, b: &str) -> f64 {
+    let norm = |s: &str| -> Vec<char> {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (norm(a), norm(b));
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let sub = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = sub.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,264 @@
+//! Non-CSV source readers: Parquet, NDJSON and XLSX. Each lays its rows out as
+//! Utf8 source columns, like the CSV path, so Bronze looks the same whatever the format.
+use crate::checkpoint::StreamPos;
+use crate::source::{self, BoxedReader};
+use crate::{Output, ReadCounts, BATCH_SIZE};
+use anyhow::{anyhow, Context, Result};
+use arrow::array::{Array, ArrayRef, StringArray};
+use arrow::compute::{can_cast_types, cast};
+use arrow::datatypes::DataType;
+use arrow::record_batch::RecordBatchReader;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use calamine::{open_workbook, Data, Reader, Xlsx};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::Value;
+use std::fs::File;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+// ---------- Parquet
+
+/// Every column rendered as text; row_number is the 1-based row in the file.
+pub(crate) fn read_parquet(path: &Path, label: &str, hash: &str, out: &mut Output<'_>) -> Result<ReadCounts> {
+    let f = File::open(path).with_context(|| format!("open {label}"))?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(f)
+        .with_context(|| format!("read Parquet footer of {label}"))?
+        .with_batch_size(BATCH_SIZE)
+        .build()?;
+    let headers: Vec<String> = reader.schema().fields().iter().map(|f| f.name().clone()).collect();
+    out.open(headers, None, label)?;
+
+    let mut counts = ReadCounts::default();
+    for batch in reader {
+        let batch = batch.with_context(|| format!("read {label}"))?;
+        let cols: Vec<StringArray> = batch.columns().iter().map(to_utf8).collect::<Result<_>>()?;
+        for row in 0..batch.num_rows() {
+            counts.rows += 1;
+            let fields = cols.iter().map(|c| (!c.is_null(row)).then(|| c.value(row)));
+            out.push(fields, label, hash, counts.rows as i64)?;
+        }
+    }
+    Ok(counts)
+}
+
+/// Casts scalar columns to Utf8; binary and nested values use Arrow's display
+/// format (hex bytes, `{k: v}` structs, `[..]` lists).
+fn to_utf8(col: &ArrayRef) -> Result<StringArray> {
+    let binary = matches!(
+        col.data_type(),
+        DataType::Binary | DataType::LargeBinary | DataType::BinaryView | DataType::FixedSizeBinary(_)
+    );
+    if !binary && can_cast_types(col.data_type(), &DataType::Utf8) {
+        let arr = cast(col, &DataType::Utf8)?;
+        return arr
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .cloned()
+            .ok_or_else(|| anyhow!("cast to Utf8 returned {}", arr.data_type()));
+    }
+    let fmt = ArrayFormatter::try_new(col.as_ref(), &FormatOptions::default())?;
+    Ok((0..col.len())
+        .map(|i| (!col.is_null(i)).then(|| fmt.value(i).to_string()))
+        .collect())
+}
+
+// ---------- NDJSON
+
+/// One JSON object per line. Columns are `columns` when given, otherwise the keys
+/// of the first object (sorted); keys outside them are dropped. Lines that are not
+/// UTF-8, not JSON or not an object are quarantined. `resume_at` continues the
+/// stream from its checkpoint.
+pub(crate) async fn read_ndjson(
+    reader: BoxedReader,
+    columns: &[String],
+    label: &str,
+    hash: &str,
+    resume_at: Option<&StreamPos>,
+    out: &mut Output<'_>,
+) -> Result<ReadCounts> {
+    let (reader, mut headers) = match resume_at {
+        Some(pos) => {
+            out.open(pos.headers.clone(), None, label)?;
+            (source::skip(reader, pos.byte, label).await?, Some(pos.headers.clone()))
+        }
+        None => (reader, None),
+    };
+    let mut reader = BufReader::new(reader);
+    let (mut record_no, mut counts) = ReadCounts::resumed(resume_at);
+    let mut dropped_keys: u64 = 0;
+
+    let mut buf = Vec::new();
+    let mut offset: i64 = resume_at.map_or(0, |p| p.byte as i64);
+    let mut line_no: i64 = resume_at.map_or(0, |p| p.line as i64 - 1);
+    loop {
+        buf.clear();
+        let n = reader.read_until(b'\n', &mut buf).await.with_context(|| format!("read {label}"))?;
+        if n == 0 {
+            break;
+        }
+        let (start, line) = (offset, line_no + 1);
+        offset += n as i64;
+        line_no += 1;
+        let bytes = buf.strip_prefix(b"\xEF\xBB\xBF".as_slice()).unwrap_or(&buf);
+        if bytes.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        // row_number counts records (non-blank lines), quarantined ones included
+        out.at_record(start as u64, line as u64, record_no, counts.quarantined)?;
+        record_no += 1;
+
+        let text = match std::str::from_utf8(bytes) {
+            Ok(t) => t.trim_end(),
+            Err(e) => {
+                let raw = String::from_utf8_lossy(bytes);
+                out.quarantine(label, Some(line), Some(start), "INVALID_UTF8", &e.to_string(), Some(raw.trim_end()))?;
+                counts.quarantined += 1;
+                continue;
+            }
+        };
+        let obj = match serde_json::from_str::<Value>(text) {
+            Ok(Value::Object(obj)) => obj,
+            Ok(other) => {
+                let detail = format!("expected a JSON object, got {}", json_kind(&other));
+                out.quarantine(label, Some(line), Some(start), "NOT_AN_OBJECT", &detail, Some(text))?;
+                counts.quarantined += 1;
+                continue;
+            }
+            Err(e) => {
+                out.quarantine(label, Some(line), Some(start), "INVALID_JSON", &e.to_string(), Some(text))?;
+                counts.quarantined += 1;
+                continue;
+            }
+        };
+
+        let headers = match headers.as_ref() {
+            Some(h) => h,
+            None => {
+                let h: Vec<String> = if columns.is_empty() {
+                    obj.keys().cloned().collect()
+                } else {
+                    columns.to_vec()
+                };
+                out.open(h.clone(), None, label)?;
+                headers.insert(h)
+            }
+        };
+        if obj.keys().any(|k| !headers.contains(k)) {
+            dropped_keys += 1;
+        }
+        let values: Vec<Option<String>> = headers
+            .iter()
+            .map(|h| match obj.get(h) {
+                None | Some(Value::Null) => None,
+                Some(Value::String(s)) => Some(s.clone()),
+                Some(v) => Some(v.to_string()),
+            })
+            .collect();
+        counts.rows += 1;
+        out.push(values.iter().map(|v| v.as_deref()), label, hash, record_no)?;
+    }
+
+    if headers.is_none() {
+        return Err(anyhow!("no JSON object found in {label}"));
+    }
+    if dropped_keys > 0 {
+        tracing::warn!(file = %label, rows = dropped_keys, "keys outside the Bronze columns were dropped");
+    }
+    Ok(counts)
+}
+
+fn json_kind(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+// ---------- XLSX
+
+/// The first row of `sheet` (default: the first worksheet) is the header. Blank rows
+/// are skipped; rows holding an Excel error value (`#N/A`, `#REF!`, ...) are quarantined.
+pub(crate) fn read_xlsx(
+    path: &Path,
+    sheet: Option<&str>,
+    label: &str,
+    hash: &str,
+    out: &mut Output<'_>,
+) -> Result<ReadCounts> {
+    let mut wb: Xlsx<_> = open_workbook(path).map_err(|e| anyhow!("open workbook {label}: {e:?}"))?;
+    let name = match sheet {
+        Some(s) => s.to_string(),
+        None => wb
+            .sheet_names()
+            .first()
+            .cloned()
+            .ok_or_else(|| anyhow!("no worksheet in {label}"))?,
+    };
+    let range = wb
+        .worksheet_range(&name)
+        .map_err(|e| anyhow!("read worksheet {name} of {label}: {e:?}"))?;
+    // sheet row of the range's first row (0-based)
+    let first_row = range.start().map_or(0, |(r, _)| r as i64);
+
+    let mut rows = range.rows();
+    let mut headers: Vec<String> = rows
+        .next()
+        .ok_or_else(|| anyhow!("worksheet {name} of {label} is empty"))?
+        .iter()
+        .map(|c| cell_text(c).unwrap_or_default().trim().to_string())
+        .collect();
+    // the used range often runs past the last named column
+    while headers.last().is_some_and(String::is_empty) {
+        headers.pop();
+    }
+    let width = headers.len();
+    out.open(headers.clone(), None, label)?;
+
+    let source_file = format!("{label}!{name}");
+    let mut counts = ReadCounts::default();
+    let mut record_no: i64 = 0;
+    for (i, row) in rows.enumerate() {
+        if row.iter().all(|c| matches!(c, Data::Empty)) {
+            continue;
+        }
+        record_no += 1;
+        let cells = &row[..width.min(row.len())];
+        if let Some((col, err)) = cells.iter().enumerate().find_map(|(j, c)| match c {
+            Data::Error(e) => Some((j, e)),
+            _ => None,
+        }) {
+            let raw: Vec<String> = cells.iter().map(|c| cell_text(c).unwrap_or_default()).collect();
+            let detail = format!("{err} in column {}", headers[col]);
+            // header is sheet row first_row + 1 (1-based), data starts right after it
+            let line = first_row + i as i64 + 2;
+            out.quarantine(&source_file, Some(line), None, "CELL_ERROR", &detail, Some(&raw.join("\t")))?;
+            counts.quarantined += 1;
+            continue;
+        }
+        let values: Vec<Option<String>> = cells.iter().map(cell_text).collect();
+        counts.rows += 1;
+        out.push(values.iter().map(|v| v.as_deref()), &source_file, hash, record_no)?;
+    }
+    Ok(counts)
+}
+
+/// Cell value as Bronze text. Whole floats lose their `.0` (codes such as `75056`
+/// are stored as numbers); dates are ISO 8601.
+fn cell_text(c: &Data) -> Option<String> {
+    match c {
+        Data::Empty | Data::Error(_) => None,
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => Some(s.clone()),
+        Data::Int(v) => Some(v.to_string()),
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => Some((*f as i64).to_string()),
+        Data::Float(f) => Some(f.to_string()),
+        Data::Bool(b) => Some(b.to_string()),
+        Data::DateTime(dt) => dt.as_datetime().map(|d| {
+            let fmt = if dt.as_f64().fract() == 0.0 { "%Y-%m-%d" } else { "%Y-%m-%dT%H:%M:%S" };
+            d.format(fmt).to_string()
+        }),
+    }
+}
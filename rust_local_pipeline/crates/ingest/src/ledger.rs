@@ -0,0 +1,135 @@
+//! Per-dataset record of the source files already ingested, keyed by content hash.
+//!
+//! Layout: `<root>/<ledger_dir>/<slug>/ingest_ledger.jsonl`, one JSON entry per
+//! source file and `ingest_date`. Re-ingesting a date replaces that date's entries.
+use crate::source::SourceFile;
+use crate::store;
+use metadata::staging;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const LEDGER_FILE: &str = "ingest_ledger.jsonl";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub source_file: String,
+    pub blake3: String, // hex, of the bytes on disk (before decompression)
+    pub size: u64,
+    pub mtime: Option<i64>, // seconds since the Unix epoch
+    #[serde(default)]
+    pub e_tag: Option<String>, // object sources only
+    pub ingest_date: String,
+}
+
+pub(crate) struct Ledger {
+    path: PathBuf,
+    entries: Vec<LedgerEntry>,
+}
+
+impl Ledger {
+    pub(crate) fn open(dataset_dir: &Path) -> Result<Self> {
+        let path = dataset_dir.join(LEDGER_FILE);
+        let mut entries = Vec::new();
+        if path.exists() {
+            let f = File::open(&path).with_context(|| format!("open {}", path.display()))?;
+            for (i, line) in BufReader::new(f).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let entry: LedgerEntry = serde_json::from_str(&line)
+                    .with_context(|| format!("parse {} line {}", path.display(), i + 1))?;
+                entries.push(entry);
+            }
+        }
+        Ok(Self { path, entries })
+    }
+
+    /// Earliest entry with this content under another `ingest_date`.
+    pub(crate) fn find(&self, blake3: &str, ingest_date: &str) -> Option<&LedgerEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.blake3 == blake3 && e.ingest_date != ingest_date)
+            .min_by(|a, b| a.ingest_date.cmp(&b.ingest_date))
+    }
+
+    /// Hash of an object recorded earlier with the same name, size and ETag.
+    fn known_hash(&self, source_file: &str, size: u64, e_tag: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|e| e.source_file == source_file && e.size == size && e.e_tag.as_deref() == Some(e_tag))
+            .map(|e| e.blake3.as_str())
+    }
+
+    /// Replaces the entries of `ingest_date` with `entries` (synced tmp file + rename).
+    pub(crate) fn record(&mut self, ingest_date: &str, entries: Vec<LedgerEntry>) -> Result<()> {
+        self.entries.retain(|e| e.ingest_date != ingest_date);
+        self.entries.extend(entries);
+
+        let mut out = Vec::new();
+        for e in &self.entries {
+            serde_json::to_writer(&mut out, e)?;
+            out.push(b'\n');
+        }
+        staging::write_file_atomic(&self.path, &out)
+    }
+}
+
+/// Hashes a source file and reads its size and mtime. An object whose name, size
+/// and ETag are already in the ledger reuses the recorded hash instead of being read.
+pub(crate) async fn fingerprint(src: &SourceFile, ingest_date: &str, ledger: &Ledger) -> Result<LedgerEntry> {
+    let (store, meta, label) = match src {
+        SourceFile::Local(path) => return fingerprint_file(path, ingest_date).await,
+        SourceFile::Object { store, meta, label } => (store, meta, label),
+    };
+    let known = meta
+        .e_tag
+        .as_deref()
+        .and_then(|tag| ledger.known_hash(label, meta.size, tag));
+    let hash = match known {
+        Some(hash) => hash.to_string(),
+        None => store::hash_object(store.as_ref(), meta)
+            .await
+            .with_context(|| format!("hash {label}"))?,
+    };
+    Ok(LedgerEntry {
+        source_file: label.clone(),
+        blake3: hash,
+        size: meta.size,
+        mtime: Some(meta.last_modified.timestamp()),
+        e_tag: meta.e_tag.clone(),
+        ingest_date: ingest_date.to_string(),
+    })
+}
+
+async fn fingerprint_file(path: &Path, ingest_date: &str) -> Result<LedgerEntry> {
+    let p = path.to_path_buf();
+    let hash = tokio::task::spawn_blocking(move || -> Result<String> {
+        let f = File::open(&p).with_context(|| format!("open {}", p.display()))?;
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_reader(f)?;
+        Ok(hasher.finalize().to_hex().to_string())
+    })
+    .await??;
+
+    let meta = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("stat {}", path.display()))?;
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64);
+    Ok(LedgerEntry {
+        source_file: path.display().to_string(),
+        blake3: hash,
+        size: meta.len(),
+        mtime,
+        e_tag: None,
+        ingest_date: ingest_date.to_string(),
+    })
+}
@@ -3,180 +3,1306 @@ use arrow::ipc::writer::FileWriter;
 use arrow::datatypes::{DataType, Field, Schema};
 use csv_async::{AsyncReaderBuilder, StringRecord};
 use futures::StreamExt;
-use std::fs::{create_dir_all, File};
+use metadata::staging::{self, StagedPartition};
+use metadata::{DatasetDescriptor, DriftPolicy, DriftSpec};
+use object_store::{ObjectStore, ObjectStoreScheme};
+use serde::{Deserialize, Serialize};
+use url::Url;
+use encoding_rs::Encoding;
+use std::collections::HashMap;
+use std::fs::{read_dir, remove_file, rename, File};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use arrow::array::{ArrayRef, Int64Array, Int64Builder, RecordBatch, StringArray, StringBuilder};
-use tokio::io::BufReader;
 
+mod checkpoint;
+mod drift;
+mod formats;
+mod ledger;
+mod parallel;
+mod source;
+mod store;
+
+pub use drift::{DriftReport, Rename};
+pub use ledger::LedgerEntry;
+pub use source::{Compression, Dialect};
+use checkpoint::{Checkpoint, StreamPos};
+use source::SourceFile;
+
+/// Where `source` lives. Object-store kinds take builder options such as
+/// `aws_endpoint` / `aws_allow_http` (MinIO) or `google_service_account`.
 #[derive(Debug, Clone)]
 pub enum SourceKind {
     LocalFile,
+    /// `s3://bucket/key` (or an S3-compatible endpoint)
+    S3 { options: Vec<(String, String)> },
+    /// `gs://bucket/key`
+    Gcs { options: Vec<(String, String)> },
+    /// `az://container/key`, `abfss://...`, `https://<account>.blob.core.windows.net/...`
+    Azure { options: Vec<(String, String)> },
+    /// `http(s)://host/path` (directories need a WebDAV listing)
+    Http { options: Vec<(String, String)> },
+    /// A ready-made store, e.g. `LocalFileSystem` or `InMemory`; `source` is a path inside it.
+    Store(Arc<dyn ObjectStore>),
+}
+
+impl SourceKind {
+    /// Picks the kind from the URL scheme of `source`; anything that is not a URL is
+    /// a local path. Store options are read from `AWS_*`, `GOOGLE_*` and `AZURE_*`
+    /// environment variables.
+    pub fn for_source(source: &str) -> Result<Self> {
+        let Ok(url) = Url::parse(source) else {
+            return Ok(SourceKind::LocalFile);
+        };
+        // `C:\data` parses as a URL with scheme `c`
+        if url.scheme().len() == 1 {
+            return Ok(SourceKind::LocalFile);
+        }
+        let options = store::options_from_env();
+        let (scheme, _) = ObjectStoreScheme::parse(&url)
+            .with_context(|| format!("unsupported source URL: {source}"))?;
+        Ok(match scheme {
+            ObjectStoreScheme::AmazonS3 => SourceKind::S3 { options },
+            ObjectStoreScheme::GoogleCloudStorage => SourceKind::Gcs { options },
+            ObjectStoreScheme::MicrosoftAzure => SourceKind::Azure { options },
+            ObjectStoreScheme::Http => SourceKind::Http { options },
+            other => return Err(anyhow!("unsupported source URL scheme {other:?}: {source}")),
+        })
+    }
+}
+
+/// Source file format, from the descriptor's `[source] format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceFormat {
+    Csv,
+    Parquet,
+    Ndjson,
+    Xlsx,
+}
+
+impl SourceFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "csv" => SourceFormat::Csv,
+            "parquet" => SourceFormat::Parquet,
+            "ndjson" | "jsonl" => SourceFormat::Ndjson,
+            "xlsx" => SourceFormat::Xlsx,
+            other => return Err(anyhow!("unsupported source format: {other}")),
+        })
+    }
+}
+
+impl std::fmt::Display for SourceFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SourceFormat::Csv => "csv",
+            SourceFormat::Parquet => "parquet",
+            SourceFormat::Ndjson => "ndjson",
+            SourceFormat::Xlsx => "xlsx",
+        })
+    }
+}
+
+/// What to do when every source file's content was already ingested under another date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnUnchanged {
+    Skip,
+    Fail,
 }
 
 #[derive(Debug, Clone)]
 pub struct IngestConfig {
     pub slug: String,
     pub source: SourceKind,
+    pub format: SourceFormat,
+    pub sheet: Option<String>, // XLSX worksheet; None = first
+    pub columns: Vec<String>,  // descriptor columns, the NDJSON layout (empty = keys of the first object)
     pub ingest_date: String, // "YYYY-MM-DD"
     pub storage_root: PathBuf, // e.g., "./data"
     pub bronze_dir: String,    // e.g., "bronze"
+    pub delimiter: Option<u8>, // e.g., Some(b','); None = sniff per file
+    pub quote: Option<u8>,     // e.g., Some(b'"'); None = sniff per file
+    pub encoding: String,      // e.g., "utf-8", "windows-1252" (transcoded to UTF-8)
+    pub max_rows_per_part: u64,  // e.g., 5_000_000 (0 = unbounded)
+    pub max_bytes_per_part: u64, // e.g., 512 MiB of field data (0 = unbounded)
+    pub errors_dir: String,      // e.g., "bronze_errors"
+    pub max_error_ratio: f64,    // e.g., 0.01; quarantined / records above this fails the ingest
+    pub ledger_dir: String,      // e.g., "ledger"
+    pub on_unchanged: OnUnchanged, // e.g., Skip
+    pub force: bool,             // ingest even when the content hash is already in the ledger
+    pub drift: DriftSpec,        // e.g., policy Warn, min_similarity 0.8
+    pub aliases: HashMap<String, String>, // alternative source name -> column name
+    pub drift_dir: String,       // e.g., "drift"
+    pub parse_threads: usize,    // CSV parser threads, e.g., 0 = one per core, 1 = sequential reader
+    pub resume: bool,            // continue from the partition's checkpoint instead of starting over
+    pub stop_after_parts: Option<usize>, // fail once this many parts are checkpointed, as a crash would (resume tests)
+    pub run_id: String,          // e.g., "20241017T093012Z-1f3a9c2e"; names the staging directory
+}
+
+impl IngestConfig {
+    /// Defaults taken from the dataset descriptor (format, delimiter, encoding).
+    pub fn from_descriptor(
+        desc: &DatasetDescriptor,
+        ingest_date: String,
+        storage_root: PathBuf,
+    ) -> Result<Self> {
+        let format = SourceFormat::parse(&desc.source.format)?;
+        encoding_for(&desc.source.encoding)?;
+        Ok(Self {
+            slug: desc.slug.clone(),
+            source: SourceKind::LocalFile,
+            format,
+            sheet: desc.source.sheet.clone(),
+            columns: desc.columns.iter().map(|c| c.name.clone()).collect(),
+            ingest_date,
+            storage_root,
+            bronze_dir: "bronze".to_string(),
+            delimiter: desc.delimiter()?,
+            quote: desc.quote()?,
+            encoding: desc.source.encoding.clone(),
+            max_rows_per_part: DEFAULT_MAX_ROWS_PER_PART,
+            max_bytes_per_part: DEFAULT_MAX_BYTES_PER_PART,
+            errors_dir: "bronze_errors".to_string(),
+            max_error_ratio: DEFAULT_MAX_ERROR_RATIO,
+            ledger_dir: "ledger".to_string(),
+            on_unchanged: OnUnchanged::Skip,
+            force: false,
+            drift: desc.drift.clone(),
+            aliases: desc
+                .columns
+                .iter()
+                .flat_map(|c| c.aliases.iter().map(|a| (a.clone(), c.name.clone())))
+                .collect(),
+            drift_dir: "drift".to_string(),
+            parse_threads: DEFAULT_PARSE_THREADS,
+            resume: false,
+            stop_after_parts: None,
+            run_id: staging::new_run_id(),
+        })
+    }
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct IngestStats {
     pub rows_in: u64,
-    pub bytes_in: u64,
+    pub quarantined: u64, // malformed records sent to bronze_errors (not in rows_in)
+    pub bytes_in: u64,           // on disk (compressed when the source is)
+    pub bytes_uncompressed: u64, // decoded CSV/NDJSON bytes
     pub parts_written: u32,
-    pub out_path: PathBuf,
+    pub resumed_parts: u32, // parts kept from the interrupted run (resume)
+    pub out_dir: PathBuf,
+    pub parts: Vec<PathBuf>,
+    pub errors_out: PathBuf, // bronze_errors partition directory
+    pub files: Vec<FileStats>, // one entry per stream (file or zip member), in ingest order
+    pub skipped: Vec<SkippedSource>, // every source, when all are unchanged since an earlier ingest_date
+    pub drift: Option<DriftReport>,  // header drift vs the previous ingest (or the descriptor)
+    pub run_id: String, // the interrupted run's when resuming, else cfg.run_id
+}
+
+impl IngestStats {
+    /// Every source was already ingested: nothing was written.
+    pub fn unchanged(&self) -> bool {
+        self.files.is_empty() && !self.skipped.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SkippedSource {
+    pub path: PathBuf,
+    pub blake3: String,
+    pub ingested_on: String, // ingest_date that already holds this content
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileStats {
+    pub path: PathBuf, // `archive.zip!member.csv` for zip members
+    pub compression: Compression,
+    pub dialect: Option<Dialect>, // CSV only
+    pub blake3: String, // of the source file on disk (shared by zip members)
+    pub rows_in: u64,
+    pub quarantined: u64,
+    pub bytes_in: u64,
+    pub bytes_uncompressed: u64,
 }
 
 const BATCH_SIZE: usize = 65_536;
+pub const DEFAULT_MAX_ROWS_PER_PART: u64 = 5_000_000;
+pub const DEFAULT_MAX_BYTES_PER_PART: u64 = 512 * 1024 * 1024;
+pub const DEFAULT_MAX_ERROR_RATIO: f64 = 0.01;
+pub const DEFAULT_PARSE_THREADS: usize = 0;
+
+/// Ingests `source` (a CSV file, a directory of files, or a glob such as
+/// `./raw/dvf_*.csv`) into one Bronze `ingest_date` partition. Gzip, zstd and
+/// zip sources are decompressed on the fly. With an object-store `cfg.source`,
+/// `source` is a URL naming an object, a prefix, or a `*` glob over keys.
+///
+/// Each source file is hashed (BLAKE3) and checked against the dataset's ingest
+/// ledger. When every source was already ingested under another `ingest_date`,
+/// nothing is written and the run is skipped or fails (`cfg.on_unchanged`) unless
+/// `cfg.force` is set; when only some were, all of them are ingested.
+pub async fn ingest_dataset(cfg: IngestConfig, source: &Path) -> Result<IngestStats> {
+    let sources: Vec<SourceFile> = match &cfg.source {
+        SourceKind::LocalFile => resolve_sources(source)?.into_iter().map(SourceFile::Local).collect(),
+        kind => {
+            let url = source
+                .to_str()
+                .ok_or_else(|| anyhow!("source URL is not valid UTF-8: {}", source.display()))?;
+            store::resolve_objects(kind, url).await?
+        }
+    };
+    let encoding = encoding_for(&cfg.encoding)?;
+
+    let partition = format!("ingest_date={}", cfg.ingest_date);
+    let bronze_dataset = cfg.storage_root.join(&cfg.bronze_dir).join(&cfg.slug);
+    let errors_dataset = cfg.storage_root.join(&cfg.errors_dir).join(&cfg.slug);
+    let out_dir = bronze_dataset.join(&partition);
 
-pub async fn ingest_dataset(cfg: IngestConfig, source_path: &Path) -> Result<IngestStats> {
-    if !source_path.exists() {
-        return Err(anyhow!("source file not found: {}", source_path.display()));
+    // Content hashes vs the ledger
+    let ledger_dir = cfg.storage_root.join(&cfg.ledger_dir).join(&cfg.slug);
+    let mut ledger = ledger::Ledger::open(&ledger_dir)?;
+    let mut fingerprints: Vec<(&SourceFile, LedgerEntry)> = Vec::with_capacity(sources.len());
+    let mut skipped: Vec<SkippedSource> = Vec::new();
+    for src in &sources {
+        let fp = ledger::fingerprint(src, &cfg.ingest_date, &ledger).await?;
+        if !cfg.force
+            && let Some(prior) = ledger.find(&fp.blake3, &cfg.ingest_date)
+        {
+            tracing::info!(file = %fp.source_file, blake3 = %fp.blake3, ingested_on = %prior.ingest_date, "unchanged source");
+            skipped.push(SkippedSource {
+                path: PathBuf::from(&fp.source_file),
+                blake3: fp.blake3.clone(),
+                ingested_on: prior.ingest_date.clone(),
+            });
+        }
+        fingerprints.push((src, fp));
+    }
+    // The drop is skipped only as a whole: with one changed file, the unchanged ones are
+    // ingested with it so the partition holds the full drop (validate reports their rows
+    // as DUPLICATE_PRIOR_INGEST).
+    if skipped.len() == fingerprints.len() {
+        if !skipped.is_empty() && cfg.on_unchanged == OnUnchanged::Fail {
+            let list: Vec<String> = skipped
+                .iter()
+                .map(|s| format!("{} (ingest_date={})", s.path.display(), s.ingested_on))
+                .collect();
+            return Err(anyhow!(
+                "source content already ingested: {} (use --force to ingest again)",
+                list.join(", ")
+            ));
+        }
+        // Nothing new: leave Bronze untouched
+        return Ok(IngestStats { out_dir, skipped, run_id: cfg.run_id, ..Default::default() });
+    }
+    if !skipped.is_empty() {
+        tracing::info!(unchanged = skipped.len(), sources = fingerprints.len(), "ingesting unchanged sources with the changed ones");
+        skipped.clear();
     }
 
-    let out_dir = cfg
+    let sources: Vec<String> = fingerprints.iter().map(|(_, fp)| fp.blake3.clone()).collect();
+    // Resuming continues in the staging directory of the latest interrupted run
+    let mut resumed = None;
+    let mut run_id = cfg.run_id.clone();
+    if cfg.resume {
+        for (id, dir) in staging::staged_runs(&bronze_dataset, &partition)?.into_iter().rev() {
+            if let Some(cp) = checkpoint::load(&dir)? {
+                resumed = Some((cp, dir));
+                run_id = id;
+                break;
+            }
+        }
+    }
+    match &resumed {
+        Some((cp, dir)) if cp.ingest_date != cfg.ingest_date || cp.format != cfg.format.to_string() || cp.sources != sources => {
+            return Err(anyhow!(
+                "checkpoint in {} is for other sources; ingest without --resume to start over",
+                dir.display()
+            ));
+        }
+        Some((cp, dir)) => tracing::info!(dir = %dir.display(), parts = cp.parts, "resuming ingest"),
+        None if cfg.resume => tracing::warn!(partition = %out_dir.display(), "no checkpoint to resume from, starting over"),
+        None => {}
+    }
+    // Bronze and its quarantine are written aside and moved into place on success
+    let (staged, staged_errors) = if resumed.is_some() {
+        (
+            StagedPartition::reopen(&bronze_dataset, &partition, &run_id)?,
+            StagedPartition::reopen(&errors_dataset, &partition, &run_id)?,
+        )
+    } else {
+        (
+            StagedPartition::create(&bronze_dataset, &partition, &run_id)?,
+            StagedPartition::create(&errors_dataset, &partition, &run_id)?,
+        )
+    };
+    let resumed = resumed.map(|(cp, _)| cp).unwrap_or_default();
+    // parts the checkpoint does not list are redone
+    remove_parts(staged.dir(), resumed.parts)?;
+    remove_parts(staged_errors.dir(), resumed.error_parts)?;
+    let drift_path = cfg
         .storage_root
-        .join(&cfg.bronze_dir)
+        .join(&cfg.drift_dir)
         .join(&cfg.slug)
-        .join(format!("ingest_date={}", cfg.ingest_date));
-    create_dir_all(&out_dir).with_context(|| format!("mkdir -p {}", out_dir.display()))?;
+        .join(format!("ingest_date={}", cfg.ingest_date))
+        .join("drift.json");
+    let sink = if resumed.headers.is_empty() {
+        None
+    } else {
+        let headers = resumed.headers.clone();
+        Some(BronzeSink::new(headers, resumed.dialect, encoding, &cfg, staged.dir(), resumed.parts)?)
+    };
+    let mut out = Output {
+        cfg: &cfg,
+        out_dir: staged.dir(),
+        encoding,
+        sink,
+        errors: ErrorSink::new(staged_errors.dir().to_path_buf(), resumed.error_parts)?,
+        drift_path: &drift_path,
+        drift: resumed.drift.clone(),
+        renames: resumed.renames.clone(),
+        // a sequential reader is cheaper when a single thread would parse anyway
+        pool: match cfg.format {
+            SourceFormat::Csv if cfg.parse_threads != 1 => Some(parallel::pool(cfg.parse_threads)?),
+            _ => None,
+        },
+        files: Vec::with_capacity(fingerprints.len()),
+        stream: None,
+        part_done: false,
+        checkpoint: Checkpoint {
+            ingest_date: cfg.ingest_date.clone(),
+            format: cfg.format.to_string(),
+            sources,
+            ..Default::default()
+        },
+    };
 
-    let out_path = out_dir.join("part-000000.arrow");
+    let mut bytes_in: u64 = 0;
 
-    let f = tokio::fs::File::open(source_path).await?;
-    let meta = f.metadata().await?;
-    let bytes_in = meta.len() as u64;
-    let mut rdr = AsyncReaderBuilder::new()
-        .has_headers(true)
-        .delimiter(b',')
-        .flexible(true)
-        .create_reader(BufReader::new(f));
+    // remote zip, Parquet and XLSX files are copied here while they are read
+    let spool_dir = cfg.storage_root.join("_spool");
+
+    for (src, fp) in &fingerprints {
+        let file_bytes = fp.size;
+        bytes_in += file_bytes;
+        let first_stream = out.files.len();
+        let label = src.label();
 
-    // Read header (column names)
-    let headers: StringRecord = rdr.headers().await?.clone();
-    if headers.is_empty() {
-        return Err(anyhow!("empty CSV header in {}", source_path.display()));
+        // Resuming: sources read to the end before the interruption are not read again
+        let resume_at = resumed.stream.as_ref().filter(|p| is_stream_of(&p.label, &label));
+        let done: Vec<&FileStats> = resumed
+            .files
+            .iter()
+            .filter(|f| is_stream_of(&f.path.to_string_lossy(), &label))
+            .collect();
+        if resume_at.is_none() && !done.is_empty() {
+            for f in done {
+                out.stream_done(f.clone())?;
+            }
+            continue;
+        }
+
+        match cfg.format {
+            SourceFormat::Csv | SourceFormat::Ndjson => {
+                let compression = source::detect(src).await?;
+                for stream in source::open_streams(src, compression, &spool_dir).await? {
+                    if let Some(f) = done.iter().find(|f| f.path.to_string_lossy() == stream.label) {
+                        out.stream_done((*f).clone())?;
+                        continue;
+                    }
+                    let resume_at = resume_at.filter(|p| p.label == stream.label);
+                    let reader = source::transcode(stream.reader, encoding);
+                    let (dialect, counts) = if cfg.format == SourceFormat::Csv {
+                        let (dialect, counts) =
+                            read_csv(reader, &stream.label, &fp.blake3, resume_at, &mut out).await?;
+                        (Some(dialect), counts)
+                    } else {
+                        let counts = formats::read_ndjson(
+                            reader,
+                            &cfg.columns,
+                            &stream.label,
+                            &fp.blake3,
+                            resume_at,
+                            &mut out,
+                        )
+                        .await?;
+                        (None, counts)
+                    };
+
+                    let uncompressed = stream.uncompressed.load(Ordering::Relaxed);
+                    tracing::info!(
+                        file = %stream.label,
+                        rows = counts.rows,
+                        quarantined = counts.quarantined,
+                        bytes_uncompressed = uncompressed,
+                        %compression,
+                        "ingested"
+                    );
+                    out.stream_done(FileStats {
+                        path: PathBuf::from(&stream.label),
+                        compression,
+                        dialect,
+                        blake3: fp.blake3.clone(),
+                        rows_in: counts.rows,
+                        quarantined: counts.quarantined,
+                        // on-disk size is per file: a zip's size goes to its first member
+                        bytes_in: if out.files.len() == first_stream { file_bytes } else { 0 },
+                        bytes_uncompressed: uncompressed,
+                    })?;
+                }
+            }
+            SourceFormat::Parquet | SourceFormat::Xlsx => {
+                // both need random access: objects are spooled to local disk first
+                let (path, _spool) = source::local_copy(src, &spool_dir).await?;
+                let counts = if cfg.format == SourceFormat::Parquet {
+                    formats::read_parquet(&path, &label, &fp.blake3, &mut out)?
+                } else {
+                    formats::read_xlsx(&path, cfg.sheet.as_deref(), &label, &fp.blake3, &mut out)?
+                };
+                tracing::info!(file = %label, rows = counts.rows, quarantined = counts.quarantined, format = %cfg.format, "ingested");
+                out.stream_done(FileStats {
+                    path: PathBuf::from(&label),
+                    compression: Compression::None,
+                    dialect: None,
+                    blake3: fp.blake3.clone(),
+                    rows_in: counts.rows,
+                    quarantined: counts.quarantined,
+                    bytes_in: file_bytes,
+                    bytes_uncompressed: 0,
+                })?;
+            }
+        }
     }
-    let src_col_count = headers.len();
+    let files = out.files;
+    let rows_in: u64 = files.iter().map(|f| f.rows_in).sum();
+    let quarantined: u64 = files.iter().map(|f| f.quarantined).sum();
+    let bytes_uncompressed: u64 = files.iter().map(|f| f.bytes_uncompressed).sum();
+
+    let sink = out
+        .sink
+        .ok_or_else(|| anyhow!("no {} streams found in {}", cfg.format, source.display()))?;
+    let parts = sink.finish()?;
+    out.errors.finish()?;
+    // every part is complete: nothing left to resume
+    checkpoint::remove(staged.dir())?;
+    let drift = out.drift;
 
-    // Build Arrow schema: all source columns Utf8 (nullable) + lineage
-    let mut fields: Vec<Field> = headers
+    // The quarantine is published either way; Bronze only when within the error budget
+    let errors_dir = staged_errors.commit()?;
+    let records = rows_in + quarantined;
+    if records > 0 && quarantined as f64 / records as f64 > cfg.max_error_ratio {
+        staged.discard()?;
+        return Err(anyhow!(
+            "{quarantined} of {records} records quarantined (max ratio {}), see {}",
+            cfg.max_error_ratio,
+            errors_dir.display()
+        ));
+    }
+    let parts: Vec<PathBuf> = parts
         .iter()
-        .map(|name| Field::new(name.trim(), DataType::Utf8, true))
+        .map(|p| out_dir.join(p.file_name().unwrap_or_default()))
         .collect();
-    fields.push(Field::new("ingest_date", DataType::Utf8, false));
-    fields.push(Field::new("source_file", DataType::Utf8, false));
-    fields.push(Field::new("row_number", DataType::Int64, false));
-    let schema = Arc::new(Schema::new(fields));
+    staged.commit()?;
+    ledger.record(&cfg.ingest_date, fingerprints.into_iter().map(|(_, fp)| fp).collect())?;
+
+    Ok(IngestStats {
+        rows_in,
+        quarantined,
+        bytes_in,
+        bytes_uncompressed,
+        parts_written: parts.len() as u32,
+        resumed_parts: resumed.parts as u32,
+        out_dir,
+        parts,
+        errors_out: errors_dir,
+        files,
+        skipped,
+        drift,
+        run_id,
+    })
+}
+
+/// Whether `stream` (`file`, or `archive.zip!member` for zip members) comes from `source`.
+fn is_stream_of(stream: &str, source: &str) -> bool {
+    stream
+        .strip_prefix(source)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('!'))
+}
 
-    // IPC writer (sync file)
-    let out_file = File::create(&out_path)
-        .with_context(|| format!("create {}", out_path.display()))?;
-    let mut writer = FileWriter::try_new(out_file, &schema)?;
+/// Expands `source` into the files to ingest, sorted by path:
+/// a file as-is, every regular file of a directory, or the matches of a glob.
+pub fn resolve_sources(source: &Path) -> Result<Vec<PathBuf>> {
+    let mut out: Vec<PathBuf> = if source.is_file() {
+        vec![source.to_path_buf()]
+    } else if source.is_dir() {
+        read_dir(source)
+            .with_context(|| format!("read {}", source.display()))?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|p| p.is_file())
+            .filter(|p| !p.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')))
+            .collect()
+    } else {
+        let pattern = source.to_string_lossy();
+        glob::glob(&pattern)
+            .with_context(|| format!("invalid source pattern {pattern}"))?
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|p| p.is_file())
+            .collect()
+    };
+    if out.is_empty() {
+        return Err(anyhow!("no source files found for {}", source.display()));
+    }
+    out.sort();
+    Ok(out)
+}
+
+
+/// Rows and quarantined records read from one stream.
+#[derive(Debug, Default, Clone, Copy)]
+struct ReadCounts {
+    rows: u64,
+    quarantined: u64,
+}
+
+impl ReadCounts {
+    /// Records before the first one to read, and what they counted for.
+    fn resumed(resume_at: Option<&StreamPos>) -> (i64, Self) {
+        resume_at.map_or((0, Self::default()), |p| {
+            let rows = p.record as u64 - p.quarantined;
+            (p.record, Self { rows, quarantined: p.quarantined })
+        })
+    }
+}
+
+/// Bronze sink and quarantine shared by the readers of one ingest.
+struct Output<'a> {
+    cfg: &'a IngestConfig,
+    out_dir: &'a Path,
+    encoding: &'static Encoding,
+    // Created from the first header seen; every other stream must match it
+    sink: Option<BronzeSink>,
+    errors: ErrorSink,
+    drift_path: &'a Path,
+    drift: Option<DriftReport>,
+    renames: HashMap<String, String>, // new header name -> Bronze name (alias policy)
+    pool: Option<rayon::ThreadPool>,  // CSV parser threads; None = sequential reader
+    files: Vec<FileStats>,            // streams read to the end
+    // Current stream: label, source header and dialect (for checkpoints)
+    stream: Option<(String, Vec<String>, Option<Dialect>)>,
+    part_done: bool, // a Bronze part was completed since the last checkpoint
+    checkpoint: Checkpoint,
+}
+
+impl Output<'_> {
+    /// Starts a stream with these source columns.
+    fn open(&mut self, headers: Vec<String>, dialect: Option<Dialect>, label: &str) -> Result<()> {
+        self.stream = Some((label.to_string(), headers.clone(), dialect));
+        let headers = if self.sink.is_none() {
+            self.check_drift(headers, label)?
+        } else {
+            headers
+                .into_iter()
+                .map(|h| self.renames.get(&h).cloned().unwrap_or(h))
+                .collect()
+        };
+        match self.sink.as_ref() {
+            Some(s) => {
+                if s.headers != headers {
+                    return Err(anyhow!("header of {label} differs from the first source"));
+                }
+                if s.dialect != dialect {
+                    tracing::warn!(file = %label, ?dialect, "dialect differs from the first source");
+                }
+            }
+            None => {
+                if headers.is_empty() {
+                    return Err(anyhow!("no source columns in {label}"));
+                }
+                let sink = BronzeSink::new(headers, dialect, self.encoding, self.cfg, self.out_dir, 0)?;
+                self.sink = Some(sink);
+            }
+        }
+        Ok(())
+    }
+
+    /// Compares the first header with the previous ingest (or the descriptor) and
+    /// applies the drift policy; returns the header Bronze will use.
+    fn check_drift(&mut self, headers: Vec<String>, label: &str) -> Result<Vec<String>> {
+        let cfg = self.cfg;
+        let dataset_dir = cfg.storage_root.join(&cfg.bronze_dir).join(&cfg.slug);
+        let baseline = match drift::previous_header(&dataset_dir, &cfg.ingest_date)? {
+            Some(b) => b,
+            None if !cfg.columns.is_empty() => ("descriptor".to_string(), cfg.columns.clone()),
+            None => return Ok(headers),
+        };
+        let mut report = drift::compare(&baseline.0, &baseline.1, &headers, &cfg.aliases, cfg.drift.min_similarity);
+        if report.is_empty() {
+            drift::write_report(self.drift_path, None)?;
+            return Ok(headers);
+        }
+
+        if cfg.drift.policy == DriftPolicy::Alias && !report.renamed.is_empty() {
+            self.renames = report.renamed.iter().map(|r| (r.to.clone(), r.from.clone())).collect();
+            report.mapped = true;
+        }
+        drift::write_report(self.drift_path, Some(&report))?;
+        if cfg.drift.policy == DriftPolicy::Fail && report.is_breaking() {
+            return Err(anyhow!(
+                "schema drift in {label} {} (see {})",
+                report.summary(),
+                self.drift_path.display()
+            ));
+        }
+        tracing::warn!(file = %label, policy = ?cfg.drift.policy, "schema drift {}", report.summary());
+        self.drift = Some(report);
+        Ok(headers
+            .into_iter()
+            .map(|h| self.renames.get(&h).cloned().unwrap_or(h))
+            .collect())
+    }
+
+    fn push<'v>(
+        &mut self,
+        fields: impl IntoIterator<Item = Option<&'v str>>,
+        source_file: &str,
+        source_hash: &str,
+        row_number: i64,
+    ) -> Result<()> {
+        let sink = self.sink.as_mut().ok_or_else(|| anyhow!("row pushed before its header"))?;
+        self.part_done |= sink.push(fields, source_file, source_hash, row_number)?;
+        Ok(())
+    }
+
+    /// How many of the next rows (by field bytes) fit before the sink would flush.
+    fn rows_fitting(&self, row_bytes: &[u64]) -> Result<usize> {
+        let sink = self.sink.as_ref().ok_or_else(|| anyhow!("rows pushed before their header"))?;
+        Ok(sink.rows_fitting(row_bytes))
+    }
+
+    /// `rows[range]`, parsed off the async task; row numbers are offset by `row_base`.
+    fn push_slice(
+        &mut self,
+        rows: &SourceRows,
+        range: Range<usize>,
+        row_base: i64,
+        source_file: &str,
+        source_hash: &str,
+    ) -> Result<()> {
+        let sink = self.sink.as_mut().ok_or_else(|| anyhow!("rows pushed before their header"))?;
+        self.part_done |= sink.push_slice(rows, range, row_base, source_file, source_hash)?;
+        Ok(())
+    }
+
+    /// Called at the start of each record of a CSV or NDJSON stream (`record`
+    /// records and `quarantined` of them before it): saves a checkpoint there
+    /// when a part was completed since the last one.
+    fn at_record(&mut self, byte: u64, line: u64, record: i64, quarantined: u64) -> Result<()> {
+        if !self.part_done {
+            return Ok(());
+        }
+        let Some((label, headers, dialect)) = self.stream.clone() else {
+            return Ok(());
+        };
+        self.save_checkpoint(Some(StreamPos { label, headers, dialect, byte, line, record, quarantined }))
+    }
+
+    /// Records a stream read to the end; saves a checkpoint when a part was
+    /// completed and none of the rows read since waits in an open one.
+    fn stream_done(&mut self, stats: FileStats) -> Result<()> {
+        self.files.push(stats);
+        self.stream = None;
+        if self.part_done && self.sink.as_ref().is_some_and(|s| !s.has_open_part()) {
+            self.save_checkpoint(None)?;
+        }
+        Ok(())
+    }
+
+    fn save_checkpoint(&mut self, stream: Option<StreamPos>) -> Result<()> {
+        let sink = self.sink.as_ref().ok_or_else(|| anyhow!("checkpoint before the first header"))?;
+        // quarantined records up to here go in a completed part as well
+        self.errors.commit()?;
+        let cp = &mut self.checkpoint;
+        cp.parts = sink.writer.parts.len();
+        cp.error_parts = self.errors.parts;
+        cp.headers = sink.headers.clone();
+        cp.dialect = sink.dialect;
+        cp.renames = self.renames.clone();
+        cp.drift = self.drift.clone();
+        cp.files = self.files.clone();
+        cp.stream = stream;
+        checkpoint::save(self.out_dir, cp)?;
+        self.part_done = false;
+        if let Some(n) = self.cfg.stop_after_parts
+            && cp.parts >= n
+        {
+            return Err(anyhow!("stopped after {} completed parts (stop_after_parts)", cp.parts));
+        }
+        Ok(())
+    }
 
-    // Batch builders
-    let mut col_builders: Vec<StringBuilder> =
-        (0..src_col_count).map(|_| StringBuilder::with_capacity(BATCH_SIZE, BATCH_SIZE * 8)).collect();
-    let mut ingest_date_builder = StringBuilder::with_capacity(BATCH_SIZE, BATCH_SIZE * 12);
-    let mut source_file_builder = StringBuilder::with_capacity(BATCH_SIZE, BATCH_SIZE * 32);
-    let mut rownum_builder = Int64Builder::with_capacity(BATCH_SIZE);
+    fn quarantine(
+        &mut self,
+        source_file: &str,
+        line_number: Option<i64>,
+        byte_offset: Option<i64>,
+        code: &str,
+        detail: &str,
+        raw: Option<&str>,
+    ) -> Result<()> {
+        self.errors.push(source_file, line_number, byte_offset, code, detail, raw)
+    }
+}
 
-    let mut rows_in: u64 = 0;
-    let source_file_str = source_path.display().to_string();
+/// Reads one decoded CSV stream (header + records) into `out`, on the parser
+/// threads when there are several. `resume_at` continues a stream from its checkpoint.
+async fn read_csv(
+    reader: source::BoxedReader,
+    label: &str,
+    hash: &str,
+    resume_at: Option<&StreamPos>,
+    out: &mut Output<'_>,
+) -> Result<(Dialect, ReadCounts)> {
+    let (dialect, reader) = match resume_at {
+        Some(pos) => {
+            let dialect = pos.dialect.ok_or_else(|| anyhow!("checkpoint of {label} has no CSV dialect"))?;
+            (dialect, source::skip(reader, pos.byte, label).await?)
+        }
+        None => source::resolve_dialect(reader, out.cfg.delimiter, out.cfg.quote).await?,
+    };
+    if let Some(pool) = out.pool.take() {
+        let counts = parallel::read_csv(reader, dialect, resume_at, &pool, label, hash, out).await;
+        out.pool = Some(pool);
+        return Ok((dialect, counts?));
+    }
+    let mut rdr = AsyncReaderBuilder::new()
+        .has_headers(resume_at.is_none())
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .flexible(true)
+        .create_reader(reader);
 
-    let mut records = rdr.records();
+    let headers: Vec<String> = match resume_at {
+        Some(pos) => pos.headers.clone(),
+        None => rdr
+            .headers()
+            .await?
+            .iter()
+            .map(|h| h.trim_start_matches('\u{feff}').trim().to_string())
+            .collect(),
+    };
+    let expected = headers.len();
+    out.open(headers, Some(dialect), label)?;
 
+    // row_number is the 1-based data record within its own source stream,
+    // quarantined records included, so it keeps pointing at the source
+    let (mut record_no, mut counts) = ReadCounts::resumed(resume_at);
+    // (line, byte) of a record in the stream; the reader counts from where it started
+    let (base_line, base_byte) = resume_at.map_or((0, 0), |p| (p.line - 1, p.byte));
+    let at = |line: u64, byte: u64| (base_line + line, base_byte + byte);
+    let mut records = rdr.byte_records();
     while let Some(rec_res) = records.next().await {
-        let rec = rec_res?;
-        rows_in += 1;
-
-        for (i, bldr) in col_builders.iter_mut().enumerate() {
-            let val_opt = rec.get(i).map(|s| s.trim());
-            if let Some(v) = val_opt {
-                if v.is_empty() {
-                    bldr.append_null();
-                } else {
+        let rec = match rec_res {
+            Ok(rec) => rec,
+            Err(e) if e.is_io_error() => {
+                return Err(e).with_context(|| format!("read {label}"));
+            }
+            Err(e) => {
+                let start = e.position().map(|p| at(p.line(), p.byte()));
+                if let Some((line, byte)) = start {
+                    out.at_record(byte, line, record_no, counts.quarantined)?;
+                }
+                record_no += 1;
+                let (line, offset) = start.map_or((None, None), |(l, b)| (Some(l as i64), Some(b as i64)));
+                out.quarantine(label, line, offset, "CSV_PARSE_ERROR", &e.to_string(), None)?;
+                counts.quarantined += 1;
+                continue;
+            }
+        };
+        let start = rec.position().map(|p| at(p.line(), p.byte()));
+        if let Some((line, byte)) = start {
+            out.at_record(byte, line, record_no, counts.quarantined)?;
+        }
+        record_no += 1;
+        let (line, offset) = start.map_or((None, None), |(l, b)| (Some(l as i64), Some(b as i64)));
+        if rec.len() != expected {
+            let raw = raw_line(rec.iter(), dialect.delimiter);
+            let detail = format!("expected {expected} fields, got {}", rec.len());
+            out.quarantine(label, line, offset, "FIELD_COUNT_MISMATCH", &detail, Some(&raw))?;
+            counts.quarantined += 1;
+            continue;
+        }
+        match StringRecord::from_byte_record(rec) {
+            Ok(rec) => {
+                counts.rows += 1;
+                out.push(rec.iter().map(Some), label, hash, record_no)?;
+            }
+            Err(e) => {
+                let detail = e.utf8_error().to_string();
+                let raw = raw_line(e.into_byte_record().iter(), dialect.delimiter);
+                out.quarantine(label, line, offset, "INVALID_UTF8", &detail, Some(&raw))?;
+                counts.quarantined += 1;
+            }
+        }
+    }
+    Ok((dialect, counts))
+}
+
+/// Record fields re-joined with the delimiter (quoting is not preserved).
+fn raw_line<'a>(fields: impl Iterator<Item = &'a [u8]>, delimiter: u8) -> String {
+    let mut out = Vec::new();
+    for (i, f) in fields.enumerate() {
+        if i > 0 {
+            out.push(delimiter);
+        }
+        out.extend_from_slice(f);
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn encoding_for(label: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| anyhow!("unsupported source encoding: {label}"))
+}
+
+// ---------- Bronze sink
+
+/// Source columns built off the async task, with each row's record number
+/// (1-based within its chunk) and field bytes.
+struct SourceRows {
+    columns: Vec<ArrayRef>,
+    row_numbers: Vec<i64>,
+    row_bytes: Vec<u64>,
+}
+
+/// Batches records into Arrow arrays (all source columns Utf8 + lineage) and
+/// hands them to the part writer.
+struct BronzeSink {
+    headers: Vec<String>,
+    dialect: Option<Dialect>,
+    schema: Arc<Schema>,
+    ingest_date: String,
+    writer: PartWriter,
+    col_builders: Vec<StringBuilder>,
+    ingest_date_builder: StringBuilder,
+    source_file_builder: StringBuilder,
+    source_hash_builder: StringBuilder,
+    rownum_builder: Int64Builder,
+    pending: usize,
+    pending_bytes: u64,
+}
+
+impl BronzeSink {
+    /// `done_parts` parts of an interrupted ingest are kept; writing goes on after them.
+    fn new(
+        headers: Vec<String>,
+        dialect: Option<Dialect>,
+        encoding: &'static Encoding,
+        cfg: &IngestConfig,
+        out_dir: &Path,
+        done_parts: usize,
+    ) -> Result<Self> {
+        // Build Arrow schema: all source columns Utf8 (nullable) + lineage
+        let mut fields: Vec<Field> = headers
+            .iter()
+            .map(|name| Field::new(name, DataType::Utf8, true))
+            .collect();
+        fields.push(Field::new("ingest_date", DataType::Utf8, false));
+        fields.push(Field::new("source_file", DataType::Utf8, false));
+        fields.push(Field::new("source_blake3", DataType::Utf8, false));
+        fields.push(Field::new("row_number", DataType::Int64, false));
+        // What the reader was given, for downstream stages
+        let mut meta = HashMap::from([("ingest.format".to_string(), cfg.format.to_string())]);
+        if matches!(cfg.format, SourceFormat::Csv | SourceFormat::Ndjson) {
+            meta.insert("ingest.encoding".to_string(), encoding.name().to_string());
+        }
+        if let Some(dialect) = dialect {
+            meta.extend([
+                ("ingest.delimiter".to_string(), (dialect.delimiter as char).to_string()),
+                ("ingest.quote".to_string(), (dialect.quote as char).to_string()),
+                (
+                    "ingest.dialect".to_string(),
+                    if dialect.sniffed { "sniffed" } else { "configured" }.to_string(),
+                ),
+            ]);
+        }
+        let schema = Arc::new(Schema::new_with_metadata(fields, meta));
+
+        // IPC writer (sync file), rolls over to the next part at the configured bounds
+        let writer = PartWriter::new(
+            out_dir.to_path_buf(),
+            schema.clone(),
+            cfg.max_rows_per_part,
+            cfg.max_bytes_per_part,
+            done_parts,
+        )?;
+        let col_builders = (0..headers.len())
+            .map(|_| StringBuilder::with_capacity(BATCH_SIZE, BATCH_SIZE * 8))
+            .collect();
+        Ok(Self {
+            headers,
+            dialect,
+            schema,
+            ingest_date: cfg.ingest_date.clone(),
+            writer,
+            col_builders,
+            ingest_date_builder: StringBuilder::with_capacity(BATCH_SIZE, BATCH_SIZE * 12),
+            source_file_builder: StringBuilder::with_capacity(BATCH_SIZE, BATCH_SIZE * 32),
+            source_hash_builder: StringBuilder::with_capacity(BATCH_SIZE, BATCH_SIZE * 64),
+            rownum_builder: Int64Builder::with_capacity(BATCH_SIZE),
+            pending: 0,
+            pending_bytes: 0,
+        })
+    }
+
+    /// One row of source fields, in header order (missing trailing fields are null).
+    /// Returns whether a part was completed.
+    fn push<'v>(
+        &mut self,
+        fields: impl IntoIterator<Item = Option<&'v str>>,
+        source_file: &str,
+        source_hash: &str,
+        row_number: i64,
+    ) -> Result<bool> {
+        self.pending += 1;
+
+        let mut fields = fields.into_iter();
+        for bldr in self.col_builders.iter_mut() {
+            match fields.next().flatten().map(str::trim) {
+                Some(v) if !v.is_empty() => {
+                    self.pending_bytes += v.len() as u64;
                     bldr.append_value(v);
                 }
-            } else {
-                bldr.append_null();
+                _ => bldr.append_null(),
             }
         }
 
-        ingest_date_builder.append_value(&cfg.ingest_date);
-        source_file_builder.append_value(&source_file_str);
-        rownum_builder.append_value(rows_in as i64);
+        self.ingest_date_builder.append_value(&self.ingest_date);
+        self.source_file_builder.append_value(source_file);
+        self.source_hash_builder.append_value(source_hash);
+        self.rownum_builder.append_value(row_number);
 
-        // Flush when batch full
-        if (rows_in as usize) % BATCH_SIZE == 0 {
-            write_batch(
-                &schema,
-                &mut writer,
-                &mut col_builders,
-                &mut ingest_date_builder,
-                &mut source_file_builder,
-                &mut rownum_builder,
-            )?;
+        // Flush when the batch is full or the current part reaches its bound
+        if self.pending == BATCH_SIZE || self.writer.is_full_with(self.pending, self.pending_bytes) {
+            return self.flush();
         }
+        Ok(false)
     }
 
-    if (rows_in as usize) % BATCH_SIZE != 0 {
-        write_batch(
-            &schema,
-            &mut writer,
-            &mut col_builders,
-            &mut ingest_date_builder,
-            &mut source_file_builder,
-            &mut rownum_builder,
-        )?;
+    /// How many of the next rows (by field bytes) go in before `push` would
+    /// flush, so parts roll over at the same rows whichever way they came in.
+    fn rows_fitting(&self, row_bytes: &[u64]) -> usize {
+        let mut bytes = self.pending_bytes;
+        for (i, b) in row_bytes.iter().enumerate() {
+            bytes += b;
+            let rows = self.pending + i + 1;
+            if rows == BATCH_SIZE || self.writer.is_full_with(rows, bytes) {
+                return i + 1;
+            }
+        }
+        row_bytes.len()
     }
 
-    writer.finish()?;
+    /// Already-built source columns (`rows[range]`) plus their lineage, as one
+    /// batch. Returns whether a part was completed.
+    fn push_slice(
+        &mut self,
+        rows: &SourceRows,
+        range: Range<usize>,
+        row_base: i64,
+        source_file: &str,
+        source_hash: &str,
+    ) -> Result<bool> {
+        let mut done = self.flush()?;
+        let len = range.len();
+        let mut cols: Vec<ArrayRef> = rows.columns.iter().map(|c| c.slice(range.start, len)).collect();
+        cols.push(Arc::new(StringArray::from_iter_values(std::iter::repeat_n(&self.ingest_date, len))));
+        cols.push(Arc::new(StringArray::from_iter_values(std::iter::repeat_n(source_file, len))));
+        cols.push(Arc::new(StringArray::from_iter_values(std::iter::repeat_n(source_hash, len))));
+        cols.push(Arc::new(Int64Array::from_iter_values(
+            rows.row_numbers[range.clone()].iter().map(|n| row_base + n),
+        )));
+        let bytes = rows.row_bytes[range].iter().sum();
+        let batch = RecordBatch::try_new(self.schema.clone(), cols)?;
+        done |= self.writer.write(&batch, bytes)?;
+        Ok(done)
+    }
 
-    Ok(IngestStats {
-        rows_in,
-        bytes_in,
-        parts_written: 1,
-        out_path,
-    })
+    /// Whether rows were taken in that are not in a completed part yet.
+    fn has_open_part(&self) -> bool {
+        self.pending > 0 || self.writer.current.is_some()
+    }
+
+    /// Returns whether a part was completed.
+    fn flush(&mut self) -> Result<bool> {
+        if self.pending == 0 {
+            return Ok(false);
+        }
+        // Build Arrow arrays for this batch
+        let mut cols: Vec<ArrayRef> = Vec::with_capacity(self.schema.fields().len());
+        for b in self.col_builders.iter_mut() {
+            let arr: StringArray = b.finish();
+            cols.push(Arc::new(arr) as ArrayRef);
+        }
+        let ingest_arr: StringArray = self.ingest_date_builder.finish();
+        let source_arr: StringArray = self.source_file_builder.finish();
+        let hash_arr: StringArray = self.source_hash_builder.finish();
+        let rownum_arr: Int64Array = self.rownum_builder.finish();
+        cols.push(Arc::new(ingest_arr) as ArrayRef);
+        cols.push(Arc::new(source_arr) as ArrayRef);
+        cols.push(Arc::new(hash_arr) as ArrayRef);
+        cols.push(Arc::new(rownum_arr) as ArrayRef);
+
+        let batch = RecordBatch::try_new(self.schema.clone(), cols)?;
+        let done = self.writer.write(&batch, self.pending_bytes)?;
+        self.pending = 0;
+        self.pending_bytes = 0;
+        Ok(done)
+    }
+
+    fn finish(mut self) -> Result<Vec<PathBuf>> {
+        self.flush()?;
+        self.writer.finish()
+    }
 }
 
-#[allow(clippy::too_many_arguments)]
-fn write_batch(
-    schema: &Arc<Schema>,
-    writer: &mut FileWriter<File>,
-    col_builders: &mut [StringBuilder],
-    ingest_date_builder: &mut StringBuilder,
-    source_file_builder: &mut StringBuilder,
-    rownum_builder: &mut Int64Builder,
-) -> Result<()> {
-    // Build Arrow arrays for this batch
-    let mut cols: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+// ---------- Quarantine
+
+/// Malformed records: where they came from, why, and what was read. Written as
+/// parts like Bronze, one completed at each checkpoint that quarantined records.
+struct ErrorSink {
+    dir: PathBuf,
+    schema: Arc<Schema>,
+    current: Option<OpenPart>,
+    parts: usize, // completed
+    source_file: StringBuilder,
+    line_number: Int64Builder,
+    byte_offset: Int64Builder,
+    error_code: StringBuilder,
+    error_detail: StringBuilder,
+    raw_line: StringBuilder,
+    pending: usize,
+}
+
+impl ErrorSink {
+    /// `done_parts` parts of an interrupted ingest are kept.
+    fn new(dir: PathBuf, done_parts: usize) -> Result<Self> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("source_file", DataType::Utf8, false),
+            Field::new("line_number", DataType::Int64, true),
+            Field::new("byte_offset", DataType::Int64, true),
+            Field::new("error_code", DataType::Utf8, false),
+            Field::new("error_detail", DataType::Utf8, false),
+            Field::new("raw_line", DataType::Utf8, true),
+        ]));
+        check_parts(&dir, done_parts)?;
+        Ok(Self {
+            dir,
+            schema,
+            current: None,
+            parts: done_parts,
+            source_file: StringBuilder::new(),
+            line_number: Int64Builder::new(),
+            byte_offset: Int64Builder::new(),
+            error_code: StringBuilder::new(),
+            error_detail: StringBuilder::new(),
+            raw_line: StringBuilder::new(),
+            pending: 0,
+        })
+    }
+
+    fn push(
+        &mut self,
+        source_file: &str,
+        line_number: Option<i64>,
+        byte_offset: Option<i64>,
+        code: &str,
+        detail: &str,
+        raw: Option<&str>,
+    ) -> Result<()> {
+        self.source_file.append_value(source_file);
+        self.line_number.append_option(line_number);
+        self.byte_offset.append_option(byte_offset);
+        self.error_code.append_value(code);
+        self.error_detail.append_value(detail);
+        self.raw_line.append_option(raw);
+        self.pending += 1;
+        if self.pending == BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.pending == 0 {
+            return Ok(());
+        }
+        let cols: Vec<ArrayRef> = vec![
+            Arc::new(self.source_file.finish()),
+            Arc::new(self.line_number.finish()),
+            Arc::new(self.byte_offset.finish()),
+            Arc::new(self.error_code.finish()),
+            Arc::new(self.error_detail.finish()),
+            Arc::new(self.raw_line.finish()),
+        ];
+        let batch = RecordBatch::try_new(self.schema.clone(), cols)?;
+        if self.current.is_none() {
+            self.current = Some(OpenPart::create(&self.dir, self.parts, &self.schema)?);
+        }
+        if let Some(part) = self.current.as_mut() {
+            part.writer.write(&batch)?;
+        }
+        self.pending = 0;
+        Ok(())
+    }
+
+    /// Completes the open part, if any records went into it.
+    fn commit(&mut self) -> Result<()> {
+        self.flush()?;
+        if let Some(part) = self.current.take() {
+            part.finish()?;
+            self.parts += 1;
+        }
+        Ok(())
+    }
 
-    for b in col_builders.iter_mut() {
-        let arr: StringArray = b.finish();
-        cols.push(Arc::new(arr) as ArrayRef);
+    /// An ingest without malformed records still yields one (empty) part.
+    fn finish(mut self) -> Result<()> {
+        self.commit()?;
+        if self.parts == 0 {
+            OpenPart::create(&self.dir, 0, &self.schema)?.finish()?;
+        }
+        Ok(())
     }
-    let ingest_arr: StringArray = ingest_date_builder.finish();
-    let source_arr: StringArray = source_file_builder.finish();
-    let rownum_arr: Int64Array = rownum_builder.finish();
-    cols.push(Arc::new(ingest_arr) as ArrayRef);
-    cols.push(Arc::new(source_arr) as ArrayRef);
-    cols.push(Arc::new(rownum_arr) as ArrayRef);
+}
 
-    let batch = RecordBatch::try_new(schema.clone(), cols)?;
-    writer.write(&batch)?;
+// ---------- Bronze parts
 
-    for b in col_builders.iter_mut() {
-        *b = StringBuilder::with_capacity(BATCH_SIZE, BATCH_SIZE * 8);
+/// A part being written: `part-NNNNNN.arrow.tmp`, renamed to `part-NNNNNN.arrow`
+/// once its IPC footer is written, so readers never see a truncated part.
+struct OpenPart {
+    writer: FileWriter<File>,
+    tmp: PathBuf,
+    path: PathBuf,
+}
+
+impl OpenPart {
+    fn create(dir: &Path, index: usize, schema: &Schema) -> Result<Self> {
+        let path = part_path(dir, index);
+        let tmp = path.with_extension("arrow.tmp");
+        let f = File::create(&tmp).with_context(|| format!("create {}", tmp.display()))?;
+        Ok(Self { writer: FileWriter::try_new(f, schema)?, tmp, path })
+    }
+
+    fn finish(mut self) -> Result<PathBuf> {
+        self.writer.finish()?;
+        self.writer.get_ref().sync_all()?;
+        rename(&self.tmp, &self.path).with_context(|| format!("rename {}", self.tmp.display()))?;
+        Ok(self.path)
     }
-    *ingest_date_builder = StringBuilder::with_capacity(BATCH_SIZE, BATCH_SIZE * 12);
-    *source_file_builder = StringBuilder::with_capacity(BATCH_SIZE, BATCH_SIZE * 32);
-    *rownum_builder = Int64Builder::with_capacity(BATCH_SIZE);
+}
+
+fn part_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("part-{index:06}.arrow"))
+}
 
+/// Writes `part-000000.arrow`, `part-000001.arrow`, ... rolling over once a part
+/// holds `max_rows` rows or `max_bytes` bytes of field data (0 = no bound).
+struct PartWriter {
+    dir: PathBuf,
+    schema: Arc<Schema>,
+    max_rows: u64,
+    max_bytes: u64,
+    current: Option<OpenPart>,
+    rows: u64,
+    bytes: u64,
+    parts: Vec<PathBuf>, // completed
+}
+
+impl PartWriter {
+    /// Continues after `done_parts` completed parts (0 for a fresh ingest).
+    fn new(dir: PathBuf, schema: Arc<Schema>, max_rows: u64, max_bytes: u64, done_parts: usize) -> Result<Self> {
+        check_parts(&dir, done_parts)?;
+        Ok(Self {
+            parts: (0..done_parts).map(|i| part_path(&dir, i)).collect(),
+            dir,
+            schema,
+            max_rows,
+            max_bytes,
+            current: None,
+            rows: 0,
+            bytes: 0,
+        })
+    }
+
+    /// Whether the current part is full once `rows`/`bytes` more are added.
+    fn is_full_with(&self, rows: usize, bytes: u64) -> bool {
+        (self.max_rows > 0 && self.rows + rows as u64 >= self.max_rows)
+            || (self.max_bytes > 0 && self.bytes + bytes >= self.max_bytes)
+    }
+
+    /// Returns whether the batch completed a part.
+    fn write(&mut self, batch: &RecordBatch, field_bytes: u64) -> Result<bool> {
+        if self.current.is_none() {
+            self.current = Some(OpenPart::create(&self.dir, self.parts.len(), &self.schema)?);
+        }
+        if let Some(part) = self.current.as_mut() {
+            part.writer.write(batch)?;
+        }
+        self.rows += batch.num_rows() as u64;
+        self.bytes += field_bytes;
+        if self.is_full_with(0, 0) {
+            self.close_part()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn close_part(&mut self) -> Result<()> {
+        if let Some(part) = self.current.take() {
+            self.parts.push(part.finish()?);
+        }
+        self.rows = 0;
+        self.bytes = 0;
+        Ok(())
+    }
+
+    /// Closes the last part; an empty source still yields one (empty) part.
+    fn finish(mut self) -> Result<Vec<PathBuf>> {
+        if self.parts.is_empty() {
+            self.write(&RecordBatch::new_empty(self.schema.clone()), 0)?;
+        }
+        self.close_part()?;
+        Ok(self.parts)
+    }
+}
+
+/// Parts kept from an interrupted ingest must all be there.
+fn check_parts(dir: &Path, done_parts: usize) -> Result<()> {
+    for i in 0..done_parts {
+        let path = part_path(dir, i);
+        if !path.exists() {
+            return Err(anyhow!("{} is listed in the checkpoint but missing", path.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Removes the parts from index `keep` on, and parts left unfinished (`.arrow.tmp`).
+fn remove_parts(dir: &Path, keep: usize) -> Result<()> {
+    for entry in read_dir(dir).with_context(|| format!("read {}", dir.display()))? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let Some(rest) = name.strip_prefix("part-") else { continue };
+        let stale = match rest.strip_suffix(".arrow") {
+            Some(index) => index.parse::<usize>().map_or(true, |i| i >= keep),
+            None => rest.ends_with(".arrow.tmp"),
+        };
+        if stale {
+            remove_file(&path).with_context(|| format!("remove {}", path.display()))?;
+        }
+    }
     Ok(())
 }
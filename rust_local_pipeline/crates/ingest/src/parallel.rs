@@ -0,0 +1,469 @@
+//! Chunked CSV parsing on a thread pool. The stream is cut into chunks at record
+//! boundaries (a quote-aware scan, much cheaper than parsing), each chunk is parsed
+//! into Arrow columns on the pool, and the results are written back in stream
+//! order, so Bronze rows, `row_number` and quarantine positions match the
+//! sequential reader.
+use crate::checkpoint::StreamPos;
+use crate::source::{BoxedReader, Dialect};
+use crate::{Output, ReadCounts, SourceRows, BATCH_SIZE};
+use anyhow::{anyhow, Context, Result};
+use arrow::array::{ArrayRef, StringBuilder};
+use csv::{ByteRecord, ReaderBuilder, StringRecord};
+use rayon::ThreadPool;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+use tokio::sync::oneshot;
+
+/// Target chunk size; a chunk ends at the first record boundary after it.
+pub(crate) const CHUNK_BYTES: usize = 8 * 1024 * 1024;
+const READ_BYTES: usize = 1024 * 1024;
+
+/// Thread pool for `threads` parsers (0 = one per core).
+pub(crate) fn pool(threads: usize) -> Result<ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("csv-parse-{i}"))
+        .build()
+        .context("start CSV parser threads")
+}
+
+/// Reads one decoded CSV stream (header + records) into `out`, parsing on `pool`.
+/// With `resume_at`, `reader` starts at the checkpointed record (no header).
+pub(crate) async fn read_csv(
+    reader: BoxedReader,
+    dialect: Dialect,
+    resume_at: Option<&StreamPos>,
+    pool: &ThreadPool,
+    label: &str,
+    hash: &str,
+    out: &mut Output<'_>,
+) -> Result<ReadCounts> {
+    let (line, byte) = resume_at.map_or((1, 0), |p| (p.line, p.byte));
+    let mut chunks = Splitter::new(reader, dialect, line, byte);
+
+    let headers = match resume_at {
+        Some(pos) => pos.headers.clone(),
+        None => {
+            let header = chunks
+                .next_record()
+                .await
+                .with_context(|| format!("read {label}"))?
+                .ok_or_else(|| anyhow!("no header in {label}"))?;
+            parse_header(&header.data, dialect).with_context(|| format!("read header of {label}"))?
+        }
+    };
+    let expected = headers.len();
+    out.open(headers, Some(dialect), label)?;
+
+    // Enough chunks in flight to keep every parser busy while the oldest is written
+    let max_in_flight = pool.current_num_threads() * 2;
+    let mut in_flight: VecDeque<oneshot::Receiver<ParsedChunk>> = VecDeque::new();
+    let (mut record_no, mut counts) = ReadCounts::resumed(resume_at);
+    loop {
+        let chunk = chunks.next_chunk().await.with_context(|| format!("read {label}"))?;
+        let done = chunk.is_none();
+        if let Some(chunk) = chunk {
+            let (tx, rx) = oneshot::channel();
+            pool.spawn(move || {
+                // the receiver only goes away when the ingest already failed
+                let _ = tx.send(parse_chunk(chunk, dialect, expected));
+            });
+            in_flight.push_back(rx);
+        }
+        while in_flight.len() >= max_in_flight || (done && !in_flight.is_empty()) {
+            let Some(rx) = in_flight.pop_front() else { break };
+            let parsed = rx.await.map_err(|_| anyhow!("CSV parser thread stopped"))?;
+            write_chunk(parsed, label, hash, &mut record_no, &mut counts, out)?;
+        }
+        if done {
+            return Ok(counts);
+        }
+    }
+}
+
+/// Writes a parsed chunk's rows and quarantined records in record order, saving
+/// a checkpoint at the next record whenever a part is completed.
+fn write_chunk(
+    parsed: ParsedChunk,
+    label: &str,
+    hash: &str,
+    record_no: &mut i64,
+    counts: &mut ReadCounts,
+    out: &mut Output<'_>,
+) -> Result<()> {
+    let base = *record_no;
+    let rows = &parsed.rows;
+    let mut rejected = parsed.rejected.iter().peekable();
+    let quarantine = |e: &Rejected, counts: &mut ReadCounts, out: &mut Output<'_>| {
+        counts.quarantined += 1;
+        out.quarantine(label, e.line(), e.offset(), e.code, &e.detail, e.raw.as_deref())
+    };
+
+    let mut start = 0;
+    while start < rows.row_numbers.len() {
+        let end = start + out.rows_fitting(&rows.row_bytes[start..])?;
+        let last = rows.row_numbers[end - 1];
+        while let Some(e) = rejected.next_if(|e| e.record < last) {
+            quarantine(e, counts, out)?;
+        }
+        out.push_slice(rows, start..end, base, label, hash)?;
+        counts.rows += (end - start) as u64;
+        start = end;
+
+        // the next record: a quarantined one, the next row, or the end of the chunk
+        let next_row = rows.row_numbers.get(start);
+        let next = match rejected.peek() {
+            Some(e) if next_row.is_none_or(|r| e.record < *r) => e.start.map(|(l, b)| (l, b, e.record)),
+            _ => match next_row {
+                Some(r) => Some((parsed.row_starts[start].0, parsed.row_starts[start].1, *r)),
+                None => Some((parsed.end.0, parsed.end.1, parsed.records + 1)),
+            },
+        };
+        if let Some((line, byte, record)) = next {
+            out.at_record(byte, line, base + record - 1, counts.quarantined)?;
+        }
+    }
+    for e in rejected {
+        quarantine(e, counts, out)?;
+    }
+    *record_no += parsed.records;
+    Ok(())
+}
+
+fn parse_header(data: &[u8], dialect: Dialect) -> Result<Vec<String>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .from_reader(data);
+    let mut rec = StringRecord::new();
+    rdr.read_record(&mut rec)?;
+    Ok(rec
+        .iter()
+        .map(|h| h.trim_start_matches('\u{feff}').trim().to_string())
+        .collect())
+}
+
+// ---------- Splitting
+
+/// Whole records, with where they start in the decoded stream.
+struct Chunk {
+    data: Vec<u8>,
+    line: u64, // 1-based line of the first byte
+    byte: u64, // offset of the first byte
+}
+
+/// Where the scan is within a record, mirroring the CSV reader's states so quotes
+/// inside unquoted fields (`12" screen`) do not flip it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scan {
+    StartField,
+    InField,
+    InQuoted,
+    QuoteInQuoted,
+}
+
+struct Splitter {
+    reader: BoxedReader,
+    dialect: Dialect,
+    buf: Vec<u8>,
+    scanned: usize,  // bytes of `buf` already scanned
+    boundary: usize, // end of the last complete record in `buf`
+    state: Scan,     // at `scanned`
+    line: u64,       // line of buf[0]
+    byte: u64,       // stream offset of buf[0]
+    eof: bool,
+}
+
+impl Splitter {
+    /// `reader` starts at `line` / `byte` of the stream (1 / 0 unless resuming).
+    fn new(reader: BoxedReader, dialect: Dialect, line: u64, byte: u64) -> Self {
+        Self {
+            reader,
+            dialect,
+            buf: Vec::with_capacity(CHUNK_BYTES + READ_BYTES),
+            scanned: 0,
+            boundary: 0,
+            state: Scan::StartField,
+            line,
+            byte,
+            eof: false,
+        }
+    }
+
+    /// The next single record (the header).
+    async fn next_record(&mut self) -> Result<Option<Chunk>> {
+        self.next(1).await
+    }
+
+    /// Whole records worth at least `CHUNK_BYTES` (less at the end of the stream).
+    async fn next_chunk(&mut self) -> Result<Option<Chunk>> {
+        self.next(CHUNK_BYTES).await
+    }
+
+    async fn next(&mut self, min_bytes: usize) -> Result<Option<Chunk>> {
+        loop {
+            self.scan(min_bytes);
+            if self.boundary > 0 && self.boundary >= min_bytes {
+                return Ok(Some(self.take(self.boundary)));
+            }
+            if self.eof {
+                // the last record may lack its newline
+                return Ok((!self.buf.is_empty()).then(|| self.take(self.buf.len())));
+            }
+            let len = self.buf.len();
+            self.buf.resize(len + READ_BYTES, 0);
+            let n = self.reader.read(&mut self.buf[len..]).await?;
+            self.buf.truncate(len + n);
+            self.eof = n == 0;
+        }
+    }
+
+    /// Advances the scan, stopping at the first record end at or after `min_bytes`.
+    fn scan(&mut self, min_bytes: usize) {
+        let (delimiter, quote) = (self.dialect.delimiter, self.dialect.quote);
+        while self.scanned < self.buf.len() {
+            let b = self.buf[self.scanned];
+            self.scanned += 1;
+            self.state = match (self.state, b) {
+                (Scan::InQuoted, b) if b == quote => Scan::QuoteInQuoted,
+                (Scan::InQuoted, _) => Scan::InQuoted,
+                (Scan::QuoteInQuoted, b) if b == quote => Scan::InQuoted,
+                (_, b'\n') => {
+                    // after `\r\n` the reader starts the next record at the `\n`: cut
+                    // before it so that chunks start where the sequential reader's records do
+                    let crlf = self.scanned >= 2 && self.buf[self.scanned - 2] == b'\r';
+                    self.boundary = self.scanned - usize::from(crlf);
+                    if self.boundary >= min_bytes {
+                        self.state = Scan::StartField;
+                        return;
+                    }
+                    Scan::StartField
+                }
+                (_, b) if b == delimiter => Scan::StartField,
+                (Scan::StartField, b) if b == quote => Scan::InQuoted,
+                _ => Scan::InField,
+            };
+        }
+    }
+
+    /// Splits off `buf[..end]`; the rest was scanned already and keeps its state.
+    fn take(&mut self, end: usize) -> Chunk {
+        let rest = self.buf.split_off(end);
+        let data = std::mem::replace(&mut self.buf, rest);
+        let chunk = Chunk { line: self.line, byte: self.byte, data };
+        self.line += chunk.data.iter().filter(|b| **b == b'\n').count() as u64;
+        self.byte += chunk.data.len() as u64;
+        self.scanned -= end.min(self.scanned);
+        self.boundary = 0;
+        chunk
+    }
+}
+
+// ---------- Parsing
+
+/// One chunk laid out as Bronze source columns, with what was quarantined.
+struct ParsedChunk {
+    rows: SourceRows,
+    row_starts: Vec<(u64, u64)>, // (line, byte) where each row's record starts
+    rejected: Vec<Rejected>,
+    records: i64,    // records in the chunk, quarantined ones included
+    end: (u64, u64), // (line, byte) right after the chunk
+}
+
+struct Rejected {
+    record: i64, // 1-based within the chunk
+    start: Option<(u64, u64)>,
+    code: &'static str,
+    detail: String,
+    raw: Option<String>,
+}
+
+impl Rejected {
+    fn line(&self) -> Option<i64> {
+        self.start.map(|(l, _)| l as i64)
+    }
+
+    fn offset(&self) -> Option<i64> {
+        self.start.map(|(_, b)| b as i64)
+    }
+}
+
+/// Same checks and trimming as the sequential reader and `BronzeSink::push`.
+fn parse_chunk(chunk: Chunk, dialect: Dialect, expected: usize) -> ParsedChunk {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .flexible(true)
+        .from_reader(chunk.data.as_slice());
+    let mut builders: Vec<StringBuilder> = (0..expected)
+        .map(|_| StringBuilder::with_capacity(BATCH_SIZE / 8, chunk.data.len() / expected.max(1)))
+        .collect();
+    let mut row_numbers: Vec<i64> = Vec::new();
+    let mut row_bytes: Vec<u64> = Vec::new();
+    let mut row_starts: Vec<(u64, u64)> = Vec::new();
+    let mut rejected: Vec<Rejected> = Vec::new();
+    let mut records: i64 = 0;
+    // positions within the chunk -> positions within the stream
+    let at = |p: &csv::Position| (chunk.line + p.line() - 1, chunk.byte + p.byte());
+
+    let mut rec = ByteRecord::new();
+    loop {
+        let start = match rdr.read_byte_record(&mut rec) {
+            Ok(false) => break,
+            Ok(true) => rec.position().map(at),
+            Err(e) => {
+                records += 1;
+                let start = e.position().map(at);
+                rejected.push(Rejected { record: records, start, code: "CSV_PARSE_ERROR", detail: e.to_string(), raw: None });
+                continue;
+            }
+        };
+        records += 1;
+        if rec.len() != expected {
+            rejected.push(Rejected {
+                record: records,
+                start,
+                code: "FIELD_COUNT_MISMATCH",
+                detail: format!("expected {expected} fields, got {}", rec.len()),
+                raw: Some(crate::raw_line(rec.iter(), dialect.delimiter)),
+            });
+            continue;
+        }
+        let fields: Vec<&str> = match rec
+            .iter()
+            .enumerate()
+            .map(|(i, f)| std::str::from_utf8(f).map_err(|e| (i, e)))
+            .collect()
+        {
+            Ok(fields) => fields,
+            Err((i, e)) => {
+                rejected.push(Rejected {
+                    record: records,
+                    start,
+                    code: "INVALID_UTF8",
+                    // worded like the sequential reader's error, with its 1-based field number
+                    detail: format!(
+                        "invalid utf-8: invalid UTF-8 in field {} near byte index {}",
+                        i + 1,
+                        e.valid_up_to()
+                    ),
+                    raw: Some(crate::raw_line(rec.iter(), dialect.delimiter)),
+                });
+                continue;
+            }
+        };
+        let mut bytes: u64 = 0;
+        for (bldr, v) in builders.iter_mut().zip(fields) {
+            match v.trim() {
+                "" => bldr.append_null(),
+                v => {
+                    bytes += v.len() as u64;
+                    bldr.append_value(v);
+                }
+            }
+        }
+        row_numbers.push(records);
+        row_bytes.push(bytes);
+        // a record always has a position; the fallback only keeps the vectors aligned
+        row_starts.push(start.unwrap_or((chunk.line, chunk.byte)));
+    }
+    let newlines = chunk.data.iter().filter(|b| **b == b'\n').count() as u64;
+    ParsedChunk {
+        rows: SourceRows {
+            columns: builders.iter_mut().map(|b| Arc::new(b.finish()) as ArrayRef).collect(),
+            row_numbers,
+            row_bytes,
+        },
+        row_starts,
+        rejected,
+        records,
+        end: (chunk.line + newlines, chunk.byte + chunk.data.len() as u64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray};
+
+    const DIALECT: Dialect = Dialect { delimiter: b',', quote: b'"', sniffed: false };
+
+    /// Records with quoted newlines, quotes inside unquoted fields, CRLF, a record with the
+    /// wrong field count, invalid UTF-8 and a last record without its newline.
+    fn stream() -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in 0..3 {
+            data.extend_from_slice(format!("a{i},\"multi\nline\n\",c\n").as_bytes());
+            data.extend_from_slice(b"12\" screen,b,c\n");
+            data.extend_from_slice(b"\"q \"\"x\"\"\n\",\"\",c\r\n");
+            data.extend_from_slice(b"x,y\n");
+            data.extend_from_slice(b"\xff,b,c\n");
+        }
+        data.extend_from_slice(b"\"a,b\n\n\",b,z");
+        data
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Record {
+        Row(Vec<Option<String>>, (u64, u64)),
+        Rejected(&'static str, Option<(u64, u64)>, String, Option<String>),
+    }
+
+    /// The stream cut into chunks of at least `min_bytes`, each parsed on its own.
+    async fn parse(data: &[u8], min_bytes: usize) -> Vec<Record> {
+        let mut splitter = Splitter::new(Box::new(std::io::Cursor::new(data.to_vec())), DIALECT, 1, 0);
+        let (mut records, mut data_seen, mut next_start) = (Vec::new(), Vec::new(), (1, 0));
+        while let Some(chunk) = splitter.next(min_bytes).await.unwrap() {
+            assert_eq!((chunk.line, chunk.byte), next_start);
+            data_seen.extend_from_slice(&chunk.data);
+            let parsed = parse_chunk(chunk, DIALECT, 3);
+            next_start = parsed.end;
+
+            let mut numbered: Vec<(i64, Record)> = Vec::new();
+            for (r, record) in parsed.rows.row_numbers.iter().enumerate() {
+                let values = parsed
+                    .rows
+                    .columns
+                    .iter()
+                    .map(|c| c.is_valid(r).then(|| c.as_string::<i32>().value(r).to_string()))
+                    .collect();
+                numbered.push((*record, Record::Row(values, parsed.row_starts[r])));
+            }
+            for e in parsed.rejected {
+                numbered.push((e.record, Record::Rejected(e.code, e.start, e.detail, e.raw)));
+            }
+            numbered.sort_by_key(|(record, _)| *record);
+            assert!(numbered.iter().map(|(r, _)| *r).eq(1..=parsed.records));
+            records.extend(numbered.into_iter().map(|(_, record)| record));
+        }
+        assert_eq!(data_seen, data);
+        records
+    }
+
+    #[tokio::test]
+    async fn chunks_of_any_size_parse_like_the_whole_stream() {
+        let data = stream();
+        let whole = parse(&data, usize::MAX).await;
+        assert_eq!(whole.len(), 16);
+        let first = vec![Some("a0".into()), Some("multi\nline".into()), Some("c".into())];
+        assert_eq!(whole[0], Record::Row(first, (1, 0)));
+        // a record after `\r\n` starts at the `\n`, as the sequential reader reports it
+        assert!(matches!(whole[3], Record::Rejected("FIELD_COUNT_MISMATCH", Some((6, 50)), _, _)));
+        assert_eq!(
+            whole[4],
+            Record::Rejected(
+                "INVALID_UTF8",
+                Some((8, 55)),
+                "invalid utf-8: invalid UTF-8 in field 1 near byte index 0".into(),
+                Some("\u{fffd},b,c".into())
+            )
+        );
+        // every cut: inside quoted newlines, right after them, at CRLF, ...
+        for min_bytes in 1..=data.len() {
+            assert_eq!(parse(&data, min_bytes).await, whole, "chunks of {min_bytes} bytes");
+        }
+    }
+}
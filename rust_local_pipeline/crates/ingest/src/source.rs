@@ -0,0 +1,445 @@
+//! Opening ingest sources: compression detection and streaming decoders.
+use anyhow::{anyhow, Context, Result};
+use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+use encoding_rs::{Decoder, Encoding, UTF_8};
+use futures::{StreamExt, TryStreamExt};
+use object_store::{ObjectMeta, ObjectStore};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, ReadBuf};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tokio_util::io::StreamReader;
+
+pub(crate) type BoxedReader = Box<dyn AsyncRead + Unpin + Send>;
+type BoxedBufReader = Box<dyn AsyncBufRead + Unpin + Send>;
+
+/// One source file: on local disk, or an object in a store.
+pub(crate) enum SourceFile {
+    Local(PathBuf),
+    Object {
+        store: Arc<dyn ObjectStore>,
+        meta: ObjectMeta,
+        /// `s3://bucket/key`-style name used for lineage and the ledger
+        label: String,
+    },
+}
+
+impl SourceFile {
+    pub(crate) fn label(&self) -> String {
+        match self {
+            SourceFile::Local(path) => path.display().to_string(),
+            SourceFile::Object { label, .. } => label.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Zip,
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Zip => "zip",
+        })
+    }
+}
+
+/// One decoded CSV stream: a plain/gzip/zstd file, or one member of a zip archive.
+pub(crate) struct SourceStream {
+    /// Value written to the `source_file` lineage column (`archive.zip!member.csv` for zip members).
+    pub label: String,
+    pub reader: BoxedReader,
+    /// Decoded bytes read so far.
+    pub uncompressed: Arc<AtomicU64>,
+    /// Local copy of a remote zip archive, removed once every member stream is dropped.
+    _spool: Option<Arc<SpoolFile>>,
+}
+
+/// Detects compression by extension, falling back to the leading magic bytes.
+pub(crate) async fn detect(src: &SourceFile) -> Result<Compression> {
+    let ext = match src {
+        SourceFile::Local(path) => path.extension().and_then(|e| e.to_str()).map(str::to_string),
+        SourceFile::Object { meta, .. } => meta.location.extension().map(str::to_string),
+    };
+    match ext.map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("gz" | "gzip") => return Ok(Compression::Gzip),
+        Some("zst" | "zstd") => return Ok(Compression::Zstd),
+        Some("zip") => return Ok(Compression::Zip),
+        _ => {}
+    }
+
+    let magic = match src {
+        SourceFile::Local(path) => {
+            let mut f = tokio::fs::File::open(path)
+                .await
+                .with_context(|| format!("open {}", path.display()))?;
+            let mut magic = Vec::with_capacity(4);
+            (&mut f).take(4).read_to_end(&mut magic).await?;
+            magic
+        }
+        SourceFile::Object { store, meta, label } => {
+            let end = meta.size.min(4);
+            store
+                .get_range(&meta.location, 0..end)
+                .await
+                .with_context(|| format!("read {label}"))?
+                .to_vec()
+        }
+    };
+    Ok(match magic.as_slice() {
+        [0x1f, 0x8b, ..] => Compression::Gzip,
+        [0x28, 0xb5, 0x2f, 0xfd] => Compression::Zstd,
+        [0x50, 0x4b, 0x03, 0x04] => Compression::Zip,
+        _ => Compression::None,
+    })
+}
+
+/// Opens `src` as one or more decoded streams (zip archives yield one per file member).
+/// Objects are streamed; a remote zip is first copied into `spool_dir` since reading
+/// its members needs random access.
+pub(crate) async fn open_streams(
+    src: &SourceFile,
+    compression: Compression,
+    spool_dir: &Path,
+) -> Result<Vec<SourceStream>> {
+    let label = src.label();
+    match (src, compression) {
+        (SourceFile::Local(path), Compression::Zip) => open_zip(path, &label, None).await,
+        (SourceFile::Local(path), _) => {
+            let f = tokio::fs::File::open(path)
+                .await
+                .with_context(|| format!("open {}", path.display()))?;
+            Ok(vec![counted(label, decode(Box::new(BufReader::new(f)), compression), None)])
+        }
+        (SourceFile::Object { store, meta, .. }, Compression::Zip) => {
+            let spool = Arc::new(spool_object(store.as_ref(), meta, &label, spool_dir).await?);
+            open_zip(&spool.0, &label, Some(spool.clone())).await
+        }
+        (SourceFile::Object { store, meta, .. }, _) => {
+            let stream = store
+                .get(&meta.location)
+                .await
+                .with_context(|| format!("get {label}"))?
+                .into_stream()
+                .map_err(std::io::Error::other);
+            Ok(vec![counted(label, decode(Box::new(StreamReader::new(stream)), compression), None)])
+        }
+    }
+}
+
+/// Wraps a buffered byte stream in the decoder for `compression` (not zip).
+fn decode(inner: BoxedBufReader, compression: Compression) -> BoxedReader {
+    match compression {
+        Compression::Gzip => {
+            let mut dec = GzipDecoder::new(inner);
+            // concatenated .gz members are common for yearly appends
+            dec.multiple_members(true);
+            Box::new(dec)
+        }
+        Compression::Zstd => Box::new(ZstdDecoder::new(inner)),
+        Compression::None | Compression::Zip => Box::new(inner),
+    }
+}
+
+/// One stream per file member of a local zip archive, labelled `{label}!{member}`; member
+/// names that are not UTF-8 are decoded lossily.
+async fn open_zip(path: &Path, label: &str, spool: Option<Arc<SpoolFile>>) -> Result<Vec<SourceStream>> {
+    let zip = async_zip::tokio::read::fs::ZipFileReader::new(path)
+        .await
+        .with_context(|| format!("read zip {label}"))?;
+    let mut out = Vec::new();
+    for (i, entry) in zip.file().entries().iter().enumerate() {
+        // names without the UTF-8 flag are in the archiver's code page (CP437 for old tools)
+        let name = String::from_utf8_lossy(entry.filename().as_bytes()).into_owned();
+        if name.ends_with('/') {
+            continue;
+        }
+        let member = zip
+            .reader_without_entry(i)
+            .await
+            .with_context(|| format!("open {name} in {label}"))?;
+        out.push(counted(format!("{label}!{name}"), Box::new(member.compat()), spool.clone()));
+    }
+    Ok(out)
+}
+
+/// A local path for `src`; objects are copied into `spool_dir` first (the returned
+/// guard removes the copy).
+pub(crate) async fn local_copy(src: &SourceFile, spool_dir: &Path) -> Result<(PathBuf, Option<SpoolFile>)> {
+    match src {
+        SourceFile::Local(path) => Ok((path.clone(), None)),
+        SourceFile::Object { store, meta, label } => {
+            let spool = spool_object(store.as_ref(), meta, label, spool_dir).await?;
+            Ok((spool.0.clone(), Some(spool)))
+        }
+    }
+}
+
+/// A temporary local copy of an object, deleted on drop.
+pub(crate) struct SpoolFile(PathBuf);
+
+impl Drop for SpoolFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+async fn spool_object(store: &dyn ObjectStore, meta: &ObjectMeta, label: &str, dir: &Path) -> Result<SpoolFile> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("mkdir -p {}", dir.display()))?;
+    let ext = meta.location.extension().unwrap_or("bin");
+    let spool = SpoolFile(dir.join(format!("{}.{ext}", uuid::Uuid::new_v4())));
+    let mut out = tokio::fs::File::create(&spool.0)
+        .await
+        .with_context(|| format!("create {}", spool.0.display()))?;
+    let mut stream = store
+        .get(&meta.location)
+        .await
+        .with_context(|| format!("get {label}"))?
+        .into_stream();
+    while let Some(chunk) = stream.next().await {
+        out.write_all(&chunk?).await?;
+    }
+    out.flush().await?;
+    Ok(spool)
+}
+
+fn counted(label: String, inner: BoxedReader, spool: Option<Arc<SpoolFile>>) -> SourceStream {
+    let uncompressed = Arc::new(AtomicU64::new(0));
+    SourceStream {
+        label,
+        reader: Box::new(CountingReader {
+            inner,
+            count: uncompressed.clone(),
+        }),
+        uncompressed,
+        _spool: spool,
+    }
+}
+
+/// Counts the bytes handed to the CSV reader.
+struct CountingReader {
+    inner: BoxedReader,
+    count: Arc<AtomicU64>,
+}
+
+impl AsyncRead for CountingReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = res {
+            let n = (buf.filled().len() - before) as u64;
+            self.count.fetch_add(n, Ordering::Relaxed);
+        }
+        res
+    }
+}
+
+// ---------- encoding
+
+/// Transcodes `inner` to UTF-8 while streaming. UTF-8 sources pass through untouched.
+pub(crate) fn transcode(inner: BoxedReader, encoding: &'static Encoding) -> BoxedReader {
+    if encoding == UTF_8 {
+        return inner;
+    }
+    Box::new(TranscodingReader {
+        inner,
+        decoder: encoding.new_decoder_without_bom_handling(),
+        inbuf: vec![0u8; 16 * 1024].into_boxed_slice(),
+        in_start: 0,
+        in_end: 0,
+        eof: false,
+        done: false,
+        out: Vec::new(),
+        out_pos: 0,
+    })
+}
+
+/// Reads past the first `n` bytes of a (decoded) stream, to resume after them.
+pub(crate) async fn skip(mut reader: BoxedReader, n: u64, label: &str) -> Result<BoxedReader> {
+    let skipped = tokio::io::copy(&mut (&mut reader).take(n), &mut tokio::io::sink())
+        .await
+        .with_context(|| format!("read {label}"))?;
+    if skipped < n {
+        return Err(anyhow!("{label} is shorter than its checkpoint ({skipped} of {n} bytes)"));
+    }
+    Ok(reader)
+}
+
+struct TranscodingReader {
+    inner: BoxedReader,
+    decoder: Decoder,
+    inbuf: Box<[u8]>,
+    in_start: usize,
+    in_end: usize,
+    eof: bool,
+    done: bool,
+    out: Vec<u8>,
+    out_pos: usize,
+}
+
+impl AsyncRead for TranscodingReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            // Hand out already decoded bytes first
+            if this.out_pos < this.out.len() {
+                let n = (this.out.len() - this.out_pos).min(buf.remaining());
+                buf.put_slice(&this.out[this.out_pos..this.out_pos + n]);
+                this.out_pos += n;
+                return Poll::Ready(Ok(()));
+            }
+            if this.done {
+                return Poll::Ready(Ok(()));
+            }
+            // Refill the input buffer
+            if this.in_start == this.in_end && !this.eof {
+                let mut rb = ReadBuf::new(&mut this.inbuf);
+                match Pin::new(&mut this.inner).poll_read(cx, &mut rb) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Ready(Ok(())) => {
+                        let n = rb.filled().len();
+                        this.in_start = 0;
+                        this.in_end = n;
+                        this.eof = n == 0;
+                    }
+                }
+            }
+            // Decode what we have (flushing the decoder at EOF)
+            let src = &this.inbuf[this.in_start..this.in_end];
+            let cap = this
+                .decoder
+                .max_utf8_buffer_length(src.len())
+                .unwrap_or(src.len() * 3 + 16)
+                .max(16);
+            this.out.clear();
+            this.out.resize(cap, 0);
+            this.out_pos = 0;
+            let (_, read, written, _) = this.decoder.decode_to_utf8(src, &mut this.out, this.eof);
+            this.in_start += read;
+            this.out.truncate(written);
+            if this.eof && this.in_start == this.in_end {
+                this.done = true;
+            }
+        }
+    }
+}
+
+// ---------- dialect sniffing
+
+const SNIFF_BYTES: usize = 16 * 1024;
+const DELIMITER_CANDIDATES: [u8; 4] = [b',', b';', b'|', b'\t'];
+
+/// CSV dialect of one stream, as configured or sniffed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub sniffed: bool,
+}
+
+/// Resolves the dialect, reading up to `SNIFF_BYTES` of (UTF-8) input when
+/// either setting is unset. The sampled bytes are stitched back in front of the reader.
+pub(crate) async fn resolve_dialect(
+    mut reader: BoxedReader,
+    delimiter: Option<u8>,
+    quote: Option<u8>,
+) -> Result<(Dialect, BoxedReader)> {
+    if let (Some(delimiter), Some(quote)) = (delimiter, quote) {
+        return Ok((Dialect { delimiter, quote, sniffed: false }, reader));
+    }
+    let mut sample = Vec::with_capacity(SNIFF_BYTES);
+    (&mut reader).take(SNIFF_BYTES as u64).read_to_end(&mut sample).await?;
+
+    let quote = quote.unwrap_or_else(|| sniff_quote(&sample));
+    let delimiter = delimiter.unwrap_or_else(|| sniff_delimiter(&sample, quote));
+    let reader: BoxedReader = Box::new(std::io::Cursor::new(sample).chain(reader));
+    Ok((Dialect { delimiter, quote, sniffed: true }, reader))
+}
+
+/// Complete lines of the sample (the last one may be cut off).
+fn sample_lines(sample: &[u8]) -> Vec<&[u8]> {
+    let mut lines: Vec<&[u8]> = sample.split(|&b| b == b'\n').collect();
+    if lines.len() > 1 {
+        lines.pop();
+    }
+    lines
+        .into_iter()
+        .map(|l| l.strip_suffix(b"\r").unwrap_or(l))
+        .filter(|l| !l.is_empty())
+        .take(50)
+        .collect()
+}
+
+/// `"` unless `'` is clearly the one opening fields.
+fn sniff_quote(sample: &[u8]) -> u8 {
+    let opens = |q: u8| {
+        sample
+            .windows(2)
+            .filter(|w| w[1] == q && (DELIMITER_CANDIDATES.contains(&w[0]) || w[0] == b'\n'))
+            .count()
+    };
+    if opens(b'\'') > opens(b'"') { b'\'' } else { b'"' }
+}
+
+/// Picks the candidate with the same non-zero count (outside quotes) on every
+/// line, preferring the highest count; falls back to the most frequent on the header.
+fn sniff_delimiter(sample: &[u8], quote: u8) -> u8 {
+    let lines = sample_lines(sample);
+    let count = |line: &[u8], d: u8| {
+        let mut in_quotes = false;
+        line.iter()
+            .filter(|&&b| {
+                if b == quote {
+                    in_quotes = !in_quotes;
+                }
+                b == d && !in_quotes
+            })
+            .count()
+    };
+
+    let mut best: Option<(usize, u8)> = None;
+    for d in DELIMITER_CANDIDATES {
+        let Some(first) = lines.first().map(|l| count(l, d)) else { break };
+        if first > 0
+            && lines.iter().all(|l| count(l, d) == first)
+            && best.is_none_or(|(n, _)| first > n)
+        {
+            best = Some((first, d));
+        }
+    }
+    if let Some((_, d)) = best {
+        return d;
+    }
+    let header = lines.first().copied().unwrap_or_default();
+    DELIMITER_CANDIDATES
+        .iter()
+        .copied()
+        .max_by_key(|&d| count(header, d))
+        .filter(|&d| count(header, d) > 0)
+        .unwrap_or(b',')
+}
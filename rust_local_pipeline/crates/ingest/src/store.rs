@@ -0,0 +1,94 @@
+//! Object-store sources (S3, GCS, Azure, HTTP) via the `object_store` crate.
+use crate::source::SourceFile;
+use crate::SourceKind;
+use anyhow::{anyhow, Context, Result};
+use futures::{StreamExt, TryStreamExt};
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectMeta, ObjectStore};
+use std::sync::Arc;
+use url::Url;
+
+/// Environment variable prefixes forwarded to the store builders (`AWS_ENDPOINT`, ...).
+const ENV_PREFIXES: [&str; 3] = ["AWS_", "GOOGLE_", "AZURE_"];
+
+/// Store options from the environment, with keys lower-cased as `object_store` expects.
+pub(crate) fn options_from_env() -> Vec<(String, String)> {
+    std::env::vars()
+        .filter(|(k, _)| ENV_PREFIXES.iter().any(|p| k.starts_with(p)))
+        .map(|(k, v)| (k.to_ascii_lowercase(), v))
+        .collect()
+}
+
+/// Lists the objects addressed by `source`: a single object, every object under a
+/// prefix, or the matches of a `*` glob in the key. Sorted by key.
+pub(crate) async fn resolve_objects(kind: &SourceKind, source: &str) -> Result<Vec<SourceFile>> {
+    let (store, path, base) = open_store(kind, source)?;
+
+    let key = path.as_ref();
+    let mut metas: Vec<ObjectMeta> = if let Some(star) = key.find(['*', '[']) {
+        // list from the last complete segment before the pattern
+        let prefix = key[..star].rfind('/').map(|i| ObjectPath::from(&key[..i]));
+        let pattern = glob::Pattern::new(key).with_context(|| format!("invalid glob: {source}"))?;
+        store
+            .list(prefix.as_ref())
+            .try_filter(|m| futures::future::ready(pattern.matches(m.location.as_ref())))
+            .try_collect()
+            .await?
+    } else {
+        match store.head(&path).await {
+            Ok(meta) => vec![meta],
+            Err(object_store::Error::NotFound { .. }) => {
+                store.list(Some(&path)).try_collect().await?
+            }
+            Err(e) => return Err(e).with_context(|| format!("head {source}")),
+        }
+    };
+    metas.retain(|m| !m.location.filename().is_some_and(|n| n.starts_with('.')));
+    metas.sort_by(|a, b| a.location.cmp(&b.location));
+    if metas.is_empty() {
+        return Err(anyhow!("no source objects found for {source}"));
+    }
+
+    Ok(metas
+        .into_iter()
+        .map(|meta| SourceFile::Object {
+            store: store.clone(),
+            label: format!("{base}{}", meta.location),
+            meta,
+        })
+        .collect())
+}
+
+/// BLAKE3 of an object's bytes, streamed.
+pub(crate) async fn hash_object(store: &dyn ObjectStore, meta: &ObjectMeta) -> Result<String> {
+    let mut stream = store.get(&meta.location).await?.into_stream();
+    let mut hasher = blake3::Hasher::new();
+    while let Some(chunk) = stream.next().await {
+        hasher.update(&chunk?);
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// The store behind `source`, the object path inside it, and the label prefix
+/// (`s3://bucket/`) used for lineage.
+fn open_store(kind: &SourceKind, source: &str) -> Result<(Arc<dyn ObjectStore>, ObjectPath, String)> {
+    let options = match kind {
+        SourceKind::Store(store) => {
+            let path = ObjectPath::parse(source).with_context(|| format!("invalid object path: {source}"))?;
+            return Ok((store.clone(), path, String::new()));
+        }
+        SourceKind::LocalFile => return Err(anyhow!("{source} is a local path, not an object store URL")),
+        SourceKind::S3 { options }
+        | SourceKind::Gcs { options }
+        | SourceKind::Azure { options }
+        | SourceKind::Http { options } => options,
+    };
+    let url = Url::parse(source).with_context(|| format!("invalid source URL: {source}"))?;
+    let (store, path) = object_store::parse_url_opts(&url, options.iter().map(|(k, v)| (k, v.clone())))
+        .with_context(|| format!("open object store for {source}"))?;
+    let base = source
+        .strip_suffix(path.as_ref())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{}/", &url[..url::Position::BeforePath]));
+    Ok((Arc::from(store), path, base))
+}
@@ -0,0 +1,54 @@
+//! The chunked CSV parser against the sequential reader (`parse_threads = 1`), on a source
+//! spanning several chunks with quoted newlines, CRLF records and quarantined records.
+use ingest::IngestStats;
+use std::fs::write;
+use std::path::Path;
+use testkit::{HEADER, read_parts};
+
+/// ~22 MB: well over two of the parser's 8 MiB chunks, so chunk ends fall inside quoted
+/// newlines, after CRLF and next to quarantined records.
+fn csv_source() -> Vec<u8> {
+    let mut data = format!("{HEADER}\n").into_bytes();
+    for i in 0..90_000u32 {
+        let record = match i {
+            i if i % 997 == 0 => format!("M{i},2023-01-01,1\n").into_bytes(),
+            i if i % 1_499 == 0 => [format!("M{i},").as_bytes(), b"\xff", ",".repeat(38).as_bytes(), b"\n"].concat(),
+            i => {
+                let end = if i % 3 == 0 { "\r\n" } else { "\n" };
+                let street = format!("\"RUE {i}\nBATIMENT \"\"B\"\"\n{}\"", "X".repeat(100 + i as usize % 50));
+                format!("M{i},2023-01-01,1,Vente,1000,1,,{street},,75001{}{end}", ",".repeat(30)).into_bytes()
+            }
+        };
+        data.extend(record);
+    }
+    data
+}
+
+async fn ingest(root: &Path, source: &Path, parse_threads: usize) -> IngestStats {
+    testkit::ingest_with(root, source, "2024-01-01", |cfg| {
+        cfg.parse_threads = parse_threads;
+        cfg.max_rows_per_part = 25_000;
+        cfg.max_error_ratio = 1.0;
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn parallel_parsing_matches_the_sequential_reader() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("dvf.csv");
+    write(&source, csv_source()).unwrap();
+    let (seq_root, par_root) = (dir.path().join("sequential"), dir.path().join("parallel"));
+
+    let seq = ingest(&seq_root, &source, 1).await;
+    let par = ingest(&par_root, &source, 4).await;
+    assert!(seq.parts_written > 1);
+    assert!(seq.quarantined > 100);
+    assert_eq!((par.rows_in, par.quarantined, par.parts_written), (seq.rows_in, seq.quarantined, seq.parts_written));
+
+    // Bronze rows with their row_number, part by part
+    assert_eq!(read_parts(&par.out_dir), read_parts(&seq.out_dir));
+    // quarantined records with their line_number and byte_offset
+    assert_eq!(read_parts(&par.errors_out), read_parts(&seq.errors_out));
+}
@@ -0,0 +1,87 @@
+//! Ingests stopped after each number of completed parts, then resumed: Bronze and its
+//! quarantine must come out as from an uninterrupted ingest, whether the stop fell inside a
+//! CSV, a gzip stream, a zip member or between them.
+use ingest::{IngestConfig, IngestStats};
+use serde_json::Value;
+use std::fs::{create_dir, read_to_string, write};
+use std::path::{Path, PathBuf};
+use testkit::{Line, gzip, read_parts, zip};
+
+/// A DVF file whose records only carry an `id_mutation`: `prefix1`, `prefix2`, ...
+fn source(prefix: &str, rows: usize) -> String {
+    let lines: Vec<Line> = (1..=rows).map(|i| Line::id(&format!("{prefix}{i}"))).collect();
+    testkit::csv(&lines)
+}
+
+/// `raw/a.csv` (with a malformed record), `raw/b.csv.gz` and `raw/c.zip` (members
+/// `m1.csv` and `m2.csv`).
+async fn sources(dir: &Path) -> PathBuf {
+    let raw = dir.join("raw");
+    create_dir(&raw).unwrap();
+    write(raw.join("a.csv"), source("A", 7).replacen("\nA5,", "\nA-bad,1\nA5,", 1)).unwrap();
+    write(raw.join("b.csv.gz"), gzip(source("B", 5).as_bytes()).await).unwrap();
+    let members = [("m1.csv", source("M", 4)), ("m2.csv", source("N", 4))];
+    let members: Vec<(&str, &[u8])> = members.iter().map(|(name, data)| (*name, data.as_bytes())).collect();
+    write(raw.join("c.zip"), zip(&members).await).unwrap();
+    raw
+}
+
+async fn ingest(root: &Path, raw: &Path, tweak: impl FnOnce(&mut IngestConfig)) -> anyhow::Result<IngestStats> {
+    testkit::ingest_with(root, raw, "2024-01-01", |cfg| {
+        cfg.max_rows_per_part = 3;
+        cfg.max_error_ratio = 1.0;
+        tweak(cfg);
+    })
+    .await
+}
+
+#[tokio::test]
+async fn a_resumed_ingest_matches_an_uninterrupted_one() {
+    let dir = tempfile::tempdir().unwrap();
+    let raw = sources(dir.path()).await;
+    let clean = ingest(&dir.path().join("clean"), &raw, |_| {}).await.unwrap();
+    assert_eq!((clean.rows_in, clean.quarantined), (20, 1));
+    let expected = (read_parts(&clean.out_dir), read_parts(&clean.errors_out));
+
+    let mut stops = Vec::new();
+    for n in 1..clean.parts_written as usize {
+        let root = dir.path().join(format!("stopped-{n}"));
+        let err = ingest(&root, &raw, |cfg| {
+            cfg.run_id = "run-1".to_string();
+            cfg.stop_after_parts = Some(n);
+        })
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("stopped after"), "{err:#}");
+
+        let staged = root.join("bronze/dvf/_staging/run-1/ingest_date=2024-01-01");
+        let checkpoint = read_to_string(staged.join("_checkpoint.json")).unwrap();
+        let checkpoint: Value = serde_json::from_str(&checkpoint).unwrap();
+        assert_eq!(checkpoint["parts"], n);
+        let stream = &checkpoint["stream"];
+        let label = stream["label"].as_str().map(|l| Path::new(l).file_name().unwrap().to_string_lossy().to_string());
+        stops.push((label, stream["record"].as_i64()));
+        // the part being written when the ingest stopped
+        write(staged.join(format!("part-{n:06}.arrow.tmp")), b"torn").unwrap();
+
+        let resumed = ingest(&root, &raw, |cfg| cfg.resume = true).await.unwrap();
+        assert_eq!((resumed.resumed_parts, resumed.run_id.as_str()), (n as u32, "run-1"));
+        assert_eq!((resumed.rows_in, resumed.quarantined), (clean.rows_in, clean.quarantined));
+        let bronze = (read_parts(&resumed.out_dir), read_parts(&resumed.errors_out));
+        assert_eq!(bronze, expected, "stopped after {n} parts");
+        assert!(!root.join("bronze/dvf/_staging").exists());
+    }
+    // where each stop left the stream being read: the first record not in a completed part
+    let at = |stream: &str, record| (Some(stream.to_string()), Some(record));
+    assert_eq!(
+        stops,
+        [
+            at("a.csv", 3),
+            at("a.csv", 7),
+            at("b.csv.gz", 2),
+            at("b.csv.gz", 5),
+            at("c.zip!m1.csv", 3),
+            at("c.zip!m2.csv", 2),
+        ]
+    );
+}
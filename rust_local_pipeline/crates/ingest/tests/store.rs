@@ -0,0 +1,110 @@
+//! Object-store sources, ingested from an `InMemory` store: one object, a prefix, a `*`
+//! glob over keys, and gzip and zip objects.
+use async_zip::base::write::ZipFileWriter;
+use async_zip::{Compression, StringEncoding, ZipEntryBuilder, ZipString};
+use ingest::{IngestStats, SourceKind};
+use object_store::memory::InMemory;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload};
+use std::path::Path;
+use std::sync::Arc;
+use testkit::{Line, column_pairs, gzip, pairs, zip};
+
+/// A DVF file whose records only carry an `id_mutation`.
+fn csv(ids: &[&str]) -> Vec<u8> {
+    let lines: Vec<Line> = ids.iter().map(|id| Line::id(id)).collect();
+    testkit::csv(&lines).into_bytes()
+}
+
+/// A zip archive holding one member whose name is CP437, without the UTF-8 flag.
+async fn cp437_zip(name: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut w = ZipFileWriter::new(Vec::new());
+    let name = ZipString::new(name.to_vec(), StringEncoding::Raw);
+    w.write_entry_whole(ZipEntryBuilder::new(name, Compression::Deflate), data).await.unwrap();
+    w.close().await.unwrap()
+}
+
+/// raw/2022/dvf.csv, raw/2023/dvf.csv, raw/dvf_2024.csv.gz, archive/dvf.zip and
+/// archive/cp437.zip.
+async fn store() -> Arc<dyn ObjectStore> {
+    let store = InMemory::new();
+    let objects = [
+        ("raw/2022/dvf.csv", csv(&["A1", "A2"])),
+        ("raw/2023/dvf.csv", csv(&["B1"])),
+        ("raw/dvf_2024.csv.gz", gzip(&csv(&["C1"])).await),
+        ("archive/dvf.zip", zip(&[("dvf_2021.csv", &csv(&["D1", "D2"]))]).await),
+        ("archive/cp437.zip", cp437_zip(b"dvf_2020_\x82.csv", &csv(&["E1"])).await), // é in CP437
+    ];
+    for (key, bytes) in objects {
+        store.put(&ObjectPath::from(key), PutPayload::from(bytes)).await.unwrap();
+    }
+    Arc::new(store)
+}
+
+/// Ingests `source` from `store` into a fresh storage root; returns the stats and the
+/// `(source_file, id_mutation)` of every Bronze row, in Bronze order.
+async fn ingest(store: Arc<dyn ObjectStore>, source: &str) -> (IngestStats, Vec<(String, String)>) {
+    let root = tempfile::tempdir().unwrap();
+    let stats = testkit::ingest_with(root.path(), Path::new(source), "2024-01-01", |cfg| {
+        cfg.source = SourceKind::Store(store);
+    })
+    .await
+    .unwrap();
+    let rows = column_pairs(&stats.parts, "source_file", "id_mutation");
+    (stats, rows)
+}
+
+#[tokio::test]
+async fn ingests_a_single_object() {
+    let (stats, rows) = ingest(store().await, "raw/2022/dvf.csv").await;
+    assert_eq!(stats.rows_in, 2);
+    assert_eq!(rows, pairs(&[("raw/2022/dvf.csv", "A1"), ("raw/2022/dvf.csv", "A2")]));
+}
+
+#[tokio::test]
+async fn ingests_every_object_under_a_prefix() {
+    let (stats, rows) = ingest(store().await, "raw").await;
+    assert_eq!(stats.files.len(), 3);
+    assert_eq!(
+        rows,
+        pairs(&[
+            ("raw/2022/dvf.csv", "A1"),
+            ("raw/2022/dvf.csv", "A2"),
+            ("raw/2023/dvf.csv", "B1"),
+            ("raw/dvf_2024.csv.gz", "C1"),
+        ])
+    );
+}
+
+#[tokio::test]
+async fn ingests_the_objects_matching_a_glob() {
+    let (_, rows) = ingest(store().await, "raw/*/dvf.csv").await;
+    assert_eq!(
+        rows,
+        pairs(&[("raw/2022/dvf.csv", "A1"), ("raw/2022/dvf.csv", "A2"), ("raw/2023/dvf.csv", "B1")])
+    );
+}
+
+#[tokio::test]
+async fn decompresses_a_gzip_object() {
+    let (stats, rows) = ingest(store().await, "raw/dvf_2024.csv.gz").await;
+    assert_eq!(stats.files[0].compression.to_string(), "gzip");
+    assert!(stats.bytes_uncompressed > stats.bytes_in);
+    assert_eq!(rows, pairs(&[("raw/dvf_2024.csv.gz", "C1")]));
+}
+
+#[tokio::test]
+async fn ingests_the_members_of_a_zip_object() {
+    let (stats, rows) = ingest(store().await, "archive/dvf.zip").await;
+    assert_eq!(stats.files[0].compression.to_string(), "zip");
+    assert_eq!(
+        rows,
+        pairs(&[("archive/dvf.zip!dvf_2021.csv", "D1"), ("archive/dvf.zip!dvf_2021.csv", "D2")])
+    );
+}
+
+#[tokio::test]
+async fn decodes_zip_member_names_that_are_not_utf8() {
+    let (_, rows) = ingest(store().await, "archive/cp437.zip").await;
+    assert_eq!(rows, pairs(&[("archive/cp437.zip!dvf_2020_\u{fffd}.csv", "E1")]));
+}
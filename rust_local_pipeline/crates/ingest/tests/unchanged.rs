@@ -0,0 +1,67 @@
+//! Drops made of several files where only some of them changed since the last ingest.
+use ingest::{IngestStats, OnUnchanged};
+use std::fs::write;
+use std::path::Path;
+use testkit::{Line, column_pairs, pairs};
+
+/// A DVF file whose records only carry an `id_mutation`.
+fn write_source(path: &Path, ids: &[&str]) {
+    let lines: Vec<Line> = ids.iter().map(|id| Line::id(id)).collect();
+    write(path, testkit::csv(&lines)).unwrap();
+}
+
+async fn ingest(root: &Path, source: &Path, ingest_date: &str, on_unchanged: OnUnchanged) -> anyhow::Result<IngestStats> {
+    testkit::ingest_with(root, source, ingest_date, |cfg| cfg.on_unchanged = on_unchanged).await
+}
+
+/// `(source file name, id_mutation)` of every Bronze row, sorted.
+fn bronze_rows(stats: &IngestStats) -> Vec<(String, String)> {
+    let mut rows: Vec<(String, String)> = column_pairs(&stats.parts, "source_file", "id_mutation")
+        .into_iter()
+        .map(|(f, id)| (Path::new(&f).file_name().unwrap().to_string_lossy().to_string(), id))
+        .collect();
+    rows.sort();
+    rows
+}
+
+#[tokio::test]
+async fn a_drop_with_one_changed_file_is_ingested_whole() {
+    let root = tempfile::tempdir().unwrap();
+    let raw = root.path().join("raw");
+    std::fs::create_dir(&raw).unwrap();
+    write_source(&raw.join("dvf_2022.csv"), &["A1", "A2"]);
+    write_source(&raw.join("dvf_2023.csv"), &["B1"]);
+
+    let first = ingest(root.path(), &raw, "2024-01-01", OnUnchanged::Skip).await.unwrap();
+    assert_eq!(first.rows_in, 3);
+
+    // only the 2023 file changes: the 2022 one is ingested again with it
+    write_source(&raw.join("dvf_2023.csv"), &["B1", "B2"]);
+    let second = ingest(root.path(), &raw, "2024-04-01", OnUnchanged::Fail).await.unwrap();
+    assert!(!second.unchanged());
+    assert!(second.skipped.is_empty());
+    assert_eq!(second.files.len(), 2);
+    assert_eq!(
+        bronze_rows(&second),
+        pairs(&[("dvf_2022.csv", "A1"), ("dvf_2022.csv", "A2"), ("dvf_2023.csv", "B1"), ("dvf_2023.csv", "B2")])
+    );
+}
+
+#[tokio::test]
+async fn a_drop_is_skipped_only_when_every_file_is_unchanged() {
+    let root = tempfile::tempdir().unwrap();
+    let raw = root.path().join("raw");
+    std::fs::create_dir(&raw).unwrap();
+    write_source(&raw.join("dvf_2022.csv"), &["A1"]);
+    write_source(&raw.join("dvf_2023.csv"), &["B1"]);
+    ingest(root.path(), &raw, "2024-01-01", OnUnchanged::Skip).await.unwrap();
+
+    let again = ingest(root.path(), &raw, "2024-04-01", OnUnchanged::Skip).await.unwrap();
+    assert!(again.unchanged());
+    assert_eq!(again.skipped.len(), 2);
+    assert!(again.skipped.iter().all(|s| s.ingested_on == "2024-01-01"));
+    assert!(!again.out_dir.exists());
+
+    let err = ingest(root.path(), &raw, "2024-04-01", OnUnchanged::Fail).await.unwrap_err();
+    assert!(err.to_string().contains("already ingested"), "{err:#}");
+}
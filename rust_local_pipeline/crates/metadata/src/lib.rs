@@ -1,19 +1,768 @@
-//! Minimal dataset descriptor loader (to be expanded).
-use anyhow::Result;
+//! Dataset descriptors loaded from `config/datasets/{slug}.toml`.
+use anyhow::{anyhow, bail, Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::Path;
+use time::macros::format_description;
+use time::{Date, Duration, Month};
+
+pub mod runs;
+pub mod staging;
+
+/// Default location of the descriptor files, relative to the working directory.
+pub const DEFAULT_CONFIG_DIR: &str = "./config/datasets";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatasetDescriptor {
     pub slug: String,
     pub schema_version: u32,
+    pub source: SourceSpec,
+    /// Source columns, in the order the publisher ships them.
+    pub columns: Vec<ColumnSpec>,
+    #[serde(default)]
+    pub dedup: DedupSpec,
+    /// How ingest reacts when the source header drifts from the previous ingest.
+    #[serde(default)]
+    pub drift: DriftSpec,
+    /// Gold partitioning, outermost first.
+    #[serde(default)]
+    pub partitions: Vec<PartitionSpec>,
+    /// Validation rules, evaluated in order; the first failing `reject` rule wins.
+    #[serde(default)]
+    pub rules: Vec<RuleSpec>,
+    /// Silver columns computed from source columns, appended after them.
+    #[serde(default)]
+    pub derived: Vec<DerivedSpec>,
+    /// Column profile written next to Silver by validate.
+    #[serde(default)]
+    pub profile: ProfileSpec,
+    /// Coordinate bounds per territory, for `territory` rules.
+    #[serde(default)]
+    pub territories: Vec<TerritorySpec>,
+    /// Mutation-level Silver table written by validate next to the row-level one.
+    #[serde(default)]
+    pub mutations: Option<MutationSpec>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceSpec {
+    /// `csv`, `parquet`, `ndjson` or `xlsx`.
+    #[serde(default = "default_format")]
     pub format: String,
+    /// Single character, or `"auto"` to sniff it from the start of each file.
+    #[serde(default = "default_delim")]
+    pub delimiter: String,
+    /// Single character, or `"auto"` to sniff it alongside the delimiter.
+    #[serde(default = "default_quote")]
+    pub quote: String,
+    /// WHATWG label (`utf-8`, `windows-1252`, `latin1`, ...); sources are transcoded to UTF-8.
+    #[serde(default = "default_utf8")]
+    pub encoding: String,
+    /// XLSX worksheet to read; defaults to the first one.
+    #[serde(default)]
+    pub sheet: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnSpec {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub ty: ColumnType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub normalize: Option<Normalize>,
+    /// Left-pad with '0' up to this width (e.g. 5 for postal codes).
+    #[serde(default)]
+    pub pad_left: Option<usize>,
+    /// `time` format description for `date` columns. Defaults to `[year]-[month]-[day]`.
+    #[serde(default)]
+    pub date_format: Option<String>,
+    /// Former or alternative source names, mapped back to `name` under the `alias` drift policy.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalize {
+    Upper,
+    Lower,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSpec {
+    /// Reason code written to Rejects (e.g. `DVF_DATE_INVALID`).
+    pub code: String,
+    #[serde(default)]
+    pub detail: Option<String>,
+    #[serde(default)]
+    pub action: RuleAction,
+    #[serde(flatten)]
+    pub check: RuleCheck,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    /// Send the row to Rejects.
+    #[default]
+    Reject,
+    /// Keep the row, null the offending value(s).
+    Nullify,
+    /// Keep the row, replace the offending value with the one the reference gives
+    /// (referential rules only); nullify it when the reference is ambiguous.
+    Repair,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleCheck {
+    /// Columns must be present. Empty = every column declared `required = true`.
+    Required {
+        #[serde(default)]
+        columns: Vec<String>,
+    },
+    /// Value must parse as the column's declared type.
+    Type { column: String },
+    /// Inclusive bounds; numbers for numeric columns. Dates take `YYYY-MM-DD`, or `ingest_date` /
+    /// `today` shifted by `+N`/`-N` days, months or years (e.g. `ingest_date+1m`, `today-2y`).
+    Range {
+        column: String,
+        #[serde(default)]
+        min: Option<Bound>,
+        #[serde(default)]
+        max: Option<Bound>,
+    },
+    Regex { column: String, pattern: String },
+    Enum { column: String, values: Vec<String> },
+    /// All columns null, or all present.
+    PairedNullability { columns: Vec<String> },
+    /// Point inside the bounding box of the territory (`[[territories]]`) its department belongs to.
+    Territory { lon: String, lat: String, department: String },
+    /// Point inside its department's outline in the departement reference
+    /// (`gold/ref/departements.parquet`), or within `tolerance` degrees of it.
+    InDepartment {
+        lon: String,
+        lat: String,
+        department: String,
+        #[serde(default)]
+        tolerance: f64,
+    },
+    /// Commune code listed in the INSEE COG, as a current or a former commune.
+    CommuneKnown { commune: String },
+    /// Commune code of a current commune, not of one merged into a commune nouvelle.
+    /// `repair` puts the commune nouvelle in its place and keeps the old code in `old_commune`.
+    CommuneCurrent {
+        commune: String,
+        #[serde(default)]
+        old_commune: Option<String>,
+    },
+    /// Department code of the COG.
+    DepartmentKnown { department: String },
+    /// Department of the commune (`repair`: the commune's department).
+    DepartmentCommune { department: String, commune: String },
+    /// Postal code listed in the La Poste table.
+    PostalKnown { postal_code: String },
+    /// Postal code serving the commune (`repair`: the commune's only postal code).
+    PostalCommune { postal_code: String, commune: String },
 }
 
-pub fn load_descriptor(_slug: &str) -> Result<DatasetDescriptor> {
-    // Placeholder: will read config/datasets/{slug}.toml later.
-    Ok(DatasetDescriptor {
-        slug: "dvf".to_string(),
-        schema_version: 1,
-        format: "csv".to_string(),
-    })
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Bound {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DerivedSpec {
+    /// Int16 year of a date column.
+    Year { name: String, column: String },
+    /// First day of the month of a date column.
+    MonthStart { name: String, column: String },
+    Geohash {
+        name: String,
+        lon: String,
+        lat: String,
+        #[serde(default = "default_geohash_precision")]
+        precision: usize,
+    },
+    /// `numerator / denominator` when `denominator > min_denominator`; null otherwise.
+    Ratio {
+        name: String,
+        numerator: String,
+        denominator: String,
+        #[serde(default)]
+        min_denominator: f64,
+    },
+    /// BLAKE3 of the `[dedup] key_columns` (FixedSizeBinary(32)).
+    DedupKey { name: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftSpec {
+    #[serde(default)]
+    pub policy: DriftPolicy,
+    /// Name similarity (0..1) above which a removed/added pair is reported as a rename.
+    #[serde(default = "default_min_similarity")]
+    pub min_similarity: f64,
+}
+
+impl Default for DriftSpec {
+    fn default() -> Self {
+        Self {
+            policy: DriftPolicy::default(),
+            min_similarity: default_min_similarity(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftPolicy {
+    /// Refuse to ingest a drifted header.
+    Fail,
+    /// Report the drift and ingest the header as-is.
+    #[default]
+    Warn,
+    /// Rename aliased and renamed-looking columns back to their known names; report the rest.
+    Alias,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerritorySpec {
+    pub name: String,
+    /// Department codes of the territory. Empty = every department no other territory lists.
+    #[serde(default)]
+    pub departments: Vec<String>,
+    pub lon: [f64; 2], // [min, max]
+    pub lat: [f64; 2], // [min, max]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileSpec {
+    /// Profile every validation (`pipeline validate --profile` profiles a single run).
+    #[serde(default)]
+    pub enabled: bool,
+    /// Silver columns whose most frequent values are reported; keep them low-cardinality.
+    #[serde(default)]
+    pub top_k_columns: Vec<String>,
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    /// Quantiles (0..1) reported for numeric columns.
+    #[serde(default = "default_quantiles")]
+    pub quantiles: Vec<f64>,
+}
+
+impl Default for ProfileSpec {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            top_k_columns: Vec::new(),
+            top_k: default_top_k(),
+            quantiles: default_quantiles(),
+        }
+    }
+}
+
+/// One row per `key` (e.g. a DVF mutation) in `silver/<slug>_mutations`, aggregating the
+/// disposition/lot rows that share it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MutationSpec {
+    pub key: String, // e.g. "id_mutation"
+    /// Silver columns taken from the first row of each mutation (after `key`).
+    #[serde(default)]
+    pub carry: Vec<String>,
+    pub price: String,      // e.g. "valeur_fonciere" (the mutation's total, repeated on its rows)
+    pub surface: String,    // e.g. "surface_reelle_bati"
+    pub local_type: String, // e.g. "type_local"
+    pub parcel: String,     // e.g. "id_parcelle"
+    #[serde(default)]
+    pub lots: Option<String>, // e.g. "nombre_lots"
+    /// Columns identifying a local: rows repeating one (a row per culture) count once.
+    /// Empty = every row with a `local_type` is a local.
+    #[serde(default)]
+    pub local_key: Vec<String>,
+    /// Below this built surface, `prix_m2` is null.
+    #[serde(default)]
+    pub min_surface: f64,
+}
+
+/// Columns `validate` appends after `key` and `carry` in the mutation table.
+pub const MUTATION_COLUMNS: [&str; 8] = [
+    "nb_lignes",
+    "nb_parcelles",
+    "multi_parcelles",
+    "nb_locaux",
+    "nb_lots",
+    "surface_bati",
+    "type_local_dominant",
+    "prix_m2",
+];
+
+/// Dataset directory of the mutation table of `slug` (Silver and Gold).
+pub fn mutations_slug(slug: &str) -> String {
+    format!("{slug}_mutations")
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DedupSpec {
+    /// Columns hashed into `mutation_key`. Empty = every source column.
+    #[serde(default)]
+    pub key_columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartitionSpec {
+    /// Silver column holding the partition value.
+    pub column: String,
+    /// Directory prefix, i.e. `<dir>=<value>`.
+    pub dir: String,
+}
+
+/// Typed Silver representation of a column (`type = "..."` in the descriptor).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ColumnType {
+    #[default]
+    Utf8,
+    Int16,
+    Int32,
+    Int64,
+    Float64,
+    Date,
+    Decimal { precision: u8, scale: i8 },
+}
+
+fn default_format() -> String {
+    "csv".to_string()
+}
+fn default_delim() -> String {
+    ",".to_string()
+}
+fn default_quote() -> String {
+    "\"".to_string()
+}
+fn default_utf8() -> String {
+    "utf-8".to_string()
+}
+fn default_min_similarity() -> f64 {
+    0.8
+}
+fn default_top_k() -> usize {
+    10
+}
+fn default_quantiles() -> Vec<f64> {
+    vec![0.01, 0.25, 0.5, 0.75, 0.99]
+}
+fn default_geohash_precision() -> usize {
+    6
+}
+
+impl TryFrom<String> for ColumnType {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        let t = s.trim().to_ascii_lowercase();
+        Ok(match t.as_str() {
+            "utf8" | "string" => ColumnType::Utf8,
+            "int16" => ColumnType::Int16,
+            "int32" => ColumnType::Int32,
+            "int64" => ColumnType::Int64,
+            "float64" => ColumnType::Float64,
+            "date" => ColumnType::Date,
+            _ => {
+                // decimal(p,s)
+                let inner = t
+                    .strip_prefix("decimal(")
+                    .and_then(|r| r.strip_suffix(')'))
+                    .ok_or_else(|| anyhow!("unknown column type: {s}"))?;
+                let (p, sc) = inner
+                    .split_once(',')
+                    .ok_or_else(|| anyhow!("decimal type needs (precision,scale): {s}"))?;
+                ColumnType::Decimal {
+                    precision: p.trim().parse().with_context(|| format!("precision in {s}"))?,
+                    scale: sc.trim().parse().with_context(|| format!("scale in {s}"))?,
+                }
+            }
+        })
+    }
+}
+
+impl From<ColumnType> for String {
+    fn from(t: ColumnType) -> String {
+        t.to_string()
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnType::Utf8 => f.write_str("utf8"),
+            ColumnType::Int16 => f.write_str("int16"),
+            ColumnType::Int32 => f.write_str("int32"),
+            ColumnType::Int64 => f.write_str("int64"),
+            ColumnType::Float64 => f.write_str("float64"),
+            ColumnType::Date => f.write_str("date"),
+            ColumnType::Decimal { precision, scale } => write!(f, "decimal({precision},{scale})"),
+        }
+    }
+}
+
+impl RuleCheck {
+    /// Columns the check reads (empty for `required` without explicit columns).
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            RuleCheck::Required { columns } | RuleCheck::PairedNullability { columns } => {
+                columns.iter().map(String::as_str).collect()
+            }
+            RuleCheck::Type { column }
+            | RuleCheck::Range { column, .. }
+            | RuleCheck::Regex { column, .. }
+            | RuleCheck::Enum { column, .. } => vec![column.as_str()],
+            RuleCheck::Territory { lon, lat, department }
+            | RuleCheck::InDepartment { lon, lat, department, .. } => {
+                vec![lon.as_str(), lat.as_str(), department.as_str()]
+            }
+            RuleCheck::CommuneKnown { commune } => vec![commune.as_str()],
+            RuleCheck::CommuneCurrent { commune, old_commune } => {
+                std::iter::once(commune).chain(old_commune).map(String::as_str).collect()
+            }
+            RuleCheck::DepartmentKnown { department } => vec![department.as_str()],
+            RuleCheck::PostalKnown { postal_code } => vec![postal_code.as_str()],
+            RuleCheck::DepartmentCommune { department: a, commune: b }
+            | RuleCheck::PostalCommune { postal_code: a, commune: b } => vec![a.as_str(), b.as_str()],
+        }
+    }
+
+    /// Whether the `repair` action applies: the reference determines the right value.
+    pub fn repairable(&self) -> bool {
+        matches!(
+            self,
+            RuleCheck::CommuneCurrent { .. } | RuleCheck::DepartmentCommune { .. } | RuleCheck::PostalCommune { .. }
+        )
+    }
+
+    /// Whether the check reads the INSEE COG commune table.
+    pub fn needs_communes(&self) -> bool {
+        matches!(
+            self,
+            RuleCheck::CommuneKnown { .. }
+                | RuleCheck::CommuneCurrent { .. }
+                | RuleCheck::DepartmentKnown { .. }
+                | RuleCheck::DepartmentCommune { .. }
+        )
+    }
+
+    /// Whether the check reads the La Poste postal code table.
+    pub fn needs_postal_codes(&self) -> bool {
+        matches!(self, RuleCheck::PostalKnown { .. } | RuleCheck::PostalCommune { .. })
+    }
+}
+
+impl DerivedSpec {
+    pub fn name(&self) -> &str {
+        match self {
+            DerivedSpec::Year { name, .. }
+            | DerivedSpec::MonthStart { name, .. }
+            | DerivedSpec::Geohash { name, .. }
+            | DerivedSpec::Ratio { name, .. }
+            | DerivedSpec::DedupKey { name } => name,
+        }
+    }
+
+    /// Source columns the derivation reads.
+    pub fn inputs(&self) -> Vec<&str> {
+        match self {
+            DerivedSpec::Year { column, .. } | DerivedSpec::MonthStart { column, .. } => {
+                vec![column.as_str()]
+            }
+            DerivedSpec::Geohash { lon, lat, .. } => vec![lon.as_str(), lat.as_str()],
+            DerivedSpec::Ratio { numerator, denominator, .. } => {
+                vec![numerator.as_str(), denominator.as_str()]
+            }
+            DerivedSpec::DedupKey { .. } => Vec::new(),
+        }
+    }
+}
+
+impl DatasetDescriptor {
+    pub fn column(&self, name: &str) -> Option<&ColumnSpec> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    pub fn required_columns(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .filter(|c| c.required)
+            .map(|c| c.name.clone())
+            .collect()
+    }
+
+    /// Single-byte CSV delimiter (`"\t"` is accepted for tabs); `None` = sniff.
+    pub fn delimiter(&self) -> Result<Option<u8>> {
+        single_byte("delimiter", &self.source.delimiter)
+    }
+
+    /// Single-byte CSV quote character; `None` = sniff.
+    pub fn quote(&self) -> Result<Option<u8>> {
+        single_byte("quote", &self.source.quote)
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.columns.is_empty() {
+            bail!("descriptor {} declares no columns", self.slug);
+        }
+        let mut names = HashSet::new();
+        for c in &self.columns {
+            if !names.insert(c.name.as_str()) {
+                bail!("descriptor {}: duplicate column {}", self.slug, c.name);
+            }
+        }
+        let mut aliases = HashSet::new();
+        for a in self.columns.iter().flat_map(|c| &c.aliases) {
+            if names.contains(a.as_str()) || !aliases.insert(a.as_str()) {
+                bail!("descriptor {}: alias {a} is already a column or another alias", self.slug);
+            }
+        }
+        if !(0.0..=1.0).contains(&self.drift.min_similarity) {
+            bail!("descriptor {}: drift.min_similarity must be within 0..1", self.slug);
+        }
+        for k in &self.dedup.key_columns {
+            if !names.contains(k.as_str()) {
+                bail!("descriptor {}: dedup key column {k} is not a declared column", self.slug);
+            }
+        }
+        for r in &self.rules {
+            for c in r.check.columns() {
+                if !names.contains(c) {
+                    bail!("descriptor {}: rule {} reads unknown column {c}", self.slug, r.code);
+                }
+            }
+            if r.action == RuleAction::Nullify && matches!(r.check, RuleCheck::Required { .. }) {
+                bail!("descriptor {}: rule {} cannot nullify a required check", self.slug, r.code);
+            }
+            if r.action == RuleAction::Repair && !r.check.repairable() {
+                bail!("descriptor {}: rule {} has nothing to repair from", self.slug, r.code);
+            }
+            match &r.check {
+                RuleCheck::Range { column, min, max } if self.column(column).is_some_and(|c| c.ty == ColumnType::Date) => {
+                    let probe = Date::from_calendar_date(2000, Month::January, 1)?;
+                    for b in min.iter().chain(max) {
+                        b.resolve_date(probe, probe)
+                            .with_context(|| format!("descriptor {}: rule {}", self.slug, r.code))?;
+                    }
+                }
+                RuleCheck::Territory { .. } if self.territories.is_empty() => {
+                    bail!("descriptor {}: rule {} needs [[territories]]", self.slug, r.code);
+                }
+                RuleCheck::InDepartment { tolerance, .. } if tolerance.is_nan() || *tolerance < 0.0 => {
+                    bail!("descriptor {}: rule {} tolerance must be >= 0", self.slug, r.code);
+                }
+                _ => {}
+            }
+        }
+        // Required columns are non-nullable in Silver: something must reject blanks and bad values
+        for c in self.columns.iter().filter(|c| c.required) {
+            let covered = |f: &dyn Fn(&RuleCheck) -> bool| {
+                self.rules
+                    .iter()
+                    .any(|r| r.action == RuleAction::Reject && f(&r.check))
+            };
+            let presence = covered(&|k| match k {
+                RuleCheck::Required { columns } => columns.is_empty() || columns.contains(&c.name),
+                _ => false,
+            });
+            if !presence {
+                bail!("descriptor {}: required column {} has no `required` rule", self.slug, c.name);
+            }
+            let typed = c.ty == ColumnType::Utf8
+                || covered(&|k| matches!(k, RuleCheck::Type { column } if *column == c.name));
+            if !typed {
+                bail!("descriptor {}: required column {} has no `type` rule", self.slug, c.name);
+            }
+        }
+        for d in &self.derived {
+            for c in d.inputs() {
+                if !names.contains(c) {
+                    bail!("descriptor {}: derived {} reads unknown column {c}", self.slug, d.name());
+                }
+            }
+            if !names.insert(d.name()) {
+                bail!("descriptor {}: derived column {} clashes with another column", self.slug, d.name());
+            }
+        }
+        let mut claimed = HashSet::new();
+        let mut fallback = false;
+        for t in &self.territories {
+            let [x0, x1] = t.lon;
+            let [y0, y1] = t.lat;
+            if !(-180.0 <= x0 && x0 <= x1 && x1 <= 180.0 && -90.0 <= y0 && y0 <= y1 && y1 <= 90.0) {
+                bail!("descriptor {}: territory {} needs lon/lat as [min, max] in degrees", self.slug, t.name);
+            }
+            if t.departments.is_empty() && std::mem::replace(&mut fallback, true) {
+                bail!("descriptor {}: only one territory can leave `departments` empty", self.slug);
+            }
+            for d in &t.departments {
+                if !claimed.insert(d.as_str()) {
+                    bail!("descriptor {}: department {d} is listed by two territories", self.slug);
+                }
+            }
+        }
+        // `names` now holds every Silver column
+        for c in &self.profile.top_k_columns {
+            if !names.contains(c.as_str()) {
+                bail!("descriptor {}: profile column {c} is not a Silver column", self.slug);
+            }
+        }
+        if self.profile.quantiles.iter().any(|q| !(0.0..=1.0).contains(q)) {
+            bail!("descriptor {}: profile.quantiles must be within 0..1", self.slug);
+        }
+        if let Some(m) = &self.mutations {
+            let inputs = [&m.key, &m.price, &m.surface, &m.local_type, &m.parcel];
+            for c in inputs.into_iter().chain(&m.carry).chain(&m.lots).chain(&m.local_key) {
+                if !names.contains(c.as_str()) {
+                    bail!("descriptor {}: mutations column {c} is not a Silver column", self.slug);
+                }
+            }
+            let mut out: HashSet<&str> = MUTATION_COLUMNS.into_iter().collect();
+            for c in std::iter::once(&m.key).chain(&m.carry) {
+                if !out.insert(c.as_str()) {
+                    bail!("descriptor {}: mutations column {c} is carried twice or clashes with an aggregate", self.slug);
+                }
+            }
+            for p in &self.partitions {
+                if !out.contains(p.column.as_str()) {
+                    bail!("descriptor {}: partition column {} must be carried by [mutations]", self.slug, p.column);
+                }
+            }
+            if m.min_surface.is_nan() || m.min_surface < 0.0 {
+                bail!("descriptor {}: mutations.min_surface must be >= 0", self.slug);
+            }
+        }
+        self.delimiter()?;
+        self.quote()?;
+        Ok(())
+    }
+}
+
+impl Bound {
+    /// The date of a bound on a date column, given the partition's `ingest_date` and the
+    /// reference clock's `today`.
+    pub fn resolve_date(&self, ingest_date: Date, today: Date) -> Result<Date> {
+        let Bound::Text(s) = self else { bail!("date bound expected, got {self:?}") };
+        let s = s.trim();
+        let (anchor, shift) = if let Some(shift) = s.strip_prefix("ingest_date") {
+            (ingest_date, shift)
+        } else if let Some(shift) = s.strip_prefix("today") {
+            (today, shift)
+        } else {
+            let d = Date::parse(s, format_description!("[year]-[month]-[day]"))
+                .with_context(|| format!("date bound {s:?}: expected YYYY-MM-DD, ingest_date or today"))?;
+            return Ok(d);
+        };
+        if shift.is_empty() {
+            return Ok(anchor);
+        }
+        let bad = || anyhow!("date bound {s:?}: expected a shift like +7d, -1m or +2y");
+        let (sign, rest) = match shift.as_bytes()[0] {
+            b'+' => (1, &shift[1..]),
+            b'-' => (-1, &shift[1..]),
+            _ => return Err(bad()),
+        };
+        let (n, unit) = match rest.as_bytes().last() {
+            Some(&u @ (b'd' | b'm' | b'y')) => (&rest[..rest.len() - 1], u),
+            _ => return Err(bad()),
+        };
+        if n.is_empty() || !n.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(bad());
+        }
+        let n = i64::from(n.parse::<u32>().map_err(|_| bad())?);
+        let months = match unit {
+            b'd' => return anchor.checked_add(Duration::days(sign * n)).ok_or_else(bad),
+            b'm' => n,
+            _ => n * 12,
+        };
+        // calendar months; the day is clamped to the target month (Mar 31 - 1m = Feb 28/29)
+        let total = anchor.year() as i64 * 12 + anchor.month() as i64 - 1 + sign * months;
+        let year = i32::try_from(total.div_euclid(12)).map_err(|_| bad())?;
+        let month = Month::try_from((total.rem_euclid(12) + 1) as u8)?;
+        Date::from_calendar_date(year, month, anchor.day().min(month.length(year))).map_err(|_| bad())
+    }
+}
+
+fn single_byte(what: &str, v: &str) -> Result<Option<u8>> {
+    match v {
+        "auto" => Ok(None),
+        "\\t" | "\t" => Ok(Some(b'\t')),
+        d if d.len() == 1 && d.is_ascii() => Ok(Some(d.as_bytes()[0])),
+        d => Err(anyhow!("{what} must be a single ASCII character or \"auto\", got {d:?}")),
+    }
+}
+
+/// Loads `config/datasets/{slug}.toml` relative to the working directory.
+pub fn load_descriptor(slug: &str) -> Result<DatasetDescriptor> {
+    load_descriptor_from(Path::new(DEFAULT_CONFIG_DIR), slug)
+}
+
+pub fn load_descriptor_from(config_dir: &Path, slug: &str) -> Result<DatasetDescriptor> {
+    let path = config_dir.join(format!("{slug}.toml"));
+    let txt = read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+    let desc: DatasetDescriptor =
+        toml::from_str(&txt).with_context(|| format!("parse {}", path.display()))?;
+    if desc.slug != slug {
+        bail!("{} declares slug {:?}, expected {:?}", path.display(), desc.slug, slug);
+    }
+    desc.check()?;
+    Ok(desc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::date;
+
+    fn resolve(bound: &str, ingest_date: Date) -> Result<Date> {
+        Bound::Text(bound.to_string()).resolve_date(ingest_date, date!(2024 - 10 - 17))
+    }
+
+    #[test]
+    fn date_bounds_resolve_against_ingest_date_and_today() {
+        let ingest = date!(2024 - 10 - 01);
+        assert_eq!(resolve("2014-01-01", ingest).unwrap(), date!(2014 - 01 - 01));
+        assert_eq!(resolve("ingest_date", ingest).unwrap(), ingest);
+        assert_eq!(resolve(" today ", ingest).unwrap(), date!(2024 - 10 - 17));
+        assert_eq!(resolve("today+7d", ingest).unwrap(), date!(2024 - 10 - 24));
+        assert_eq!(resolve("ingest_date-1d", ingest).unwrap(), date!(2024 - 09 - 30));
+        assert_eq!(resolve("ingest_date+2y", ingest).unwrap(), date!(2026 - 10 - 01));
+        assert_eq!(resolve("today-2y", ingest).unwrap(), date!(2022 - 10 - 17));
+    }
+
+    #[test]
+    fn month_shifts_clamp_to_the_end_of_the_month() {
+        assert_eq!(resolve("ingest_date-1m", date!(2024 - 03 - 31)).unwrap(), date!(2024 - 02 - 29));
+        assert_eq!(resolve("ingest_date-1m", date!(2023 - 03 - 31)).unwrap(), date!(2023 - 02 - 28));
+        assert_eq!(resolve("ingest_date+1m", date!(2024 - 12 - 31)).unwrap(), date!(2025 - 01 - 31));
+        assert_eq!(resolve("ingest_date-13m", date!(2024 - 01 - 15)).unwrap(), date!(2022 - 12 - 15));
+        assert_eq!(resolve("ingest_date+2y", date!(2024 - 02 - 29)).unwrap(), date!(2026 - 02 - 28));
+    }
+
+    #[test]
+    fn malformed_date_bounds_are_errors() {
+        let ingest = date!(2024 - 10 - 01);
+        for bound in [
+            "today+", "today-", "today+7", "today+7w", "today7d", "today+d", "today++7d",
+            "today+-7d", "today+ 7d", "today+é", "today+7é", "todayé", "ingest_datex",
+            "yesterday", "2024-13-01", "2024-02-30", "today+99999999999d", "today+4294967295d",
+            "today+9000y", "today-4294967295y",
+        ] {
+            assert!(resolve(bound, ingest).is_err(), "{bound:?}");
+        }
+        let number = Bound::Number(2024.0).resolve_date(ingest, ingest);
+        assert!(number.is_err());
+    }
 }
@@ -0,0 +1,178 @@
+//! Run ledger: one entry per stage execution (successful or not) with what it read
+//! and wrote, so a Gold snapshot can be traced back to the source files it came from.
+//!
+//! Layout: `<root>/_runs/runs.jsonl`, append-only, one JSON entry per line. Inputs
+//! and outputs carry their BLAKE3 hash: an input of one entry is the output of the
+//! entry upstream of it (see [`upstream`]).
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use time::macros::format_description;
+use time::OffsetDateTime;
+
+pub const RUNS_DIR: &str = "_runs";
+const RUNS_FILE: &str = "runs.jsonl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Ok,
+    /// Ingest found nothing new (every source already ingested).
+    Unchanged,
+    Failed,
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RunStatus::Ok => "ok",
+            RunStatus::Unchanged => "unchanged",
+            RunStatus::Failed => "failed",
+        })
+    }
+}
+
+/// A file read or written by a stage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artifact {
+    pub path: String, // `archive.zip!member.csv` for zip members, URLs for object sources
+    pub blake3: String,
+    pub bytes: u64,
+}
+
+impl Artifact {
+    /// Hashes a local file.
+    pub fn hash_file(path: &Path) -> Result<Self> {
+        let mut f = File::open(path).with_context(|| format!("open {}", path.display()))?;
+        let mut hasher = blake3::Hasher::new();
+        let bytes = std::io::copy(&mut f, &mut hasher).with_context(|| format!("read {}", path.display()))?;
+        Ok(Self {
+            path: path.to_string_lossy().to_string(),
+            blake3: hasher.finalize().to_hex().to_string(),
+            bytes,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunEntry {
+    pub run_id: String, // shared by the stages of one `pipeline run`
+    pub stage: String,  // ingest, validate, curate, duckdb-refresh
+    pub dataset: String,
+    pub ingest_date: Option<String>,
+    pub snapshot_date: Option<String>,
+    pub status: RunStatus,
+    pub error: Option<String>,
+    pub started_at: String, // RFC 3339, UTC, milliseconds (sorts as text)
+    pub duration_ms: u64,
+    pub inputs: Vec<Artifact>,
+    pub outputs: Vec<Artifact>,
+    pub counts: BTreeMap<String, u64>, // e.g. rows_in, rows_out, rejects
+    pub version: String,                // e.g. "0.1.0 (git 1a2b3c4d5e6f)"
+}
+
+impl RunEntry {
+    /// An entry for a stage starting now; the caller fills in the outcome.
+    pub fn start(run_id: &str, stage: &str, dataset: &str, version: &str) -> Self {
+        Self {
+            run_id: run_id.to_string(),
+            stage: stage.to_string(),
+            dataset: dataset.to_string(),
+            ingest_date: None,
+            snapshot_date: None,
+            status: RunStatus::Ok,
+            error: None,
+            started_at: now_utc(),
+            duration_ms: 0,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            counts: BTreeMap::new(),
+            version: version.to_string(),
+        }
+    }
+}
+
+fn now_utc() -> String {
+    let format = format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z");
+    OffsetDateTime::now_utc().format(&format).unwrap_or_default()
+}
+
+pub fn ledger_path(root: &Path) -> PathBuf {
+    root.join(RUNS_DIR).join(RUNS_FILE)
+}
+
+/// Appends `entry` as one line and syncs it to disk.
+pub fn append(root: &Path, entry: &RunEntry) -> Result<()> {
+    let path = ledger_path(root);
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir).with_context(|| format!("mkdir -p {}", dir.display()))?;
+    }
+    let mut f = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("open {}", path.display()))?;
+    // start on a new line even after a torn write
+    let mut line = Vec::new();
+    let len = f.metadata()?.len();
+    if len > 0 {
+        let mut last = [0u8; 1];
+        f.seek(SeekFrom::Start(len - 1))?;
+        f.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            line.push(b'\n');
+        }
+    }
+    serde_json::to_writer(&mut line, entry)?;
+    line.push(b'\n');
+    f.write_all(&line).with_context(|| format!("append to {}", path.display()))?;
+    f.sync_all()?;
+    Ok(())
+}
+
+/// Every entry, oldest first; empty when nothing was recorded yet.
+pub fn load(root: &Path) -> Result<Vec<RunEntry>> {
+    let path = ledger_path(root);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let f = File::open(&path).with_context(|| format!("open {}", path.display()))?;
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            // a crash while appending can leave a torn line
+            Err(e) => tracing::warn!(file = %path.display(), line = i + 1, "ignoring unreadable run entry: {e}"),
+        }
+    }
+    Ok(entries)
+}
+
+/// Entries that wrote an input of `entry`: for each input, the latest successful
+/// entry started before it with an output of the same content.
+pub fn upstream<'a>(entries: &'a [RunEntry], entry: &RunEntry) -> Vec<&'a RunEntry> {
+    let mut found: Vec<&RunEntry> = Vec::new();
+    for input in &entry.inputs {
+        let producer = entries
+            .iter()
+            .filter(|e| e.status == RunStatus::Ok && e.started_at <= entry.started_at)
+            .filter(|e| !(e.run_id == entry.run_id && e.stage == entry.stage))
+            .filter(|e| e.outputs.iter().any(|o| o.blake3 == input.blake3))
+            .max_by(|a, b| a.started_at.cmp(&b.started_at));
+        if let Some(p) = producer
+            && !found.iter().any(|f| f.run_id == p.run_id && f.stage == p.stage)
+        {
+            found.push(p);
+        }
+    }
+    found
+}
@@ -0,0 +1,471 @@
+//! Atomic stage outputs. A stage writes each output partition into
+//! `<layer>/<slug>/_staging/<run_id>/<partition>/` and moves it into place once the
+//! stage has succeeded, with a `_SUCCESS` marker holding the run id. The next stage
+//! only reads partitions that carry the marker, so a crash never leaves it a
+//! truncated Arrow or Parquet file.
+//!
+//! A run holds a lock on `_staging/<run_id>/<partition>.lock` while it writes the
+//! partition, so that the cleanup of interrupted runs leaves the partitions of live
+//! runs alone. Two live runs of the same partition each commit in turn: the last one wins.
+use anyhow::{anyhow, Context, Result};
+use std::fs::{
+    create_dir_all, read_dir, remove_dir, remove_dir_all, remove_file, rename, File, OpenOptions, TryLockError,
+};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+pub const SUCCESS_FILE: &str = "_SUCCESS";
+pub const STAGING_DIR: &str = "_staging";
+/// Name of a replaced partition while a commit moves the new one in.
+const REPLACED_PREFIX: &str = ".replaced-";
+
+/// A new run id, sortable by start time: `20241017T093012Z-1f3a9c2e`.
+pub fn new_run_id() -> String {
+    let now = OffsetDateTime::now_utc();
+    let uuid = uuid::Uuid::new_v4().simple().to_string();
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z-{}",
+        now.year(),
+        u8::from(now.month()),
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second(),
+        &uuid[..8]
+    )
+}
+
+/// Whether `dir` is a partition committed by a successful stage.
+pub fn is_committed(dir: &Path) -> bool {
+    dir.join(SUCCESS_FILE).is_file()
+}
+
+/// Fails unless `dir` holds a committed partition (the stage that writes it succeeded).
+pub fn require_committed(dir: &Path) -> Result<()> {
+    if let Some(dataset_dir) = dir.parent() {
+        recover(dataset_dir)?;
+    }
+    if !dir.exists() {
+        return Err(anyhow!("{} not found", dir.display()));
+    }
+    if !is_committed(dir) {
+        return Err(anyhow!(
+            "{} has no {SUCCESS_FILE} marker: the stage writing it did not complete, run it again",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+/// One output partition being written by a run.
+#[derive(Debug, Clone)]
+pub struct StagedPartition {
+    run_id: String,
+    dir: PathBuf,    // <dataset dir>/_staging/<run_id>/<partition>
+    target: PathBuf, // <dataset dir>/<partition>
+    _lock: Arc<File>, // held until the partition is committed or discarded
+}
+
+impl StagedPartition {
+    /// Starts `partition` (e.g. `ingest_date=2024-01-01`) of `dataset_dir` afresh:
+    /// leftovers of interrupted runs for the same partition are removed, those of live
+    /// runs (whose lock is held) are kept.
+    pub fn create(dataset_dir: &Path, partition: &str, run_id: &str) -> Result<Self> {
+        recover(dataset_dir)?;
+        for (id, dir) in staged_runs(dataset_dir, partition)? {
+            if is_live(&dir)? {
+                tracing::warn!(run_id = %id, dir = %dir.display(), "keeping the staged partition of a live run");
+                continue;
+            }
+            tracing::info!(run_id = %id, dir = %dir.display(), "removing the staged partition of an interrupted run");
+            remove_staged(&dir)?;
+        }
+        Self::open(dataset_dir, partition, run_id)
+    }
+
+    /// Continues `partition` in the staging directory of `run_id`, creating it if needed.
+    /// Fails while another process holds it.
+    pub fn reopen(dataset_dir: &Path, partition: &str, run_id: &str) -> Result<Self> {
+        recover(dataset_dir)?;
+        Self::open(dataset_dir, partition, run_id)
+    }
+
+    fn open(dataset_dir: &Path, partition: &str, run_id: &str) -> Result<Self> {
+        let dir = dataset_dir.join(STAGING_DIR).join(run_id).join(partition);
+        create_dir_all(&dir).with_context(|| format!("mkdir -p {}", dir.display()))?;
+        let lock_path = lock_path(&dir);
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("open {}", lock_path.display()))?;
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(anyhow!("{} is being written by another live run", dir.display()));
+            }
+            Err(TryLockError::Error(e)) => return Err(e).with_context(|| format!("lock {}", lock_path.display())),
+        }
+        Ok(Self { run_id: run_id.to_string(), dir, target: dataset_dir.join(partition), _lock: Arc::new(lock) })
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Where the stage writes the partition.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where the partition lands on commit.
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Marks the partition complete and moves it into place, replacing the one a
+    /// previous run committed. Every file written under the stage is synced before the
+    /// marker, so a committed partition never holds a truncated file after a power loss.
+    /// The old partition is first moved aside to `_staging/<run_id>/.replaced-<partition>`
+    /// and deleted once the new one is in place; a crash in between is completed by
+    /// [`recover`].
+    pub fn commit(self) -> Result<PathBuf> {
+        sync_tree(&self.dir)?;
+        let marker = self.dir.join(SUCCESS_FILE);
+        {
+            let mut f = File::create(&marker).with_context(|| format!("create {}", marker.display()))?;
+            writeln!(f, "{}", self.run_id)?;
+            f.sync_all()?;
+        }
+        let run_dir = self.dir.parent().ok_or_else(|| anyhow!("no parent: {}", self.dir.display()))?;
+        let replaced = run_dir.join(format!(
+            "{REPLACED_PREFIX}{}",
+            self.target.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        if self.target.exists() {
+            rename(&self.target, &replaced)
+                .with_context(|| format!("rename {} -> {}", self.target.display(), replaced.display()))?;
+        }
+        rename(&self.dir, &self.target)
+            .with_context(|| format!("rename {} -> {}", self.dir.display(), self.target.display()))?;
+        if let Some(dataset_dir) = self.target.parent() {
+            sync_dir(dataset_dir)?;
+        }
+        if replaced.exists() {
+            remove_dir_all(&replaced).with_context(|| format!("remove {}", replaced.display()))?;
+        }
+        remove_lock(&self.dir)?;
+        remove_empty(run_dir);
+        Ok(self.target)
+    }
+
+    /// Drops what was written (a failed run).
+    pub fn discard(self) -> Result<()> {
+        remove_staged(&self.dir)
+    }
+}
+
+/// Finishes the commits of `dataset_dir` interrupted between moving the old partition
+/// aside and moving the new one in: the new partition is moved in when it carries its
+/// marker, else the old one is put back. Old partitions left next to a completed commit
+/// are deleted.
+pub fn recover(dataset_dir: &Path) -> Result<()> {
+    let staging = dataset_dir.join(STAGING_DIR);
+    if !staging.exists() {
+        return Ok(());
+    }
+    for run in read_dir(&staging).with_context(|| format!("read {}", staging.display()))? {
+        let run_dir = run?.path();
+        if !run_dir.is_dir() {
+            continue;
+        }
+        for entry in read_dir(&run_dir).with_context(|| format!("read {}", run_dir.display()))? {
+            let replaced = entry?.path();
+            let Some(partition) = replaced
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix(REPLACED_PREFIX))
+            else {
+                continue;
+            };
+            let target = dataset_dir.join(partition);
+            let staged = run_dir.join(partition);
+            if is_live(&staged)? {
+                continue; // the run is committing it
+            }
+            if !target.exists() {
+                let from = if is_committed(&staged) { &staged } else { &replaced };
+                tracing::warn!(
+                    partition = %target.display(),
+                    from = %from.display(),
+                    "completing an interrupted commit"
+                );
+                rename(from, &target)
+                    .with_context(|| format!("rename {} -> {}", from.display(), target.display()))?;
+                sync_dir(dataset_dir)?;
+            }
+            if replaced.exists() {
+                remove_dir_all(&replaced).with_context(|| format!("remove {}", replaced.display()))?;
+            }
+            if !staged.exists() {
+                remove_lock(&staged)?;
+            }
+        }
+        remove_empty(&run_dir);
+    }
+    Ok(())
+}
+
+/// Committed `ingest_date=` partitions of `dataset_dir` dated strictly before `ingest_date`,
+/// as `(ingest_date, dir)`, oldest first.
+pub fn committed_before(dataset_dir: &Path, ingest_date: &str) -> Result<Vec<(String, PathBuf)>> {
+    if !dataset_dir.exists() {
+        return Ok(Vec::new());
+    }
+    recover(dataset_dir)?;
+    let mut parts = Vec::new();
+    for entry in read_dir(dataset_dir).with_context(|| format!("read {}", dataset_dir.display()))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(d) = name.strip_prefix("ingest_date=")
+            && d < ingest_date
+            && is_committed(&entry.path())
+        {
+            parts.push((d.to_string(), entry.path()));
+        }
+    }
+    parts.sort();
+    Ok(parts)
+}
+
+/// Staging directories holding `partition`, as `(run_id, dir)`, oldest run first.
+pub fn staged_runs(dataset_dir: &Path, partition: &str) -> Result<Vec<(String, PathBuf)>> {
+    let staging = dataset_dir.join(STAGING_DIR);
+    if !staging.exists() {
+        return Ok(Vec::new());
+    }
+    let mut runs = Vec::new();
+    for entry in read_dir(&staging).with_context(|| format!("read {}", staging.display()))? {
+        let entry = entry?;
+        let dir = entry.path().join(partition);
+        if dir.is_dir() {
+            runs.push((entry.file_name().to_string_lossy().to_string(), dir));
+        }
+    }
+    runs.sort();
+    Ok(runs)
+}
+
+/// Writes a small file (manifest, report) in one step: temp file + rename.
+pub fn write_file_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir).with_context(|| format!("mkdir -p {}", dir.display()))?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    {
+        let mut f = File::create(&tmp).with_context(|| format!("create {}", tmp.display()))?;
+        f.write_all(contents)?;
+        f.sync_all()?;
+    }
+    rename(&tmp, path).with_context(|| format!("rename {} -> {}", tmp.display(), path.display()))?;
+    Ok(())
+}
+
+fn remove_staged(dir: &Path) -> Result<()> {
+    if dir.exists() {
+        remove_dir_all(dir).with_context(|| format!("remove {}", dir.display()))?;
+    }
+    remove_lock(dir)?;
+    if let Some(run_dir) = dir.parent() {
+        remove_empty(run_dir);
+    }
+    Ok(())
+}
+
+/// `_staging/<run_id>/<partition>.lock` for the staged partition `dir`.
+fn lock_path(dir: &Path) -> PathBuf {
+    let mut path = dir.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+/// Whether a live run holds the lock of the staged partition `dir`. A run that crashed
+/// released it with its process.
+fn is_live(dir: &Path) -> Result<bool> {
+    let path = lock_path(dir);
+    let Ok(lock) = File::open(&path) else {
+        return Ok(false);
+    };
+    match lock.try_lock_shared() {
+        Ok(()) => Ok(false),
+        Err(TryLockError::WouldBlock) => Ok(true),
+        Err(TryLockError::Error(e)) => Err(e).with_context(|| format!("lock {}", path.display())),
+    }
+}
+
+fn remove_lock(dir: &Path) -> Result<()> {
+    let path = lock_path(dir);
+    if path.exists() {
+        remove_file(&path).with_context(|| format!("remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Removes a run's staging directory, then `_staging/`, once nothing is left in them
+/// (`remove_dir` fails on a directory that is not empty, e.g. another run's).
+fn remove_empty(run_dir: &Path) {
+    if remove_dir(run_dir).is_ok()
+        && let Some(staging) = run_dir.parent()
+    {
+        let _ = remove_dir(staging);
+    }
+}
+
+/// Syncs every file under `dir`, then the directories holding them.
+fn sync_tree(dir: &Path) -> Result<()> {
+    for entry in read_dir(dir).with_context(|| format!("read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            sync_tree(&path)?;
+        } else {
+            OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .and_then(|f| f.sync_all())
+                .with_context(|| format!("sync {}", path.display()))?;
+        }
+    }
+    sync_dir(dir)
+}
+
+/// Persists the renames done in `dir` (a no-op where directories cannot be opened).
+fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    File::open(dir)
+        .and_then(|d| d.sync_all())
+        .with_context(|| format!("sync {}", dir.display()))?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{read_to_string, write};
+
+    const PARTITION: &str = "ingest_date=2024-01-01";
+
+    /// Stages `PARTITION` with one `part.arrow` holding `contents`.
+    fn stage(dataset_dir: &Path, run_id: &str, contents: &str) -> StagedPartition {
+        let staged = StagedPartition::create(dataset_dir, PARTITION, run_id).unwrap();
+        write(staged.dir().join("part.arrow"), contents).unwrap();
+        staged
+    }
+
+    fn part(dataset_dir: &Path) -> String {
+        read_to_string(dataset_dir.join(PARTITION).join("part.arrow")).unwrap()
+    }
+
+    #[test]
+    fn commit_publishes_and_replaces_the_partition() {
+        let root = tempfile::tempdir().unwrap();
+        let dataset = root.path();
+        let target = stage(dataset, "run-1", "v1").commit().unwrap();
+        assert_eq!(target, dataset.join(PARTITION));
+        assert_eq!(read_to_string(target.join(SUCCESS_FILE)).unwrap().trim(), "run-1");
+        assert_eq!(part(dataset), "v1");
+
+        stage(dataset, "run-2", "v2").commit().unwrap();
+        assert_eq!(part(dataset), "v2");
+        assert_eq!(read_to_string(target.join(SUCCESS_FILE)).unwrap().trim(), "run-2");
+        assert!(!dataset.join(STAGING_DIR).exists());
+    }
+
+    #[test]
+    fn discard_keeps_the_committed_partition() {
+        let root = tempfile::tempdir().unwrap();
+        let dataset = root.path();
+        stage(dataset, "run-1", "v1").commit().unwrap();
+        stage(dataset, "run-2", "v2").discard().unwrap();
+        assert_eq!(part(dataset), "v1");
+        require_committed(&dataset.join(PARTITION)).unwrap();
+        assert!(!dataset.join(STAGING_DIR).exists());
+    }
+
+    #[test]
+    fn create_clears_leftovers_of_interrupted_runs() {
+        let root = tempfile::tempdir().unwrap();
+        let dataset = root.path();
+        drop(stage(dataset, "run-1", "partial"));
+        let staged = stage(dataset, "run-2", "v2");
+        assert_eq!(staged_runs(dataset, PARTITION).unwrap(), vec![("run-2".to_string(), staged.dir().to_path_buf())]);
+        assert!(!lock_path(&dataset.join(STAGING_DIR).join("run-1").join(PARTITION)).exists());
+    }
+
+    #[test]
+    fn create_keeps_the_partitions_of_live_runs() {
+        let root = tempfile::tempdir().unwrap();
+        let dataset = root.path();
+        let live = stage(dataset, "run-1", "v1");
+        let staged = stage(dataset, "run-2", "v2");
+        assert_eq!(staged_runs(dataset, PARTITION).unwrap().len(), 2);
+        assert!(StagedPartition::reopen(dataset, PARTITION, "run-1").is_err());
+        live.commit().unwrap();
+        staged.commit().unwrap();
+        assert_eq!(part(dataset), "v2");
+        assert!(!dataset.join(STAGING_DIR).exists());
+    }
+
+    /// The state a crash leaves after `commit` moved the old partition aside, with the
+    /// new one marked (`marked`) or not yet.
+    fn interrupted_replace(dataset: &Path, marked: bool) {
+        stage(dataset, "run-1", "v1").commit().unwrap();
+        let staged = stage(dataset, "run-2", "v2");
+        if marked {
+            write(staged.dir().join(SUCCESS_FILE), "run-2\n").unwrap();
+        }
+        let replaced = dataset.join(STAGING_DIR).join("run-2").join(format!("{REPLACED_PREFIX}{PARTITION}"));
+        rename(dataset.join(PARTITION), replaced).unwrap();
+        drop(staged); // the crash releases the lock
+    }
+
+    #[test]
+    fn an_interrupted_replace_is_completed() {
+        let root = tempfile::tempdir().unwrap();
+        let dataset = root.path();
+        interrupted_replace(dataset, true);
+        require_committed(&dataset.join(PARTITION)).unwrap();
+        assert_eq!(part(dataset), "v2");
+        assert!(!dataset.join(STAGING_DIR).exists());
+    }
+
+    #[test]
+    fn an_unmarked_replace_restores_the_old_partition() {
+        let root = tempfile::tempdir().unwrap();
+        let dataset = root.path();
+        interrupted_replace(dataset, false);
+        // the next run of the partition starts from the old one
+        let staged = StagedPartition::create(dataset, PARTITION, "run-3").unwrap();
+        assert_eq!(part(dataset), "v1");
+        staged.discard().unwrap();
+        assert!(!dataset.join(STAGING_DIR).exists());
+    }
+
+    #[test]
+    fn the_old_partition_left_after_a_commit_is_removed() {
+        let root = tempfile::tempdir().unwrap();
+        let dataset = root.path();
+        stage(dataset, "run-1", "v1").commit().unwrap();
+        let leftover = dataset.join(STAGING_DIR).join("run-1").join(format!("{REPLACED_PREFIX}{PARTITION}"));
+        create_dir_all(&leftover).unwrap();
+        let committed = committed_before(dataset, "2025-01-01").unwrap();
+        assert_eq!(committed, vec![("2024-01-01".to_string(), dataset.join(PARTITION))]);
+        assert!(!dataset.join(STAGING_DIR).exists());
+    }
+}
@@ -0,0 +1,557 @@
+use anyhow::{anyhow, Context, Result};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use datafusion::arrow::datatypes::DataType;
+use datafusion::arrow::json::ArrayWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::functions_aggregate::expr_fn::{avg, count, median};
+use datafusion::prelude::*;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs::read_to_string;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Instant;
+use time::macros::format_description;
+use time::Date;
+use tower_http::cors::CorsLayer;
+use tower_http::trace::TraceLayer;
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+/// Name of the DataFusion table registered over the Gold snapshot.
+const TABLE: &str = "gold";
+const DEFAULT_PER_PAGE: usize = 50;
+const MAX_PER_PAGE: usize = 1_000;
+const DEFAULT_TOP_N: usize = 20;
+const MAX_TOP_N: usize = 500;
+
+#[derive(Debug, Clone)]
+struct ServeCfg {
+    slug: String,
+    storage_root: PathBuf,
+    manifests_dir: String,
+    gold_dir: String,
+    bind: SocketAddr,
+}
+
+impl ServeCfg {
+    /// Reads `SERVE_DATASET`, `SERVE_ROOT` and `SERVE_ADDR` (defaults: dvf, ./data, 0.0.0.0:8080).
+    fn from_env() -> Result<Self> {
+        let var = |k: &str, default: &str| std::env::var(k).unwrap_or_else(|_| default.to_string());
+        let bind = var("SERVE_ADDR", "0.0.0.0:8080");
+        Ok(Self {
+            slug: var("SERVE_DATASET", "dvf"),
+            storage_root: PathBuf::from(var("SERVE_ROOT", "./data")),
+            manifests_dir: "manifests".to_string(),
+            gold_dir: "gold".to_string(),
+            bind: bind
+                .parse()
+                .with_context(|| format!("invalid SERVE_ADDR: {bind}"))?,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Latest {
+    snapshot_date: String,
+}
+
+#[derive(Clone)]
+struct AppState {
+    ctx: SessionContext,
+    slug: String,
+    snapshot_date: String,
+    metrics: PrometheusHandle,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_target(false)
+        .init();
+
+    let cfg = ServeCfg::from_env()?;
+    let snapshot_date = read_latest(&cfg)?;
+
+    let ctx = SessionContext::new();
+    register_gold(&ctx, &cfg, &snapshot_date).await?;
+
+    let metrics = PrometheusBuilder::new()
+        .install_recorder()
+        .map_err(|e| anyhow!("failed to install metrics recorder: {e}"))?;
+
+    let state = AppState {
+        ctx,
+        slug: cfg.slug.clone(),
+        snapshot_date,
+        metrics,
+    };
+
+    let app = Router::new()
+        .route("/health", get(health))
+        .route("/metrics", get(render_metrics))
+        .route(&format!("/{}/filter", cfg.slug), get(filter))
+        .route(&format!("/{}/agg-commune", cfg.slug), get(agg_commune))
+        .route(&format!("/{}/topn", cfg.slug), get(topn))
+        .route(&format!("/{}/geo-bbox", cfg.slug), get(geo_bbox))
+        .layer(TraceLayer::new_for_http())
+        .layer(CorsLayer::permissive())
+        .with_state(state.clone());
+
+    let listener = tokio::net::TcpListener::bind(cfg.bind)
+        .await
+        .with_context(|| format!("bind {}", cfg.bind))?;
+    info!(
+        "serving {} snapshot_date={} on http://{}",
+        state.slug, state.snapshot_date, cfg.bind
+    );
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+fn read_latest(cfg: &ServeCfg) -> Result<String> {
+    let latest_path = cfg
+        .storage_root
+        .join(&cfg.manifests_dir)
+        .join(&cfg.slug)
+        .join("latest.json");
+    let txt =
+        read_to_string(&latest_path).with_context(|| format!("read {}", latest_path.display()))?;
+    let latest: Latest = serde_json::from_str(&txt)?;
+    Ok(latest.snapshot_date)
+}
+
+/// Registers every Parquet file of the snapshot (all `year=`/`dept=` partitions) as one table.
+async fn register_gold(ctx: &SessionContext, cfg: &ServeCfg, snapshot_date: &str) -> Result<()> {
+    let snapshot_dir = cfg
+        .storage_root
+        .join(&cfg.gold_dir)
+        .join(&cfg.slug)
+        .join(format!("snapshot_date={snapshot_date}"));
+    let snapshot_dir = std::fs::canonicalize(&snapshot_dir)
+        .with_context(|| format!("Gold snapshot not found: {}", snapshot_dir.display()))?;
+    // Written by curate once the snapshot is complete
+    if !snapshot_dir.join("_SUCCESS").is_file() {
+        return Err(anyhow!("Gold snapshot {} is not committed (no _SUCCESS marker)", snapshot_dir.display()));
+    }
+    // Trailing slash: DataFusion treats the path as a directory prefix.
+    let table_path = format!("{}/", snapshot_dir.to_string_lossy().replace('\\', "/"));
+    ctx.register_parquet(TABLE, &table_path, ParquetReadOptions::default())
+        .await
+        .with_context(|| format!("register {table_path}"))?;
+    Ok(())
+}
+
+// -------------------- handlers --------------------
+
+#[derive(Debug, Deserialize)]
+struct FilterParams {
+    dep: String,
+    from: Option<String>,
+    to: Option<String>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AggParams {
+    dep: String,
+    year: Option<i16>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopNParams {
+    dep: String,
+    year: Option<i16>,
+    n: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BboxParams {
+    min_lon: f64,
+    min_lat: f64,
+    max_lon: f64,
+    max_lat: f64,
+    year: Option<i16>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+async fn health(State(st): State<AppState>) -> Json<Value> {
+    Json(json!({ "dataset": st.slug, "snapshot_date": st.snapshot_date }))
+}
+
+async fn render_metrics(State(st): State<AppState>) -> String {
+    st.metrics.render()
+}
+
+/// Transactions of one departement, optionally bounded by `from`/`to` (inclusive), paged.
+async fn filter(
+    State(st): State<AppState>,
+    Query(p): Query<FilterParams>,
+) -> Result<Json<Value>, ApiError> {
+    let start = Instant::now();
+    let mut pred = col("code_departement").eq(lit(p.dep.trim()));
+    if let Some(from) = p.from.as_deref() {
+        pred = pred.and(col("date_mutation").gt_eq(date_lit(from)?));
+    }
+    if let Some(to) = p.to.as_deref() {
+        pred = pred.and(col("date_mutation").lt_eq(date_lit(to)?));
+    }
+
+    let df = st.ctx.table(TABLE).await?.filter(pred)?;
+    let body = paged(df, p.page, p.per_page).await?;
+    observe("filter", start);
+    Ok(Json(body))
+}
+
+/// Per-commune count, average and median `prix_m2` for one departement.
+async fn agg_commune(
+    State(st): State<AppState>,
+    Query(p): Query<AggParams>,
+) -> Result<Json<Value>, ApiError> {
+    let start = Instant::now();
+    let df = commune_prices(&st, &p.dep, p.year)
+        .await?
+        .sort(vec![col("code_commune").sort(true, false)])?;
+    let rows = batches_to_json(&df.collect().await?)?;
+    observe("agg_commune", start);
+    Ok(Json(json!({ "rows": rows })))
+}
+
+/// Top-N communes of one departement by average `prix_m2`.
+async fn topn(
+    State(st): State<AppState>,
+    Query(p): Query<TopNParams>,
+) -> Result<Json<Value>, ApiError> {
+    let start = Instant::now();
+    let n = p.n.unwrap_or(DEFAULT_TOP_N).clamp(1, MAX_TOP_N);
+    let df = commune_prices(&st, &p.dep, p.year)
+        .await?
+        .sort(vec![
+            col("avg_prix_m2").sort(false, false),
+            col("code_commune").sort(true, false),
+        ])?
+        .limit(0, Some(n))?;
+    let rows = batches_to_json(&df.collect().await?)?;
+    observe("topn", start);
+    Ok(Json(json!({ "n": n, "rows": rows })))
+}
+
+/// Geolocated transactions inside a lon/lat bounding box, optionally for one year, paged.
+async fn geo_bbox(
+    State(st): State<AppState>,
+    Query(p): Query<BboxParams>,
+) -> Result<Json<Value>, ApiError> {
+    let start = Instant::now();
+    if p.min_lon > p.max_lon || p.min_lat > p.max_lat {
+        return Err(ApiError::bad_request("min_lon/min_lat must not exceed max_lon/max_lat"));
+    }
+    let mut pred = col("longitude")
+        .between(lit(p.min_lon), lit(p.max_lon))
+        .and(col("latitude").between(lit(p.min_lat), lit(p.max_lat)));
+    if let Some(y) = p.year {
+        pred = pred.and(col("year_mutation").eq(lit(y)));
+    }
+
+    let df = st.ctx.table(TABLE).await?.filter(pred)?;
+    let body = paged(df, p.page, p.per_page).await?;
+    observe("geo_bbox", start);
+    Ok(Json(body))
+}
+
+// -------------------- query helpers --------------------
+
+/// Columns returned by row-level endpoints (same shape as `gold.<slug>_transaction_latest`).
+fn presentation_cols() -> Vec<Expr> {
+    [
+        "id_mutation",
+        "numero_disposition",
+        "nature_mutation",
+        "date_mutation",
+        "valeur_fonciere",
+        "surface_reelle_bati",
+        "nombre_pieces_principales",
+        "type_local",
+        "code_postal",
+        "code_commune",
+        "nom_commune",
+        "code_departement",
+        "longitude",
+        "latitude",
+        "geohash6",
+        "prix_m2",
+        "year_mutation",
+    ]
+    .into_iter()
+    .map(col)
+    .collect()
+}
+
+async fn paged(
+    df: DataFrame,
+    page: Option<usize>,
+    per_page: Option<usize>,
+) -> Result<Value, ApiError> {
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    let total = df.clone().count().await?;
+    let df = df
+        .select(presentation_cols())?
+        .sort(vec![
+            col("date_mutation").sort(true, false),
+            col("id_mutation").sort(true, false),
+            col("numero_disposition").sort(true, false),
+        ])?
+        .limit((page - 1) * per_page, Some(per_page))?;
+    let rows = batches_to_json(&df.collect().await?)?;
+
+    Ok(json!({
+        "page": page,
+        "per_page": per_page,
+        "total": total,
+        "rows": rows,
+    }))
+}
+
+async fn commune_prices(st: &AppState, dep: &str, year: Option<i16>) -> Result<DataFrame, ApiError> {
+    let mut pred = col("code_departement")
+        .eq(lit(dep.trim()))
+        .and(col("prix_m2").is_not_null());
+    if let Some(y) = year {
+        pred = pred.and(col("year_mutation").eq(lit(y)));
+    }
+    let df = st.ctx.table(TABLE).await?.filter(pred)?.aggregate(
+        vec![col("code_commune"), col("nom_commune")],
+        vec![
+            count(col("id_mutation")).alias("n"),
+            avg(col("prix_m2")).alias("avg_prix_m2"),
+            median(col("prix_m2")).alias("median_prix_m2"),
+        ],
+    )?;
+    Ok(df)
+}
+
+/// `YYYY-MM-DD` literal cast to Date32 (the Gold `date_mutation` type). Impossible
+/// dates (`2024-02-30`) are rejected here rather than failing the query.
+fn date_lit(s: &str) -> Result<Expr, ApiError> {
+    let s = s.trim();
+    let date = Date::parse(s, format_description!("[year]-[month]-[day]")).map_err(|e| {
+        ApiError::bad_request(format!("invalid date (expected YYYY-MM-DD): {s}: {e}"))
+    })?;
+    Ok(cast(lit(date.to_string()), DataType::Date32))
+}
+
+fn batches_to_json(batches: &[RecordBatch]) -> Result<Value> {
+    let mut writer = ArrayWriter::new(Vec::new());
+    let refs: Vec<&RecordBatch> = batches.iter().collect();
+    writer.write_batches(&refs)?;
+    writer.finish()?;
+    let buf = writer.into_inner();
+    if buf.is_empty() {
+        return Ok(Value::Array(Vec::new()));
+    }
+    Ok(serde_json::from_slice(&buf)?)
+}
+
+fn observe(endpoint: &'static str, start: Instant) {
+    metrics::counter!("serve_requests_total", "endpoint" => endpoint).increment(1);
+    metrics::histogram!("serve_request_seconds", "endpoint" => endpoint)
+        .record(start.elapsed().as_secs_f64());
+}
+
+// -------------------- errors --------------------
+
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for ApiError {
+    fn from(err: E) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("{:#}", err.into()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Uri;
+    use datafusion::arrow::array::{ArrayRef, Date32Array, Float64Array, Int16Array, StringArray};
+    use std::sync::Arc;
+
+    /// A Gold table of `n` Paris transactions, one a day from 2023-01-01 (M0, M1, ...).
+    fn state(n: usize) -> AppState {
+        let days = 19_358; // 2023-01-01
+        let text = |f: &dyn Fn(usize) -> String| {
+            Arc::new(StringArray::from_iter_values((0..n).map(f))) as ArrayRef
+        };
+        let float = |v: f64| Arc::new(Float64Array::from(vec![v; n])) as ArrayRef;
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("id_mutation", text(&|i| format!("M{i}"))),
+            ("numero_disposition", text(&|_| "1".into())),
+            ("nature_mutation", text(&|_| "Vente".into())),
+            (
+                "date_mutation",
+                Arc::new(Date32Array::from_iter_values(
+                    (0..n as i32).map(|i| days + i),
+                )),
+            ),
+            ("valeur_fonciere", float(300_000.0)),
+            ("surface_reelle_bati", float(50.0)),
+            ("nombre_pieces_principales", float(2.0)),
+            ("type_local", text(&|_| "Appartement".into())),
+            ("code_postal", text(&|_| "75001".into())),
+            ("code_commune", text(&|i| format!("7510{}", i % 2 + 1))),
+            ("nom_commune", text(&|i| format!("Paris {}", i % 2 + 1))),
+            ("code_departement", text(&|_| "75".into())),
+            ("longitude", float(2.35)),
+            ("latitude", float(48.85)),
+            ("geohash6", text(&|_| "u09tvw".into())),
+            (
+                "prix_m2",
+                Arc::new(Float64Array::from_iter_values(
+                    (0..n).map(|i| 6_000.0 + i as f64),
+                )),
+            ),
+            ("year_mutation", Arc::new(Int16Array::from(vec![2023; n]))),
+        ];
+        let batch = RecordBatch::try_from_iter(columns).unwrap();
+        let ctx = SessionContext::new();
+        ctx.register_batch(TABLE, batch).unwrap();
+        AppState {
+            ctx,
+            slug: "dvf".to_string(),
+            snapshot_date: "2024-01-01".to_string(),
+            metrics: PrometheusBuilder::new().build_recorder().handle(),
+        }
+    }
+
+    fn query<T: serde::de::DeserializeOwned>(q: &str) -> Result<T, String> {
+        let uri: Uri = format!("/dvf/filter?{q}").parse().unwrap();
+        Query::<T>::try_from_uri(&uri)
+            .map(|Query(p)| p)
+            .map_err(|e| e.body_text())
+    }
+
+    async fn get_filter(st: &AppState, q: &str) -> Result<Value, ApiError> {
+        filter(State(st.clone()), Query(query(q).unwrap()))
+            .await
+            .map(|Json(v)| v)
+    }
+
+    fn ids(body: &Value) -> Vec<&str> {
+        body["rows"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["id_mutation"].as_str().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn pages_are_clamped_to_their_bounds() {
+        let st = state(5);
+        let body = get_filter(&st, "dep=75&page=2&per_page=2").await.unwrap();
+        assert_eq!(
+            (
+                body["page"].as_u64(),
+                body["per_page"].as_u64(),
+                body["total"].as_u64()
+            ),
+            (Some(2), Some(2), Some(5))
+        );
+        assert_eq!(ids(&body), ["M2", "M3"]);
+
+        // page and per_page start at 1
+        let body = get_filter(&st, "dep=75&page=0&per_page=0").await.unwrap();
+        assert_eq!(
+            (body["page"].as_u64(), body["per_page"].as_u64()),
+            (Some(1), Some(1))
+        );
+        assert_eq!(ids(&body), ["M0"]);
+
+        let body = get_filter(&st, "dep=75&per_page=100000").await.unwrap();
+        assert_eq!(body["per_page"].as_u64(), Some(MAX_PER_PAGE as u64));
+        assert_eq!(ids(&body).len(), 5);
+
+        // past the last page: no rows, the total still counts them
+        let body = get_filter(&st, "dep=75&page=4&per_page=2").await.unwrap();
+        assert_eq!(body["total"].as_u64(), Some(5));
+        assert!(ids(&body).is_empty());
+
+        let Json(body) = topn(State(st.clone()), Query(query("dep=75&n=0").unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(body["n"].as_u64(), Some(1));
+        let Json(body) = topn(State(st), Query(query("dep=75&n=100000").unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(body["n"].as_u64(), Some(MAX_TOP_N as u64));
+        assert_eq!(body["rows"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn date_bounds_are_inclusive() {
+        let st = state(5);
+        let body = get_filter(&st, "dep=75&from=2023-01-02&to=2023-01-04")
+            .await
+            .unwrap();
+        assert_eq!(ids(&body), ["M1", "M2", "M3"]);
+    }
+
+    #[tokio::test]
+    async fn bad_params_are_client_errors() {
+        let st = state(5);
+        for q in [
+            "dep=75&from=2024-02-30",
+            "dep=75&to=2023-1-02",
+            "dep=75&from=yesterday",
+            "dep=75&to=2023-01-0١",
+        ] {
+            let err = get_filter(&st, q).await.unwrap_err();
+            assert_eq!(err.status, StatusCode::BAD_REQUEST, "{q}: {}", err.message);
+            assert!(
+                err.message.starts_with("invalid date"),
+                "{q}: {}",
+                err.message
+            );
+        }
+
+        let p = query("min_lon=3&min_lat=48&max_lon=2&max_lat=49").unwrap();
+        let err = geo_bbox(State(st), Query(p)).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        // rejected by the Query extractor (400 before the handler runs)
+        assert!(query::<FilterParams>("page=1").is_err());
+        assert!(query::<FilterParams>("dep=75&page=-1").is_err());
+        assert!(query::<AggParams>("dep=75&year=twenty").is_err());
+        assert!(query::<BboxParams>("min_lon=x&min_lat=48&max_lon=2&max_lat=49").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! DVF fixtures shared by the integration tests of ingest, validate and curate: the
+//! descriptor, source lines, the ingest and validate steps, and readers for the Arrow IPC
+//! outputs.
+use arrow::array::{AsArray, RecordBatch};
+use arrow::compute::concat_batches;
+use arrow::ipc::reader::FileReader;
+use async_zip::base::write::ZipFileWriter;
+use async_zip::{Compression, ZipEntryBuilder};
+use ingest::{IngestConfig, IngestStats};
+use metadata::DatasetDescriptor;
+use std::fmt;
+use std::fs::{read_dir, write, File};
+use std::path::Path;
+use time::macros::date;
+use tokio::io::AsyncWriteExt;
+use validate::{ValidateConfig, ValidationStats};
+
+/// Columns of a DVF source file, in order.
+pub const HEADER: &str = "id_mutation,date_mutation,numero_disposition,nature_mutation,valeur_fonciere,adresse_numero,adresse_suffixe,adresse_nom_voie,adresse_code_voie,code_postal,code_commune,nom_commune,code_departement,ancien_code_commune,ancien_nom_commune,id_parcelle,ancien_id_parcelle,numero_volume,lot1_numero,lot1_surface_carrez,lot2_numero,lot2_surface_carrez,lot3_numero,lot3_surface_carrez,lot4_numero,lot4_surface_carrez,lot5_numero,lot5_surface_carrez,nombre_lots,code_type_local,type_local,surface_reelle_bati,nombre_pieces_principales,code_nature_culture,nature_culture,code_nature_culture_speciale,nature_culture_speciale,surface_terrain,longitude,latitude";
+
+pub fn descriptor() -> DatasetDescriptor {
+    let config_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../config/datasets");
+    metadata::load_descriptor_from(&config_dir, "dvf").unwrap()
+}
+
+/// One DVF source line, edited by column name.
+#[derive(Debug, Clone)]
+pub struct Line(Vec<String>);
+
+impl Line {
+    /// A valid line: a 50 m² apartment in Paris sold 300000 on 2023-03-01, on parcel
+    /// `<mutation>-P`.
+    pub fn sale(mutation: &str) -> Self {
+        Self::id(mutation)
+            .set("date_mutation", "2023-03-01")
+            .set("numero_disposition", "1")
+            .set("nature_mutation", "Vente")
+            .set("valeur_fonciere", "300000")
+            .set("adresse_numero", "1")
+            .set("adresse_nom_voie", "RUE X")
+            .set("code_postal", "75001")
+            .set("code_commune", "75056")
+            .set("nom_commune", "Paris")
+            .set("code_departement", "75")
+            .set("id_parcelle", &format!("{mutation}-P"))
+            .set("lot1_numero", "1")
+            .set("nombre_lots", "1")
+            .set("code_type_local", "2")
+            .set("type_local", "Appartement")
+            .set("surface_reelle_bati", "50")
+            .set("longitude", "2.35")
+            .set("latitude", "48.85")
+    }
+
+    /// A line carrying only an `id_mutation`.
+    pub fn id(mutation: &str) -> Self {
+        Self(vec![String::new(); HEADER.split(',').count()]).set("id_mutation", mutation)
+    }
+
+    pub fn set(mut self, column: &str, value: &str) -> Self {
+        let i = HEADER.split(',').position(|c| c == column).unwrap_or_else(|| panic!("no DVF column {column}"));
+        self.0[i] = value.to_string();
+        self
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.join(","))
+    }
+}
+
+/// A DVF source file holding `lines`.
+pub fn csv(lines: &[Line]) -> String {
+    let lines: Vec<String> = lines.iter().map(Line::to_string).collect();
+    format!("{HEADER}\n{}\n", lines.join("\n"))
+}
+
+pub async fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut enc = async_compression::tokio::write::GzipEncoder::new(Vec::new());
+    enc.write_all(data).await.unwrap();
+    enc.shutdown().await.unwrap();
+    enc.into_inner()
+}
+
+/// A zip archive holding `members` (name, contents), deflated, in order.
+pub async fn zip(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut w = ZipFileWriter::new(Vec::new());
+    for (name, data) in members {
+        w.write_entry_whole(ZipEntryBuilder::new((*name).into(), Compression::Deflate), data).await.unwrap();
+    }
+    w.close().await.unwrap()
+}
+
+/// Ingests `source` into `root` as the drop of `ingest_date`, once `tweak` has adjusted
+/// the config.
+pub async fn ingest_with(
+    root: &Path,
+    source: &Path,
+    ingest_date: &str,
+    tweak: impl FnOnce(&mut IngestConfig),
+) -> anyhow::Result<IngestStats> {
+    let mut cfg = IngestConfig::from_descriptor(&descriptor(), ingest_date.to_string(), root.to_path_buf())?;
+    tweak(&mut cfg);
+    ingest::ingest_dataset(cfg, source).await
+}
+
+/// Writes `lines` to `root/dvf_<ingest_date>.csv`, then ingests and validates them as the
+/// drop of `ingest_date`, with `today` pinned to 2024-10-17.
+pub async fn validate(desc: &DatasetDescriptor, root: &Path, ingest_date: &str, lines: &[Line]) -> ValidationStats {
+    let source = root.join(format!("dvf_{ingest_date}.csv"));
+    write(&source, csv(lines)).unwrap();
+    let cfg = IngestConfig::from_descriptor(desc, ingest_date.to_string(), root.to_path_buf()).unwrap();
+    ingest::ingest_dataset(cfg, &source).await.unwrap();
+
+    let mut cfg = ValidateConfig::from_descriptor(desc, ingest_date.to_string(), root.to_path_buf());
+    cfg.today = date!(2024 - 10 - 17);
+    validate::validate_dataset(cfg).await.unwrap()
+}
+
+/// Every `.arrow` file of `dir` read back as one batch, by file name.
+pub fn read_parts(dir: &Path) -> Vec<(String, RecordBatch)> {
+    let mut files: Vec<_> = read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "arrow"))
+        .collect();
+    files.sort();
+    files
+        .into_iter()
+        .map(|f| {
+            let reader = FileReader::try_new(File::open(&f).unwrap(), None).unwrap();
+            let schema = reader.schema();
+            let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+            let name = f.file_name().unwrap().to_string_lossy().to_string();
+            (name, concat_batches(&schema, &batches).unwrap())
+        })
+        .collect()
+}
+
+/// The values of two text columns over every row of the Arrow IPC `files`, in order.
+pub fn column_pairs<P: AsRef<Path>>(files: &[P], a: &str, b: &str) -> Vec<(String, String)> {
+    let mut rows = Vec::new();
+    for file in files {
+        for batch in FileReader::try_new(File::open(file).unwrap(), None).unwrap() {
+            let batch = batch.unwrap();
+            let a = batch.column_by_name(a).unwrap().as_string::<i32>();
+            let b = batch.column_by_name(b).unwrap().as_string::<i32>();
+            for (a, b) in a.iter().zip(b.iter()) {
+                rows.push((a.unwrap().to_string(), b.unwrap().to_string()));
+            }
+        }
+    }
+    rows
+}
+
+pub fn pairs(rows: &[(&str, &str)]) -> Vec<(String, String)> {
+    rows.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect()
+}
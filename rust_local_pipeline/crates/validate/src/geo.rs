@@ -0,0 +1,384 @@
+//! Coordinate checks by department: the bounding box of the territory a department
+//! belongs to (metropole, Guadeloupe, Martinique, ...), and its outline from the
+//! departement reference.
+//!
+//! Layout: `gold/ref/departements.parquet`, one row per department with
+//! `code_departement` and its geometry as WKB (`geom`, or `geometry`), as DuckDB
+//! spatial writes it.
+use anyhow::{anyhow, Context, Result};
+use arrow::array::{Array, AsArray};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use metadata::TerritorySpec;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BBox {
+    lon: [f64; 2],
+    lat: [f64; 2],
+}
+
+impl BBox {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        self.lon[0] <= x && x <= self.lon[1] && self.lat[0] <= y && y <= self.lat[1]
+    }
+
+    fn grow(&self, d: f64) -> Self {
+        Self {
+            lon: [self.lon[0] - d, self.lon[1] + d],
+            lat: [self.lat[0] - d, self.lat[1] + d],
+        }
+    }
+}
+
+/// Territory bounding boxes, by department.
+pub(crate) struct Territories {
+    by_dept: HashMap<String, BBox>,
+    fallback: Option<BBox>, // the territory listing no departments
+}
+
+impl Territories {
+    pub(crate) fn new(specs: &[TerritorySpec]) -> Self {
+        let mut by_dept = HashMap::new();
+        let mut fallback = None;
+        for t in specs {
+            let bbox = BBox { lon: t.lon, lat: t.lat };
+            if t.departments.is_empty() {
+                fallback = Some(bbox);
+            }
+            for d in &t.departments {
+                by_dept.insert(d.clone(), bbox);
+            }
+        }
+        Self { by_dept, fallback }
+    }
+
+    /// Whether the point lies in the territory of `dept`; false when no territory covers it.
+    pub(crate) fn contains(&self, dept: Option<&str>, x: f64, y: f64) -> bool {
+        dept.and_then(|d| self.by_dept.get(d))
+            .or(self.fallback.as_ref())
+            .is_some_and(|b| b.contains(x, y))
+    }
+}
+
+/// Department outlines: every ring of every polygon, tested with the even-odd rule
+/// (holes and multi-polygons need no special casing).
+struct Shape {
+    bbox: BBox,
+    rings: Vec<Vec<[f64; 2]>>,
+}
+
+pub(crate) struct Departments {
+    shapes: HashMap<String, Shape>,
+}
+
+impl Departments {
+    /// Reads the departement reference (GeoParquet or plain Parquet with WKB geometries).
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let f = File::open(path).with_context(|| format!("open {}", path.display()))?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(f)
+            .with_context(|| format!("read {}", path.display()))?;
+        let schema = builder.schema().clone();
+        let code_idx = schema
+            .index_of("code_departement")
+            .with_context(|| format!("{}: missing column code_departement", path.display()))?;
+        let geom_idx = ["geom", "geometry"]
+            .iter()
+            .find_map(|n| schema.index_of(n).ok())
+            .ok_or_else(|| anyhow!("{}: missing geometry column (geom)", path.display()))?;
+
+        let mut shapes = HashMap::new();
+        for batch in builder.build()? {
+            let batch = batch?;
+            let codes = cast(batch.column(code_idx), &DataType::Utf8)?;
+            let codes = codes.as_string::<i32>();
+            let geoms = cast(batch.column(geom_idx), &DataType::LargeBinary)
+                .with_context(|| format!("{}: geometry must be WKB", path.display()))?;
+            let geoms = geoms.as_binary::<i64>();
+            for row in 0..batch.num_rows() {
+                if codes.is_null(row) || geoms.is_null(row) {
+                    continue;
+                }
+                let code = codes.value(row);
+                let mut rings = Vec::new();
+                Wkb::new(geoms.value(row))
+                    .read(&mut rings)
+                    .with_context(|| format!("{}: geometry of department {code}", path.display()))?;
+                if let Some(bbox) = bounds(&rings) {
+                    shapes.insert(code.to_string(), Shape { bbox, rings });
+                }
+            }
+        }
+        Ok(Self { shapes })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    /// Whether the point lies in (or within `tolerance` degrees of) `dept`'s outline;
+    /// `None` when the reference has no outline for `dept`.
+    pub(crate) fn contains(&self, dept: &str, x: f64, y: f64, tolerance: f64) -> Option<bool> {
+        let shape = self.shapes.get(dept)?;
+        if !shape.bbox.grow(tolerance).contains(x, y) {
+            return Some(false);
+        }
+        let mut inside = false;
+        for ring in &shape.rings {
+            for (a, b) in ring.iter().zip(ring.iter().skip(1)) {
+                if (a[1] > y) != (b[1] > y) && x < a[0] + (y - a[1]) * (b[0] - a[0]) / (b[1] - a[1]) {
+                    inside = !inside;
+                }
+            }
+        }
+        if inside || tolerance <= 0.0 {
+            return Some(inside);
+        }
+        let near = shape
+            .rings
+            .iter()
+            .flat_map(|r| r.iter().zip(r.iter().skip(1)))
+            .any(|(a, b)| segment_distance(*a, *b, x, y) <= tolerance);
+        Some(near)
+    }
+}
+
+fn bounds(rings: &[Vec<[f64; 2]>]) -> Option<BBox> {
+    let mut pts = rings.iter().flatten();
+    let first = pts.next()?;
+    let mut b = BBox { lon: [first[0]; 2], lat: [first[1]; 2] };
+    for p in pts {
+        b.lon = [b.lon[0].min(p[0]), b.lon[1].max(p[0])];
+        b.lat = [b.lat[0].min(p[1]), b.lat[1].max(p[1])];
+    }
+    Some(b)
+}
+
+fn segment_distance(a: [f64; 2], b: [f64; 2], x: f64, y: f64) -> f64 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 == 0.0 {
+        0.0
+    } else {
+        (((x - a[0]) * dx + (y - a[1]) * dy) / len2).clamp(0.0, 1.0)
+    };
+    (x - (a[0] + t * dx)).hypot(y - (a[1] + t * dy))
+}
+
+// ---------- WKB
+
+/// Reader for (multi)polygons in WKB: ISO and EWKB flavours, with or without Z/M.
+struct Wkb<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Wkb<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let b = self
+            .buf
+            .get(self.pos..self.pos + N)
+            .ok_or_else(|| anyhow!("truncated WKB at byte {}", self.pos))?;
+        self.pos += N;
+        Ok(b.try_into()?)
+    }
+
+    fn u32(&mut self, le: bool) -> Result<u32> {
+        let b = self.bytes::<4>()?;
+        Ok(if le { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    }
+
+    fn f64(&mut self, le: bool) -> Result<f64> {
+        let b = self.bytes::<8>()?;
+        Ok(if le { f64::from_le_bytes(b) } else { f64::from_be_bytes(b) })
+    }
+
+    /// Appends the rings of the geometry at the current position to `rings`.
+    fn read(&mut self, rings: &mut Vec<Vec<[f64; 2]>>) -> Result<()> {
+        let le = match self.bytes::<1>()?[0] {
+            0 => false,
+            1 => true,
+            b => return Err(anyhow!("invalid WKB byte order {b}")),
+        };
+        let raw = self.u32(le)?;
+        // EWKB: Z/M/SRID flags in the high bits; ISO: +1000 (Z), +2000 (M), +3000 (ZM)
+        let mut dims = 2 + usize::from(raw & 0x8000_0000 != 0) + usize::from(raw & 0x4000_0000 != 0);
+        if raw & 0x2000_0000 != 0 {
+            self.u32(le)?; // SRID
+        }
+        let ty = raw & 0x0fff_ffff;
+        dims += match ty / 1000 {
+            1 | 2 => 1,
+            3 => 2,
+            _ => 0,
+        };
+        match ty % 1000 {
+            3 => {
+                for _ in 0..self.u32(le)? {
+                    let n = self.u32(le)? as usize;
+                    let mut ring = Vec::with_capacity(n.min(self.buf.len() / 16));
+                    for _ in 0..n {
+                        let x = self.f64(le)?;
+                        let y = self.f64(le)?;
+                        for _ in 2..dims {
+                            self.f64(le)?;
+                        }
+                        ring.push([x, y]);
+                    }
+                    rings.push(ring);
+                }
+            }
+            6 | 7 => {
+                // MultiPolygon, or GeometryCollection of polygons
+                for _ in 0..self.u32(le)? {
+                    self.read(rings)?;
+                }
+            }
+            other => return Err(anyhow!("unsupported WKB geometry type {other} (expected polygons)")),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: [[f64; 2]; 5] = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0], [0.0, 0.0]];
+    const HOLE: [[f64; 2]; 5] = [[4.0, 4.0], [6.0, 4.0], [6.0, 6.0], [4.0, 6.0], [4.0, 4.0]];
+
+    /// A WKB polygon, in the given byte order, as EWKB with an SRID when there is one.
+    fn polygon(le: bool, srid: Option<u32>, rings: &[&[[f64; 2]]]) -> Vec<u8> {
+        let u32 = |v: u32| if le { v.to_le_bytes() } else { v.to_be_bytes() };
+        let f64 = |v: f64| if le { v.to_le_bytes() } else { v.to_be_bytes() };
+        let mut b = vec![u8::from(le)];
+        match srid {
+            Some(srid) => {
+                b.extend(u32(0x2000_0003));
+                b.extend(u32(srid));
+            }
+            None => b.extend(u32(3)),
+        }
+        b.extend(u32(rings.len() as u32));
+        for ring in rings {
+            b.extend(u32(ring.len() as u32));
+            for p in *ring {
+                b.extend(f64(p[0]));
+                b.extend(f64(p[1]));
+            }
+        }
+        b
+    }
+
+    fn read(wkb: &[u8]) -> Result<Vec<Vec<[f64; 2]>>> {
+        let mut rings = Vec::new();
+        Wkb::new(wkb).read(&mut rings)?;
+        Ok(rings)
+    }
+
+    /// Department `75` with the outline `wkb`.
+    fn departments(wkb: &[u8]) -> Departments {
+        let rings = read(wkb).unwrap();
+        let shape = Shape { bbox: bounds(&rings).unwrap(), rings };
+        Departments { shapes: HashMap::from([("75".to_string(), shape)]) }
+    }
+
+    #[test]
+    fn reads_little_and_big_endian_polygons() {
+        let expected = vec![SQUARE.to_vec()];
+        assert_eq!(read(&polygon(true, None, &[&SQUARE])).unwrap(), expected);
+        assert_eq!(read(&polygon(false, None, &[&SQUARE])).unwrap(), expected);
+    }
+
+    #[test]
+    fn reads_ewkb_with_an_srid() {
+        let rings = read(&polygon(true, Some(4326), &[&SQUARE, &HOLE])).unwrap();
+        assert_eq!(rings, vec![SQUARE.to_vec(), HOLE.to_vec()]);
+        assert_eq!(read(&polygon(false, Some(2154), &[&SQUARE])).unwrap(), vec![SQUARE.to_vec()]);
+    }
+
+    #[test]
+    fn reads_the_polygons_of_a_multipolygon() {
+        let mut wkb = vec![1];
+        wkb.extend(6u32.to_le_bytes());
+        wkb.extend(2u32.to_le_bytes());
+        wkb.extend(polygon(true, None, &[&SQUARE]));
+        wkb.extend(polygon(false, None, &[&HOLE]));
+        assert_eq!(read(&wkb).unwrap(), vec![SQUARE.to_vec(), HOLE.to_vec()]);
+    }
+
+    #[test]
+    fn a_truncated_buffer_is_an_error() {
+        let wkb = polygon(true, Some(4326), &[&SQUARE]);
+        for len in [0, 1, 4, 9, 13, wkb.len() - 1] {
+            let err = read(&wkb[..len]).unwrap_err();
+            assert!(err.to_string().contains("truncated WKB"), "{len}: {err:#}");
+        }
+        // a ring claiming more points than the buffer holds
+        let mut wkb = polygon(true, None, &[&SQUARE]);
+        wkb[9..13].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(read(&wkb).is_err());
+        assert!(read(&[2, 3, 0, 0, 0]).unwrap_err().to_string().contains("byte order"));
+    }
+
+    #[test]
+    fn points_in_a_hole_are_outside() {
+        let depts = departments(&polygon(true, None, &[&SQUARE, &HOLE]));
+        assert_eq!(depts.contains("75", 2.0, 2.0, 0.0), Some(true));
+        assert_eq!(depts.contains("75", 5.0, 5.0, 0.0), Some(false));
+        // a full degree from the hole's edge
+        assert_eq!(depts.contains("75", 5.0, 5.0, 0.1), Some(false));
+        assert_eq!(depts.contains("75", 5.0, 5.0, 1.5), Some(true));
+        assert_eq!(depts.contains("13", 2.0, 2.0, 0.0), None);
+    }
+
+    #[test]
+    fn points_outside_within_tolerance_are_kept() {
+        let depts = departments(&polygon(false, Some(4326), &[&SQUARE]));
+        assert_eq!(depts.contains("75", 10.05, 5.0, 0.0), Some(false));
+        assert_eq!(depts.contains("75", 10.05, 5.0, 0.1), Some(true));
+        assert_eq!(depts.contains("75", 10.05, 5.0, 0.01), Some(false));
+        // beyond the grown bounding box
+        assert_eq!(depts.contains("75", 20.0, 20.0, 0.1), Some(false));
+    }
+
+    #[test]
+    fn overseas_departments_use_their_own_territory() {
+        let config_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../config/datasets");
+        let desc = metadata::load_descriptor_from(&config_dir, "dvf").unwrap();
+        let territories = Territories::new(&desc.territories);
+        let towns = [
+            ("971", -61.53, 16.24), // Pointe-à-Pitre
+            ("972", -61.07, 14.60), // Fort-de-France
+            ("973", -52.33, 4.93),  // Cayenne
+            ("974", 55.45, -20.88), // Saint-Denis
+            ("975", -56.17, 46.78), // Saint-Pierre
+            ("976", 45.23, -12.78), // Mamoudzou
+        ];
+        for (dept, x, y) in towns {
+            for (other, _, _) in towns {
+                assert_eq!(territories.contains(Some(other), x, y), dept == other, "{dept} point for {other}");
+            }
+            assert!(!territories.contains(Some("75"), x, y), "{dept} point for 75");
+            assert!(!territories.contains(None, x, y), "{dept} point without department");
+        }
+        // every other department, and rows without one, fall back to the metropole
+        for dept in [Some("75"), Some("2A"), None] {
+            assert!(territories.contains(dept, 2.35, 48.85));
+            assert!(!territories.contains(dept, -61.53, 16.24));
+        }
+        assert!(!territories.contains(Some("971"), 2.35, 48.85));
+
+        // without a fallback territory, unlisted departments are never covered
+        let overseas: Vec<TerritorySpec> =
+            desc.territories.into_iter().filter(|t| !t.departments.is_empty()).collect();
+        assert!(!Territories::new(&overseas).contains(Some("75"), 2.35, 48.85));
+    }
+}
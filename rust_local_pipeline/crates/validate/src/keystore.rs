@@ -0,0 +1,172 @@
+//! On-disk index of the `mutation_key`s kept in Silver, one IPC file per ingest.
+//!
+//! Layout: `<root>/<keys_dir>/<slug>/ingest_date=YYYY-MM-DD/keys.arrow`. A run only
+//! consults partitions with an earlier `ingest_date`, so re-validating the same
+//! drop overwrites its own keys instead of flagging itself. The file is written once
+//! Silver is committed, and keys of an ingest whose Silver partition is not committed
+//! are ignored: a failed validation never hides rows from the next drop. A committed
+//! Silver partition without its key file (a crash right after the commit) fails the
+//! run rather than letting its rows through again.
+use anyhow::{anyhow, Context, Result};
+use arrow::array::{Array, FixedSizeBinaryArray, FixedSizeBinaryBuilder};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::ipc::reader::FileReader as IpcReader;
+use arrow::ipc::writer::FileWriter as IpcWriter;
+use arrow::record_batch::RecordBatch;
+use metadata::staging;
+use std::fs::{create_dir_all, rename, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const KEYS_FILE: &str = "keys.arrow";
+
+pub(crate) struct KeyStore {
+    dataset_dir: PathBuf,
+    dates: Vec<String>, // earlier ingest dates, oldest first
+    /// every key kept by an earlier ingest, sorted, with the index in `dates` of the first
+    /// ingest that kept it
+    prior: Vec<([u8; 32], u16)>,
+}
+
+impl KeyStore {
+    /// Loads the keys of every ingest strictly before `ingest_date` whose partition of
+    /// `silver_dataset_dir` is committed. Fails when one of them has no key file.
+    pub(crate) fn open(dataset_dir: PathBuf, silver_dataset_dir: &Path, ingest_date: &str) -> Result<Self> {
+        let mut dates = Vec::new();
+        let mut prior = Vec::new();
+        // oldest first: a key keeps the date of its first ingest
+        for (d, silver) in staging::committed_before(silver_dataset_dir, ingest_date)? {
+            let path = dataset_dir.join(format!("ingest_date={d}")).join(KEYS_FILE);
+            if !path.exists() {
+                return Err(anyhow!(
+                    "{} is committed but {} is missing: validate ingest_date={d} again",
+                    silver.display(),
+                    path.display()
+                ));
+            }
+            let idx = u16::try_from(dates.len())
+                .map_err(|_| anyhow!("more than {} ingests in {}", u16::MAX, dataset_dir.display()))?;
+            load_keys(&path, idx, &mut prior)?;
+            dates.push(d);
+        }
+        // by key, then oldest ingest first: that entry is the one kept
+        prior.sort_unstable();
+        prior.dedup_by(|later, first| later.0 == first.0);
+        Ok(Self { dataset_dir, dates, prior })
+    }
+
+    /// Ingest date that already kept this key, if any.
+    pub(crate) fn prior(&self, key: &[u8; 32]) -> Option<&str> {
+        let i = self.prior.binary_search_by(|(k, _)| k.cmp(key)).ok()?;
+        Some(&self.dates[self.prior[i].1 as usize])
+    }
+
+    pub(crate) fn prior_len(&self) -> usize {
+        self.prior.len()
+    }
+
+    /// Replaces the key file of `ingest_date` with `keys`.
+    pub(crate) fn write(&self, ingest_date: &str, keys: &[[u8; 32]]) -> Result<PathBuf> {
+        let dir = self.dataset_dir.join(format!("ingest_date={ingest_date}"));
+        create_dir_all(&dir)?;
+        let out = dir.join(KEYS_FILE);
+        let tmp = dir.join(format!("{KEYS_FILE}.tmp"));
+
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "mutation_key",
+            DataType::FixedSizeBinary(32),
+            false,
+        )]));
+        let file = File::create(&tmp).with_context(|| format!("create {}", tmp.display()))?;
+        let mut w = IpcWriter::try_new(file, &schema)?;
+        for chunk in keys.chunks(65_536) {
+            let mut b = FixedSizeBinaryBuilder::with_capacity(chunk.len(), 32);
+            for k in chunk {
+                b.append_value(k)?;
+            }
+            w.write(&RecordBatch::try_new(schema.clone(), vec![Arc::new(b.finish())])?)?;
+        }
+        w.finish()?;
+        w.into_inner()?.sync_all()?;
+        rename(&tmp, &out).with_context(|| format!("rename {}", tmp.display()))?;
+        Ok(out)
+    }
+}
+
+fn load_keys(path: &Path, date: u16, into: &mut Vec<([u8; 32], u16)>) -> Result<()> {
+    let f = File::open(path).with_context(|| format!("open {}", path.display()))?;
+    for batch in IpcReader::try_new(f, None)? {
+        let batch = batch?;
+        let keys = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<FixedSizeBinaryArray>()
+            .ok_or_else(|| anyhow!("expected FixedSizeBinary(32) keys in {}", path.display()))?;
+        into.reserve(keys.len());
+        for i in 0..keys.len() {
+            into.push((keys.value(i).try_into()?, date));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::create_dir_all;
+
+    fn key(b: u8) -> [u8; 32] {
+        [b; 32]
+    }
+
+    /// Writes the keys of `ingest_date`, with its Silver partition committed or not.
+    fn ingest(root: &Path, ingest_date: &str, keys: &[[u8; 32]], committed: bool) -> KeyStore {
+        let store = KeyStore::open(root.join("keys"), &root.join("silver"), ingest_date).unwrap();
+        let silver = root.join("silver").join(format!("ingest_date={ingest_date}"));
+        create_dir_all(&silver).unwrap();
+        if committed {
+            File::create(silver.join(staging::SUCCESS_FILE)).unwrap();
+        }
+        store.write(ingest_date, keys).unwrap();
+        store
+    }
+
+    #[test]
+    fn prior_reports_the_first_ingest_keeping_a_key() {
+        let root = tempfile::tempdir().unwrap();
+        ingest(root.path(), "2024-01-01", &[key(1), key(3)], true);
+        ingest(root.path(), "2024-04-01", &[key(2), key(3), key(4)], true);
+
+        let store = KeyStore::open(root.path().join("keys"), &root.path().join("silver"), "2024-07-01").unwrap();
+        assert_eq!(store.prior_len(), 4);
+        assert_eq!(store.prior(&key(1)), Some("2024-01-01"));
+        assert_eq!(store.prior(&key(2)), Some("2024-04-01"));
+        assert_eq!(store.prior(&key(3)), Some("2024-01-01"));
+        assert_eq!(store.prior(&key(5)), None);
+    }
+
+    #[test]
+    fn only_earlier_ingests_with_committed_silver_count() {
+        let root = tempfile::tempdir().unwrap();
+        ingest(root.path(), "2024-01-01", &[key(1)], true);
+        ingest(root.path(), "2024-04-01", &[key(2)], false);
+        ingest(root.path(), "2024-07-01", &[key(3)], true);
+
+        let store = KeyStore::open(root.path().join("keys"), &root.path().join("silver"), "2024-07-01").unwrap();
+        assert_eq!(store.prior(&key(1)), Some("2024-01-01"));
+        assert_eq!(store.prior(&key(2)), None); // its validation failed before Silver was committed
+        assert_eq!(store.prior(&key(3)), None); // the ingest being re-validated
+    }
+
+    #[test]
+    fn committed_silver_without_its_keys_fails() {
+        let root = tempfile::tempdir().unwrap();
+        ingest(root.path(), "2024-01-01", &[key(1)], true);
+        let silver = root.path().join("silver").join("ingest_date=2024-04-01");
+        create_dir_all(&silver).unwrap();
+        File::create(silver.join(staging::SUCCESS_FILE)).unwrap();
+
+        let err = KeyStore::open(root.path().join("keys"), &root.path().join("silver"), "2024-07-01").err().unwrap();
+        assert!(err.to_string().contains("validate ingest_date=2024-04-01 again"), "{err:#}");
+    }
+}
@@ -0,0 +1,404 @@
+//! Mutation-level table: one row per mutation key, aggregating the rows that share it.
+//! DVF repeats `valeur_fonciere` (the total price) on every lot and local of a mutation,
+//! and a local on every culture line of its parcel, so the price per m² only makes sense
+//! over the built surface of the distinct locals.
+//!
+//! Layout: `silver/<slug>_mutations/ingest_date=YYYY-MM-DD/part-000000.arrow`, committed
+//! with the Silver partition. Each partition covers the Silver rows of every ingest up to
+//! its `ingest_date`. Rows without a key are left out.
+use anyhow::{Context, Result};
+use arrow::array::{
+    new_empty_array, Array, ArrayRef, AsArray, BooleanArray, Float64Array, Int32Array,
+    StringArray, UInt32Array,
+};
+use arrow::compute::{cast, concat, take};
+use arrow::datatypes::{DataType, Field, Float64Type, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use metadata::{MutationSpec, MUTATION_COLUMNS};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+#[derive(Debug, Default)]
+struct Acc {
+    rows: u32,
+    price: Option<f64>, // first non-null
+    parcels: Vec<u64>,  // hashes of the distinct parcels
+    locals: Vec<u64>,   // hashes of the distinct locals
+    surface: f64,
+    lots: i64,
+    types: Vec<(String, f64, u32)>, // local type -> built surface, locals
+}
+
+impl Acc {
+    /// The type holding the most built surface, then the most locals, then the first by name.
+    fn dominant_type(&self) -> Option<&str> {
+        self.types
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(a.2.cmp(&b.2)).then(b.0.cmp(&a.0)))
+            .map(|t| t.0.as_str())
+    }
+}
+
+pub(crate) struct Mutations {
+    schema: SchemaRef,
+    carry: Vec<usize>, // key first
+    key: usize,
+    price: usize,
+    surface: usize,
+    local_type: usize,
+    parcel: usize,
+    lots: Option<usize>,
+    local_key: Vec<usize>,
+    min_surface: f64,
+    index: HashMap<String, u32>,
+    accs: Vec<Acc>,              // by mutation, in order of first row
+    carried: Vec<Vec<ArrayRef>>, // by carried column, the first rows of the mutations of each batch
+}
+
+impl Mutations {
+    pub(crate) fn new(silver: &Schema, spec: &MutationSpec) -> Result<Self> {
+        let idx = |c: &String| {
+            silver
+                .index_of(c)
+                .with_context(|| format!("mutations column {c} is not a Silver column"))
+        };
+        let carry: Vec<usize> = std::iter::once(&spec.key)
+            .chain(&spec.carry)
+            .map(idx)
+            .collect::<Result<_>>()?;
+        let mut fields: Vec<Field> = carry.iter().map(|&i| silver.field(i).clone()).collect();
+        let [rows, parcels, multi, locals, lots, surface, dominant, prix_m2] = MUTATION_COLUMNS;
+        fields.extend([
+            Field::new(rows, DataType::Int32, false),
+            Field::new(parcels, DataType::Int32, false),
+            Field::new(multi, DataType::Boolean, false),
+            Field::new(locals, DataType::Int32, false),
+            Field::new(lots, DataType::Int32, true),
+            Field::new(surface, DataType::Float64, true),
+            Field::new(dominant, DataType::Utf8, true),
+            Field::new(prix_m2, DataType::Float64, true),
+        ]);
+        Ok(Self {
+            schema: Arc::new(Schema::new(fields)),
+            carried: vec![Vec::new(); carry.len()],
+            carry,
+            key: idx(&spec.key)?,
+            price: idx(&spec.price)?,
+            surface: idx(&spec.surface)?,
+            local_type: idx(&spec.local_type)?,
+            parcel: idx(&spec.parcel)?,
+            lots: spec.lots.as_ref().map(idx).transpose()?,
+            local_key: spec.local_key.iter().map(idx).collect::<Result<_>>()?,
+            min_surface: spec.min_surface,
+            index: HashMap::new(),
+            accs: Vec::new(),
+        })
+    }
+
+    pub(crate) fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    pub(crate) fn observe(&mut self, batch: &RecordBatch) -> Result<()> {
+        let text = |i: usize| cast(batch.column(i), &DataType::Utf8);
+        let num = |i: usize| cast(batch.column(i), &DataType::Float64);
+        let (keys, parcels, types) = (text(self.key)?, text(self.parcel)?, text(self.local_type)?);
+        let (keys, parcels, types) = (keys.as_string::<i32>(), parcels.as_string::<i32>(), types.as_string::<i32>());
+        let local_key = self.local_key.iter().map(|&i| text(i)).collect::<Result<Vec<_>, _>>()?;
+        let local_key: Vec<&StringArray> = local_key.iter().map(|a| a.as_string::<i32>()).collect();
+        let (prices, surfaces) = (num(self.price)?, num(self.surface)?);
+        let (prices, surfaces) = (prices.as_primitive::<Float64Type>(), surfaces.as_primitive::<Float64Type>());
+        let lots = self.lots.map(num).transpose()?;
+        let lots = lots.as_ref().map(|a| a.as_primitive::<Float64Type>());
+
+        let mut first = Vec::new();
+        for row in 0..batch.num_rows() {
+            if keys.is_null(row) {
+                continue;
+            }
+            let m = match self.index.get(keys.value(row)) {
+                Some(&m) => m,
+                None => {
+                    let m = self.accs.len() as u32;
+                    self.index.insert(keys.value(row).to_string(), m);
+                    self.accs.push(Acc::default());
+                    first.push(row as u32);
+                    m
+                }
+            };
+            let acc = &mut self.accs[m as usize];
+            acc.rows += 1;
+            if acc.price.is_none() && prices.is_valid(row) {
+                acc.price = Some(prices.value(row));
+            }
+            if parcels.is_valid(row) {
+                let h = hash([parcels.value(row)]);
+                if !acc.parcels.contains(&h) {
+                    acc.parcels.push(h);
+                }
+            }
+            // land-only rows have no local
+            if types.is_null(row) {
+                continue;
+            }
+            if !local_key.is_empty() {
+                let h = hash(local_key.iter().map(|a| a.is_valid(row).then(|| a.value(row))));
+                if acc.locals.contains(&h) {
+                    continue;
+                }
+                acc.locals.push(h);
+            } else {
+                acc.locals.push(0);
+            }
+            let surface = if surfaces.is_valid(row) { surfaces.value(row) } else { 0.0 };
+            acc.surface += surface;
+            if let Some(l) = lots
+                && l.is_valid(row)
+            {
+                acc.lots += l.value(row) as i64;
+            }
+            let ty = types.value(row);
+            match acc.types.iter_mut().find(|t| t.0 == ty) {
+                Some(t) => {
+                    t.1 += surface;
+                    t.2 += 1;
+                }
+                None => acc.types.push((ty.to_string(), surface, 1)),
+            }
+        }
+
+        if !first.is_empty() {
+            let first = UInt32Array::from(first);
+            for (out, &c) in self.carried.iter_mut().zip(&self.carry) {
+                out.push(take(batch.column(c), &first, None)?);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.accs.len()
+    }
+
+    /// Every mutation, in order of first row.
+    pub(crate) fn finish(self) -> Result<RecordBatch> {
+        let mut cols: Vec<ArrayRef> = Vec::with_capacity(self.schema.fields().len());
+        for (parts, field) in self.carried.iter().zip(self.schema.fields()) {
+            let parts: Vec<&dyn Array> = parts.iter().map(|a| a.as_ref()).collect();
+            cols.push(if parts.is_empty() { new_empty_array(field.data_type()) } else { concat(&parts)? });
+        }
+        let accs = &self.accs;
+        let count = |f: fn(&Acc) -> usize| -> ArrayRef {
+            Arc::new(accs.iter().map(|a| f(a) as i32).collect::<Int32Array>())
+        };
+        // no local: a land-only mutation, with neither built surface nor price per m²
+        let surface = |a: &Acc| (!a.locals.is_empty()).then_some(a.surface);
+        let prix_m2 = |a: &Acc| {
+            let s = surface(a).filter(|s| *s > 0.0 && *s >= self.min_surface)?;
+            Some(a.price? / s)
+        };
+        cols.extend([
+            count(|a| a.rows as usize),
+            count(|a| a.parcels.len()),
+            Arc::new(accs.iter().map(|a| Some(a.parcels.len() > 1)).collect::<BooleanArray>()) as ArrayRef,
+            count(|a| a.locals.len()),
+            Arc::new(
+                accs.iter()
+                    .map(|a| self.lots.map(|_| a.lots.clamp(0, i32::MAX as i64) as i32))
+                    .collect::<Int32Array>(),
+            ),
+            Arc::new(accs.iter().map(surface).collect::<Float64Array>()),
+            Arc::new(accs.iter().map(Acc::dominant_type).collect::<StringArray>()),
+            Arc::new(accs.iter().map(prix_m2).collect::<Float64Array>()),
+        ]);
+        Ok(RecordBatch::try_new(self.schema.clone(), cols)?)
+    }
+}
+
+fn hash<'a>(values: impl IntoIterator<Item = impl Hash + 'a>) -> u64 {
+    let mut h = DefaultHasher::new();
+    for v in values {
+        v.hash(&mut h);
+    }
+    h.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int32Builder;
+    use arrow::util::display::array_value_to_string;
+
+    /// A Silver row: key, price, parcel, local type, built surface, lots, and the culture
+    /// line it is repeated on.
+    type Row = (Option<&'static str>, f64, &'static str, Option<&'static str>, Option<f64>, Option<i32>, &'static str);
+
+    fn schema() -> Schema {
+        let utf8 = |name| Field::new(name, DataType::Utf8, true);
+        Schema::new(vec![
+            utf8("id_mutation"),
+            Field::new("valeur_fonciere", DataType::Float64, true),
+            utf8("id_parcelle"),
+            utf8("code_type_local"),
+            utf8("type_local"),
+            Field::new("surface_reelle_bati", DataType::Float64, true),
+            Field::new("nombre_lots", DataType::Int32, true),
+            utf8("nature_culture"),
+        ])
+    }
+
+    fn mutations() -> Mutations {
+        let spec = MutationSpec {
+            key: "id_mutation".to_string(),
+            carry: vec!["nature_culture".to_string()],
+            price: "valeur_fonciere".to_string(),
+            surface: "surface_reelle_bati".to_string(),
+            local_type: "type_local".to_string(),
+            parcel: "id_parcelle".to_string(),
+            lots: Some("nombre_lots".to_string()),
+            local_key: ["id_parcelle", "code_type_local", "surface_reelle_bati"].map(String::from).to_vec(),
+            min_surface: 9.0,
+        };
+        Mutations::new(&schema(), &spec).unwrap()
+    }
+
+    fn batch(rows: &[Row]) -> RecordBatch {
+        let code = |t: &str| match t {
+            "Maison" => "1",
+            "Appartement" => "2",
+            "Dépendance" => "3",
+            _ => "4",
+        };
+        let mut lots = Int32Builder::new();
+        rows.iter().for_each(|r| lots.append_option(r.5));
+        let cols: Vec<ArrayRef> = vec![
+            Arc::new(rows.iter().map(|r| r.0).collect::<StringArray>()),
+            Arc::new(rows.iter().map(|r| Some(r.1)).collect::<Float64Array>()),
+            Arc::new(rows.iter().map(|r| Some(r.2)).collect::<StringArray>()),
+            Arc::new(rows.iter().map(|r| r.3.map(code)).collect::<StringArray>()),
+            Arc::new(rows.iter().map(|r| r.3).collect::<StringArray>()),
+            Arc::new(rows.iter().map(|r| r.4).collect::<Float64Array>()),
+            Arc::new(lots.finish()),
+            Arc::new(rows.iter().map(|r| Some(r.6)).collect::<StringArray>()),
+        ];
+        RecordBatch::try_new(Arc::new(schema()), cols).unwrap()
+    }
+
+    /// The mutations of `batches`, one line each: the key and carried culture, then
+    /// `nb_lignes nb_parcelles multi_parcelles nb_locaux nb_lots surface_bati
+    /// type_local_dominant prix_m2` (`_` for null).
+    fn aggregate(batches: &[&[Row]]) -> Vec<String> {
+        let mut m = mutations();
+        for rows in batches {
+            m.observe(&batch(rows)).unwrap();
+        }
+        let out = m.finish().unwrap();
+        (0..out.num_rows())
+            .map(|row| {
+                let values: Vec<String> = out
+                    .columns()
+                    .iter()
+                    .map(|c| if c.is_null(row) { "_".to_string() } else { array_value_to_string(c, row).unwrap() })
+                    .collect();
+                values.join(" ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_multi_lot_sale_sums_its_distinct_locals() {
+        let m1 = Some("M1");
+        let rows = [
+            (m1, 300000.0, "P1", Some("Appartement"), Some(40.0), Some(2), "sol"),
+            (m1, 300000.0, "P1", Some("Dépendance"), None, Some(1), "sol"),
+            (m1, 300000.0, "P2", Some("Appartement"), Some(20.0), Some(1), "sol"),
+        ];
+        assert_eq!(aggregate(&[&rows]), ["M1 sol 3 2 true 3 4 60.0 Appartement 5000.0"]);
+    }
+
+    #[test]
+    fn a_local_repeated_on_culture_lines_counts_once() {
+        let m2 = Some("M2");
+        let rows = [
+            (m2, 250000.0, "P3", Some("Maison"), Some(100.0), Some(0), "sol"),
+            (m2, 250000.0, "P3", Some("Maison"), Some(100.0), Some(0), "jardin"),
+            (m2, 250000.0, "P3", Some("Maison"), Some(100.0), Some(0), "pré"),
+        ];
+        assert_eq!(aggregate(&[&rows]), ["M2 sol 3 1 false 1 0 100.0 Maison 2500.0"]);
+    }
+
+    #[test]
+    fn a_land_only_mutation_has_no_surface_nor_price_per_m2() {
+        let m3 = Some("M3");
+        let rows = [
+            (m3, 12000.0, "P4", None, None, None, "terres"),
+            (m3, 12000.0, "P5", None, None, None, "bois"),
+        ];
+        assert_eq!(aggregate(&[&rows]), ["M3 terres 2 2 true 0 0 _ _ _"]);
+    }
+
+    #[test]
+    fn prix_m2_is_null_below_min_surface() {
+        let rows = [
+            (Some("M4"), 40000.0, "P6", Some("Appartement"), Some(8.0), Some(1), "sol"),
+            (Some("M5"), 40000.0, "P7", Some("Local"), None, None, "sol"),
+            (Some("M6"), 45000.0, "P8", Some("Appartement"), Some(9.0), Some(1), "sol"),
+        ];
+        assert_eq!(
+            aggregate(&[&rows]),
+            [
+                "M4 sol 1 1 false 1 1 8.0 Appartement _",
+                "M5 sol 1 1 false 1 0 0.0 Local _",
+                "M6 sol 1 1 false 1 1 9.0 Appartement 5000.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_dominant_type_has_the_most_surface_then_locals_then_the_first_name() {
+        let rows = [
+            // one 60 m² house beats two apartments of 40 m² in all
+            (Some("M7"), 500000.0, "P9", Some("Maison"), Some(60.0), None, "sol"),
+            (Some("M7"), 500000.0, "P9", Some("Appartement"), Some(20.0), None, "sol"),
+            (Some("M7"), 500000.0, "P10", Some("Appartement"), Some(20.0), None, "sol"),
+            // 50 m² each: two apartments beat one house
+            (Some("M8"), 500000.0, "P11", Some("Maison"), Some(50.0), None, "sol"),
+            (Some("M8"), 500000.0, "P11", Some("Appartement"), Some(25.0), None, "sol"),
+            (Some("M8"), 500000.0, "P12", Some("Appartement"), Some(25.0), None, "sol"),
+            // same surface and locals: the first by name
+            (Some("M9"), 300000.0, "P13", Some("Maison"), Some(30.0), None, "sol"),
+            (Some("M9"), 300000.0, "P13", Some("Appartement"), Some(30.0), None, "sol"),
+        ];
+        assert_eq!(
+            aggregate(&[&rows]),
+            [
+                "M7 sol 3 2 true 3 0 100.0 Maison 5000.0",
+                "M8 sol 3 2 true 3 0 100.0 Appartement 5000.0",
+                "M9 sol 2 1 false 2 0 60.0 Appartement 5000.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn mutations_aggregate_across_batches() {
+        let first = [
+            (Some("M9"), 200000.0, "P11", Some("Appartement"), Some(30.0), Some(1), "sol"),
+            (None, 1.0, "P0", Some("Maison"), Some(10.0), Some(1), "sol"), // no key: left out
+            (Some("M10"), 90000.0, "P12", Some("Maison"), Some(45.0), Some(0), "sol"),
+        ];
+        // an earlier partition's rows, then a later drop adding a line to M9
+        let second = [
+            (Some("M11"), 150000.0, "P13", Some("Appartement"), Some(50.0), Some(1), "sol"),
+            (Some("M9"), 200000.0, "P14", Some("Appartement"), Some(10.0), Some(1), "jardin"),
+        ];
+        assert_eq!(
+            aggregate(&[&first, &second]),
+            [
+                "M9 sol 2 2 true 2 2 40.0 Appartement 5000.0",
+                "M10 sol 1 1 false 1 0 45.0 Maison 2000.0",
+                "M11 sol 1 1 false 1 1 50.0 Appartement 3000.0",
+            ]
+        );
+    }
+}
@@ -0,0 +1,603 @@
+//! Column profile of a Silver partition: null ratio, distinct-count estimate, min/max,
+//! quantiles of numeric columns and the most frequent values of selected columns.
+//! Each profile is compared with the one of the previous ingest to flag regressions
+//! in a new source release.
+//!
+//! Layout: `silver/<slug>/ingest_date=YYYY-MM-DD/profile.json` (+ `profile.html`),
+//! committed with the Silver partition.
+use anyhow::{anyhow, Context, Result};
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::datatypes::{
+    DataType, Date32Type, Decimal128Type, Float64Type, Int16Type, Int32Type, Int64Type, Schema,
+};
+use arrow::record_batch::RecordBatch;
+use metadata::staging;
+use metadata::ProfileSpec;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::{read_dir, read_to_string, write};
+use std::path::Path;
+use time::Date;
+
+pub const PROFILE_FILE: &str = "profile.json";
+const HTML_FILE: &str = "profile.html";
+
+/// Values kept per numeric column to compute quantiles.
+const SAMPLE_SIZE: usize = 100_000;
+/// HyperLogLog precision: 2^14 registers, ~0.8% standard error.
+const HLL_BITS: u32 = 14;
+
+// A change is reported when the null ratio moves by this much (absolute)...
+const NULL_RATIO_SHIFT: f64 = 0.05;
+// ... the distinct estimate or the median by this share of the previous value...
+const RELATIVE_SHIFT: f64 = 0.2;
+// ... or a top value's share of the rows by this much (absolute).
+const TOP_SHARE_SHIFT: f64 = 0.05;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub dataset: String,
+    pub ingest_date: String,
+    pub run_id: String,
+    pub rows: u64,
+    pub columns: Vec<ColumnProfile>,
+    /// Profile this one was compared with: `ingest_date=YYYY-MM-DD`.
+    pub baseline: Option<String>,
+    pub changes: Vec<ProfileChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnProfile {
+    pub name: String,
+    pub data_type: String, // Arrow type, e.g. "Decimal128(12, 2)"
+    pub nulls: u64,
+    pub null_ratio: f64,
+    pub distinct_estimate: u64,
+    pub min: Option<Stat>,
+    pub max: Option<Stat>,
+    /// Numeric columns only; from a sample of `sampled` values when the column holds more.
+    #[serde(default)]
+    pub quantiles: Vec<Quantile>,
+    #[serde(default)]
+    pub sampled: u64,
+    #[serde(default)]
+    pub top: Vec<TopValue>,
+}
+
+/// A number, or text for strings and dates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Stat {
+    Num(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quantile {
+    pub q: f64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopValue {
+    pub value: String,
+    pub count: u64,
+    pub ratio: f64, // share of all rows, nulls included
+}
+
+/// A metric that moved noticeably since the baseline profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileChange {
+    pub column: String,
+    pub metric: String, // null_ratio, distinct_estimate, p50, top:<value>, added, removed
+    pub previous: Option<f64>,
+    pub current: Option<f64>,
+}
+
+impl ProfileChange {
+    pub fn summary(&self) -> String {
+        let show = |v: Option<f64>| v.map(fmt_num).unwrap_or_else(|| "-".to_string());
+        format!("{} {}: {} -> {}", self.column, self.metric, show(self.previous), show(self.current))
+    }
+}
+
+impl ColumnProfile {
+    fn median(&self) -> Option<f64> {
+        self.quantiles.iter().find(|q| q.q == 0.5).map(|q| q.value)
+    }
+}
+
+// ---------- accumulation
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Text,
+    Num,
+    Date,
+    Bytes,
+}
+
+struct ColumnAcc {
+    name: String,
+    data_type: DataType,
+    kind: Kind,
+    nulls: u64,
+    hll: Hll,
+    min_num: f64,
+    max_num: f64,
+    min_text: Option<String>,
+    max_text: Option<String>,
+    sample: Option<Reservoir>,
+    top: Option<HashMap<String, u64>>,
+}
+
+/// Profiles Silver batches as validate writes them.
+pub(crate) struct Profiler {
+    cols: Vec<ColumnAcc>,
+    rows: u64,
+    top_k: usize,
+    quantiles: Vec<f64>,
+}
+
+impl Profiler {
+    pub(crate) fn new(schema: &Schema, spec: &ProfileSpec) -> Result<Self> {
+        for c in &spec.top_k_columns {
+            schema
+                .index_of(c)
+                .map_err(|_| anyhow!("profile column {c} is not a Silver column"))?;
+        }
+        let cols = schema
+            .fields()
+            .iter()
+            .map(|f| {
+                let kind = match f.data_type() {
+                    DataType::Utf8 => Kind::Text,
+                    DataType::Int16
+                    | DataType::Int32
+                    | DataType::Int64
+                    | DataType::Float64
+                    | DataType::Decimal128(..) => Kind::Num,
+                    DataType::Date32 => Kind::Date,
+                    _ => Kind::Bytes,
+                };
+                ColumnAcc {
+                    name: f.name().clone(),
+                    data_type: f.data_type().clone(),
+                    kind,
+                    nulls: 0,
+                    hll: Hll::new(),
+                    min_num: f64::INFINITY,
+                    max_num: f64::NEG_INFINITY,
+                    min_text: None,
+                    max_text: None,
+                    sample: matches!(kind, Kind::Num).then(|| Reservoir::new(f.name())),
+                    top: spec.top_k_columns.contains(f.name()).then(HashMap::new),
+                }
+            })
+            .collect();
+        Ok(Self {
+            cols,
+            rows: 0,
+            top_k: spec.top_k,
+            quantiles: spec.quantiles.clone(),
+        })
+    }
+
+    pub(crate) fn observe(&mut self, batch: &RecordBatch) -> Result<()> {
+        self.rows += batch.num_rows() as u64;
+        for (acc, array) in self.cols.iter_mut().zip(batch.columns()) {
+            acc.observe(array)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self, dataset: &str, ingest_date: &str, run_id: &str) -> Profile {
+        let rows = self.rows;
+        let columns = self
+            .cols
+            .into_iter()
+            .map(|c| c.finish(rows, self.top_k, &self.quantiles))
+            .collect();
+        Profile {
+            dataset: dataset.to_string(),
+            ingest_date: ingest_date.to_string(),
+            run_id: run_id.to_string(),
+            rows,
+            columns,
+            baseline: None,
+            changes: Vec::new(),
+        }
+    }
+}
+
+impl ColumnAcc {
+    fn observe(&mut self, array: &ArrayRef) -> Result<()> {
+        self.nulls += array.null_count() as u64;
+        match array.data_type() {
+            DataType::Utf8 => {
+                for s in array.as_string::<i32>().iter().flatten() {
+                    self.add_text(s);
+                }
+            }
+            DataType::Int16 => {
+                for v in array.as_primitive::<Int16Type>().iter().flatten() {
+                    self.add_num(f64::from(v));
+                }
+            }
+            DataType::Int32 => {
+                for v in array.as_primitive::<Int32Type>().iter().flatten() {
+                    self.add_num(f64::from(v));
+                }
+            }
+            DataType::Int64 => {
+                for v in array.as_primitive::<Int64Type>().iter().flatten() {
+                    self.add_num(v as f64);
+                }
+            }
+            DataType::Float64 => {
+                for v in array.as_primitive::<Float64Type>().iter().flatten() {
+                    self.add_num(v);
+                }
+            }
+            DataType::Decimal128(_, scale) => {
+                let div = 10f64.powi(i32::from(*scale));
+                for v in array.as_primitive::<Decimal128Type>().iter().flatten() {
+                    self.add_num(v as f64 / div);
+                }
+            }
+            DataType::Date32 => {
+                for v in array.as_primitive::<Date32Type>().iter().flatten() {
+                    self.add_num(f64::from(v));
+                }
+            }
+            DataType::FixedSizeBinary(_) => {
+                for v in array.as_fixed_size_binary().iter().flatten() {
+                    self.hll.add(hash_bytes(v));
+                }
+            }
+            other => return Err(anyhow!("cannot profile column {} of type {other}", self.name)),
+        }
+        Ok(())
+    }
+
+    fn add_text(&mut self, s: &str) {
+        self.hll.add(hash_bytes(s.as_bytes()));
+        if self.min_text.as_deref().is_none_or(|m| s < m) {
+            self.min_text = Some(s.to_string());
+        }
+        if self.max_text.as_deref().is_none_or(|m| s > m) {
+            self.max_text = Some(s.to_string());
+        }
+        if let Some(top) = &mut self.top {
+            match top.get_mut(s) {
+                Some(n) => *n += 1,
+                None => {
+                    top.insert(s.to_string(), 1);
+                }
+            }
+        }
+    }
+
+    /// Numbers and dates (days since the epoch).
+    fn add_num(&mut self, v: f64) {
+        self.hll.add(mix64(v.to_bits()));
+        self.min_num = self.min_num.min(v);
+        self.max_num = self.max_num.max(v);
+        if let Some(sample) = &mut self.sample {
+            sample.add(v);
+        }
+        if let Some(top) = &mut self.top {
+            *top.entry(render(self.kind, v)).or_insert(0) += 1;
+        }
+    }
+
+    fn finish(self, rows: u64, top_k: usize, quantiles: &[f64]) -> ColumnProfile {
+        let ratio = |n: u64| if rows == 0 { 0.0 } else { n as f64 / rows as f64 };
+        let (min, max) = match self.kind {
+            Kind::Text => (self.min_text.map(Stat::Text), self.max_text.map(Stat::Text)),
+            Kind::Num | Kind::Date if self.min_num <= self.max_num => {
+                let stat = |v: f64| match self.kind {
+                    Kind::Date => Stat::Text(render(Kind::Date, v)),
+                    _ => Stat::Num(v),
+                };
+                (Some(stat(self.min_num)), Some(stat(self.max_num)))
+            }
+            _ => (None, None),
+        };
+        let (quantiles, sampled) = match self.sample {
+            Some(mut s) if !s.values.is_empty() => {
+                s.values.sort_by(f64::total_cmp);
+                let last = (s.values.len() - 1) as f64;
+                let qs = quantiles
+                    .iter()
+                    .map(|&q| Quantile { q, value: s.values[(q * last).round() as usize] })
+                    .collect();
+                (qs, s.values.len() as u64)
+            }
+            _ => (Vec::new(), 0),
+        };
+        let mut top: Vec<(String, u64)> = self.top.unwrap_or_default().into_iter().collect();
+        // most frequent first, ties by value so the order is stable
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top.truncate(top_k);
+        ColumnProfile {
+            name: self.name,
+            data_type: self.data_type.to_string(),
+            nulls: self.nulls,
+            null_ratio: ratio(self.nulls),
+            distinct_estimate: self.hll.estimate(),
+            min,
+            max,
+            quantiles,
+            sampled,
+            top: top
+                .into_iter()
+                .map(|(value, count)| TopValue { value, count, ratio: ratio(count) })
+                .collect(),
+        }
+    }
+}
+
+fn render(kind: Kind, v: f64) -> String {
+    match kind {
+        Kind::Date => Date::from_julian_day(UNIX_EPOCH_JULIAN_DAY + v as i32)
+            .map(|d| d.to_string())
+            .unwrap_or_else(|_| v.to_string()),
+        _ => fmt_num(v),
+    }
+}
+
+const UNIX_EPOCH_JULIAN_DAY: i32 = 2_440_588;
+
+fn fmt_num(v: f64) -> String {
+    if v.fract() == 0.0 && v.abs() < 1e15 {
+        format!("{v:.0}")
+    } else {
+        format!("{v:.4}").trim_end_matches('0').to_string()
+    }
+}
+
+/// HyperLogLog distinct-count sketch.
+struct Hll {
+    registers: Vec<u8>,
+}
+
+impl Hll {
+    fn new() -> Self {
+        Self { registers: vec![0; 1 << HLL_BITS] }
+    }
+
+    fn add(&mut self, hash: u64) {
+        let idx = (hash >> (64 - HLL_BITS)) as usize;
+        let rank = ((hash << HLL_BITS) | (1 << (HLL_BITS - 1))).leading_zeros() as u8 + 1;
+        if rank > self.registers[idx] {
+            self.registers[idx] = rank;
+        }
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-i32::from(r))).sum();
+        let raw = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        // small cardinalities: linear counting is more accurate
+        if raw <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            raw.round() as u64
+        }
+    }
+}
+
+/// Uniform sample of a column's values (algorithm R), seeded by the column name so
+/// that the same Silver data always yields the same quantiles.
+struct Reservoir {
+    values: Vec<f64>,
+    seen: u64,
+    rng: u64,
+}
+
+impl Reservoir {
+    fn new(column: &str) -> Self {
+        Self { values: Vec::new(), seen: 0, rng: hash_bytes(column.as_bytes()) }
+    }
+
+    fn add(&mut self, v: f64) {
+        self.seen += 1;
+        if self.values.len() < SAMPLE_SIZE {
+            self.values.push(v);
+            return;
+        }
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let j = mix64(self.rng) % self.seen;
+        if (j as usize) < SAMPLE_SIZE {
+            self.values[j as usize] = v;
+        }
+    }
+}
+
+/// FNV-1a, finalized with `mix64`: stable across builds, unlike `DefaultHasher`.
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        h ^= u64::from(b);
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    mix64(h)
+}
+
+/// SplitMix64 finalizer.
+fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// ---------- baseline & comparison
+
+/// Profile of the latest committed Silver partition before `ingest_date`, if any.
+pub(crate) fn previous(dataset_dir: &Path, ingest_date: &str) -> Result<Option<Profile>> {
+    if !dataset_dir.exists() {
+        return Ok(None);
+    }
+    let mut dates = Vec::new();
+    for entry in read_dir(dataset_dir).with_context(|| format!("read {}", dataset_dir.display()))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(d) = name.strip_prefix("ingest_date=")
+            && d < ingest_date
+            && staging::is_committed(&entry.path())
+            && entry.path().join(PROFILE_FILE).is_file()
+        {
+            dates.push((d.to_string(), entry.path().join(PROFILE_FILE)));
+        }
+    }
+    dates.sort();
+    for (_, path) in dates.into_iter().rev() {
+        let txt = read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+        match serde_json::from_str(&txt) {
+            Ok(p) => return Ok(Some(p)),
+            Err(e) => tracing::warn!(file = %path.display(), "ignoring unreadable profile: {e}"),
+        }
+    }
+    Ok(None)
+}
+
+/// Metrics of `cur` that moved noticeably since `prev`, in column order.
+pub(crate) fn compare(prev: &Profile, cur: &Profile) -> Vec<ProfileChange> {
+    let mut changes = Vec::new();
+    let mut push = |column: &str, metric: String, previous: Option<f64>, current: Option<f64>| {
+        changes.push(ProfileChange { column: column.to_string(), metric, previous, current });
+    };
+    let shifted = |a: f64, b: f64| (b - a).abs() > RELATIVE_SHIFT * a.abs().max(f64::EPSILON);
+
+    for c in &cur.columns {
+        let Some(p) = prev.columns.iter().find(|p| p.name == c.name) else {
+            push(&c.name, "added".to_string(), None, None);
+            continue;
+        };
+        if (c.null_ratio - p.null_ratio).abs() > NULL_RATIO_SHIFT {
+            push(&c.name, "null_ratio".to_string(), Some(p.null_ratio), Some(c.null_ratio));
+        }
+        let (pd, cd) = (p.distinct_estimate as f64, c.distinct_estimate as f64);
+        if shifted(pd, cd) {
+            push(&c.name, "distinct_estimate".to_string(), Some(pd), Some(cd));
+        }
+        if let (Some(pm), Some(cm)) = (p.median(), c.median())
+            && shifted(pm, cm)
+        {
+            push(&c.name, "p50".to_string(), Some(pm), Some(cm));
+        }
+        // values entering, leaving or moving within the top list
+        let share = |col: &ColumnProfile, v: &str| col.top.iter().find(|t| t.value == v).map(|t| t.ratio);
+        let mut values: Vec<&str> = c.top.iter().chain(&p.top).map(|t| t.value.as_str()).collect();
+        values.sort_unstable();
+        values.dedup();
+        for v in values {
+            let (ps, cs) = (share(p, v), share(c, v));
+            if (cs.unwrap_or(0.0) - ps.unwrap_or(0.0)).abs() > TOP_SHARE_SHIFT {
+                push(&c.name, format!("top:{v}"), ps, cs);
+            }
+        }
+    }
+    for p in &prev.columns {
+        if !cur.columns.iter().any(|c| c.name == p.name) {
+            push(&p.name, "removed".to_string(), None, None);
+        }
+    }
+    changes
+}
+
+// ---------- output
+
+/// Writes `profile.json` and `profile.html` into `dir`.
+pub(crate) fn write_profile(dir: &Path, profile: &Profile, baseline: Option<&Profile>) -> Result<()> {
+    let json = dir.join(PROFILE_FILE);
+    write(&json, serde_json::to_vec_pretty(profile)?).with_context(|| format!("write {}", json.display()))?;
+    let html = dir.join(HTML_FILE);
+    write(&html, render_html(profile, baseline)).with_context(|| format!("write {}", html.display()))?;
+    Ok(())
+}
+
+fn render_html(p: &Profile, baseline: Option<&Profile>) -> String {
+    let mut h = String::new();
+    let title = format!("{} — ingest_date={}", p.dataset, p.ingest_date);
+    let _ = write!(
+        h,
+        "<!doctype html>\n<html><head><meta charset=\"utf-8\"><title>{t}</title>\n<style>\
+         body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse}}\
+         td,th{{border:1px solid #ccc;padding:.3em .6em;text-align:right;vertical-align:top}}\
+         td:first-child,th:first-child{{text-align:left}}.prev{{color:#888;font-size:.85em}}\
+         .changed{{background:#fde2e2}}</style></head><body>\n<h1>{t}</h1>\n\
+         <p>{rows} rows, run {run}. Compared with: {base}.</p>\n",
+        t = esc(&title),
+        rows = p.rows,
+        run = esc(&p.run_id),
+        base = esc(p.baseline.as_deref().unwrap_or("nothing (first profile)")),
+    );
+
+    if !p.changes.is_empty() {
+        h.push_str("<h2>Changes</h2>\n<ul>\n");
+        for c in &p.changes {
+            let _ = writeln!(h, "<li>{}</li>", esc(&c.summary()));
+        }
+        h.push_str("</ul>\n");
+    }
+
+    h.push_str("<h2>Columns</h2>\n<table>\n<tr><th>column</th><th>type</th><th>null ratio</th>\
+                <th>distinct (est.)</th><th>min</th><th>max</th><th>quantiles</th><th>top values</th></tr>\n");
+    for c in &p.columns {
+        let prev = baseline.and_then(|b| b.columns.iter().find(|x| x.name == c.name));
+        let changed = |metric: &str| {
+            p.changes
+                .iter()
+                .any(|x| x.column == c.name && (x.metric == metric || (metric == "top" && x.metric.starts_with("top:"))))
+        };
+        let cell = |metric: &str, cur: String, prev: Option<String>| {
+            let class = if changed(metric) { " class=\"changed\"" } else { "" };
+            let prev = prev
+                .filter(|v| *v != cur)
+                .map(|v| format!("<br><span class=\"prev\">was {}</span>", esc(&v)))
+                .unwrap_or_default();
+            format!("<td{class}>{}{prev}</td>", esc(&cur))
+        };
+        let stat = |s: &Option<Stat>| match s {
+            Some(Stat::Num(v)) => fmt_num(*v),
+            Some(Stat::Text(t)) => t.clone(),
+            None => String::new(),
+        };
+        let quantiles = |c: &ColumnProfile| {
+            c.quantiles
+                .iter()
+                .map(|q| format!("p{}={}", fmt_num(q.q * 100.0), fmt_num(q.value)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let top = |c: &ColumnProfile| {
+            c.top
+                .iter()
+                .map(|t| format!("{} ({:.1}%)", t.value, t.ratio * 100.0))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let _ = writeln!(
+            h,
+            "<tr><td>{}</td><td>{}</td>{}{}{}{}{}{}</tr>",
+            esc(&c.name),
+            esc(&c.data_type),
+            cell("null_ratio", format!("{:.2}%", c.null_ratio * 100.0), prev.map(|x| format!("{:.2}%", x.null_ratio * 100.0))),
+            cell("distinct_estimate", c.distinct_estimate.to_string(), prev.map(|x| x.distinct_estimate.to_string())),
+            cell("min", stat(&c.min), prev.map(|x| stat(&x.min))),
+            cell("max", stat(&c.max), prev.map(|x| stat(&x.max))),
+            cell("p50", quantiles(c), prev.map(quantiles)),
+            cell("top", top(c), prev.map(top)),
+        );
+    }
+    h.push_str("</table>\n</body></html>\n");
+    h
+}
+
+fn esc(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
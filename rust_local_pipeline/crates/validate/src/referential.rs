@@ -0,0 +1,312 @@
+//! INSEE and La Poste reference tables for commune, department and postal code checks.
+//!
+//! Layout: `gold/ref/cog_communes.csv` is the COG commune list
+//! (`TYPECOM,COM,...,DEP,...,COMPARENT`, e.g. `v_commune_2024.csv`).
+//! `gold/ref/codes_postaux.csv` is the La Poste base officielle des codes postaux
+//! (`code_commune_insee`, `code_postal`, ...; `#Code_commune_INSEE;...` in the La Poste
+//! export). Both are UTF-8, `,` or `;` separated.
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+struct Commune {
+    dep: Option<String>,
+    parent: Option<String>, // commune of an arrondissement, commune nouvelle of a former commune
+    merged: bool,           // commune déléguée / associée
+}
+
+/// The COG commune list: current communes, municipal arrondissements and the former
+/// communes merged into a commune nouvelle.
+pub(crate) struct Communes {
+    communes: HashMap<String, Commune>,
+    children: HashMap<String, Vec<String>>, // arrondissements and former communes, by parent
+    departments: HashSet<String>,
+}
+
+impl Communes {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let mut rdr = open_csv(path)?;
+        let idx = columns(path, rdr.headers()?, &["typecom", "com", "dep", "comparent"])?;
+        let mut communes: HashMap<String, Commune> = HashMap::new();
+        for rec in rdr.records() {
+            let rec = rec.with_context(|| format!("read {}", path.display()))?;
+            let field = |i: usize| rec.get(idx[i]).map(str::trim).filter(|v| !v.is_empty());
+            let (Some(ty), Some(code)) = (field(0), field(1)) else { continue };
+            let code = insee_code(code);
+            let dep = field(2).map(str::to_string);
+            let parent = field(3).map(insee_code);
+            match ty {
+                "COM" => {
+                    communes.insert(code, Commune { dep, parent: None, merged: false });
+                }
+                "ARM" => {
+                    communes.insert(code, Commune { dep, parent, merged: false });
+                }
+                // a commune déléguée can share the code of its commune nouvelle: keep the COM row
+                "COMD" | "COMA" => {
+                    communes.entry(code).or_insert(Commune { dep, parent, merged: true });
+                }
+                _ => {}
+            }
+        }
+        // arrondissements and former communes may come without a department: the parent's
+        let mut children: HashMap<String, Vec<String>> = HashMap::new();
+        let mut deps = Vec::new();
+        for (code, c) in &communes {
+            let Some(p) = c.parent.as_ref().filter(|p| *p != code) else { continue };
+            children.entry(p.clone()).or_default().push(code.clone());
+            if c.dep.is_none() {
+                deps.push((code.clone(), communes.get(p).and_then(|p| p.dep.clone())));
+            }
+        }
+        for (code, dep) in deps {
+            if let Some(c) = communes.get_mut(&code) {
+                c.dep = dep;
+            }
+        }
+        let departments = communes
+            .values()
+            .filter(|c| c.parent.is_none())
+            .filter_map(|c| c.dep.clone())
+            .collect();
+        Ok(Self { communes, children, departments })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.communes.len()
+    }
+
+    pub(crate) fn is_known(&self, code: &str) -> bool {
+        self.communes.contains_key(code)
+    }
+
+    /// The commune nouvelle `code` was merged into; `None` for current (or unknown) communes.
+    pub(crate) fn merged_into(&self, code: &str) -> Option<&str> {
+        let c = self.communes.get(code)?;
+        if c.merged { c.parent.as_deref() } else { None }
+    }
+
+    pub(crate) fn department_of(&self, code: &str) -> Option<&str> {
+        self.communes.get(code)?.dep.as_deref()
+    }
+
+    pub(crate) fn is_department(&self, code: &str) -> bool {
+        self.departments.contains(code)
+    }
+
+    /// `code`, its parent (commune nouvelle, or commune of an arrondissement) and the
+    /// parent's other former communes or arrondissements: the postal table may list any of them.
+    pub(crate) fn related<'a>(&'a self, code: &'a str) -> Vec<&'a str> {
+        let top = self.communes.get(code).and_then(|c| c.parent.as_deref()).unwrap_or(code);
+        let mut out = vec![code];
+        if top != code {
+            out.push(top);
+        }
+        for m in self.children.get(top).into_iter().flatten() {
+            if m != code {
+                out.push(m);
+            }
+        }
+        out
+    }
+}
+
+/// The La Poste postal code table, both ways.
+pub(crate) struct PostalCodes {
+    by_postal: HashMap<String, Vec<String>>,  // postal code -> communes
+    by_commune: HashMap<String, Vec<String>>, // commune -> postal codes
+}
+
+impl PostalCodes {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let mut rdr = open_csv(path)?;
+        let idx = columns(path, rdr.headers()?, &["code_commune_insee", "code_postal"])?;
+        let mut by_postal: HashMap<String, Vec<String>> = HashMap::new();
+        let mut by_commune: HashMap<String, Vec<String>> = HashMap::new();
+        for rec in rdr.records() {
+            let rec = rec.with_context(|| format!("read {}", path.display()))?;
+            let (Some(commune), Some(postal)) = (rec.get(idx[0]), rec.get(idx[1])) else { continue };
+            let (commune, postal) = (insee_code(commune.trim()), insee_code(postal.trim()));
+            if commune.is_empty() || postal.is_empty() {
+                continue;
+            }
+            // one row per (commune, postal code, ligne 5): keep each pair once
+            let communes = by_postal.entry(postal.clone()).or_default();
+            if !communes.contains(&commune) {
+                communes.push(commune.clone());
+                by_commune.entry(commune).or_default().push(postal);
+            }
+        }
+        Ok(Self { by_postal, by_commune })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.by_postal.len()
+    }
+
+    pub(crate) fn is_known(&self, postal: &str) -> bool {
+        self.by_postal.contains_key(postal)
+    }
+
+    pub(crate) fn postal_codes_of(&self, commune: &str) -> &[String] {
+        self.by_commune.get(commune).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+/// Five-character INSEE/postal code: numeric codes that lost their leading zero are padded.
+fn insee_code(s: &str) -> String {
+    if s.len() < 5 && s.bytes().all(|b| b.is_ascii_digit()) {
+        format!("{s:0>5}")
+    } else {
+        s.to_string()
+    }
+}
+
+fn open_csv(path: &Path) -> Result<csv::Reader<BufReader<File>>> {
+    let f = File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let mut buf = BufReader::new(f);
+    // `;` in La Poste exports, `,` in the INSEE files and the data.gouv.fr mirrors
+    let header = buf.fill_buf()?;
+    let line = header.split(|&b| b == b'\n').next().unwrap_or_default();
+    let semi = line.iter().filter(|&&b| b == b';').count();
+    let comma = line.iter().filter(|&&b| b == b',').count();
+    Ok(csv::ReaderBuilder::new()
+        .delimiter(if semi > comma { b';' } else { b',' })
+        .flexible(true)
+        .from_reader(buf))
+}
+
+/// Positions of `names` in the header, matched case-insensitively.
+fn columns(path: &Path, header: &csv::StringRecord, names: &[&str]) -> Result<Vec<usize>> {
+    names
+        .iter()
+        .map(|n| {
+            header
+                .iter()
+                .position(|h| h.trim_start_matches(['\u{feff}', '#']).trim().eq_ignore_ascii_case(n))
+                .ok_or_else(|| anyhow!("{}: missing column {n}", path.display()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A COG extract: a commune nouvelle sharing its code with a commune déléguée, a former
+    /// commune listed without its department, Paris and Lyon arrondissements, a code that lost
+    /// its leading zero and Corsica.
+    const COG: &str = "\u{feff}TYPECOM,COM,REG,DEP,LIBELLE,COMPARENT
+COM,01001,84,01,L'Abergement-Clémenciat,
+COM,01015,84,01,Arboys en Bugey,
+COMD,01015,84,01,Arbignieu,01015
+COMD,01340,84,01,Saint-Bois,01015
+COM,14666,28,14,Sannerville,
+COMA,14712,,,Troarn,14666
+COM,75056,11,75,Paris,
+ARM,75101,,,Paris 1er Arrondissement,75056
+ARM,75102,,,Paris 2e Arrondissement,75056
+COM,69123,84,69,Lyon,
+ARM,69381,,,Lyon 1er Arrondissement,69123
+COM,1004,84,01,Ambérieu-en-Bugey,
+COM,2A004,94,2A,Ajaccio,
+";
+
+    /// A La Poste extract: one row per ligne 5, arrondissements listed for Paris but not Lyon,
+    /// former communes not listed, and two postal codes for Ajaccio.
+    const POSTAL: &str = "#Code_commune_INSEE;Nom_de_la_commune;Code_postal;Libellé_d_acheminement;Ligne_5
+01001;L ABERGEMENT CLEMENCIAT;01400;L ABERGEMENT CLEMENCIAT;
+01015;ARBOYS EN BUGEY;01300;ARBOYS EN BUGEY;
+01015;ARBOYS EN BUGEY;01300;ARBOYS EN BUGEY;ARBIGNIEU
+01015;ARBOYS EN BUGEY;01300;ARBOYS EN BUGEY;ST BOIS
+1004;AMBERIEU EN BUGEY;1500;AMBERIEU EN BUGEY;
+14666;SANNERVILLE;14940;SANNERVILLE;
+75101;PARIS 01;75001;PARIS;
+75102;PARIS 02;75002;PARIS;
+69123;LYON;69001;LYON;
+2A004;AJACCIO;20000;AJACCIO;
+2A004;AJACCIO;20090;AJACCIO;
+";
+
+    /// The COG and La Poste extracts, loaded from `dir`.
+    pub(crate) fn references(dir: &Path) -> (Communes, PostalCodes) {
+        let (cog, postal) = (dir.join("cog_communes.csv"), dir.join("codes_postaux.csv"));
+        std::fs::write(&cog, COG).unwrap();
+        std::fs::write(&postal, POSTAL).unwrap();
+        (Communes::load(&cog).unwrap(), PostalCodes::load(&postal).unwrap())
+    }
+
+    fn sorted(mut v: Vec<&str>) -> Vec<&str> {
+        v.sort_unstable();
+        v
+    }
+
+    #[test]
+    fn former_communes_point_to_their_commune_nouvelle() {
+        let dir = tempfile::tempdir().unwrap();
+        let (communes, _) = references(dir.path());
+        assert_eq!(communes.len(), 12);
+        // the COM row wins over the commune déléguée sharing its code
+        assert_eq!(communes.merged_into("01015"), None);
+        assert_eq!(communes.merged_into("01340"), Some("01015"));
+        assert_eq!(communes.merged_into("14712"), Some("14666"));
+        // arrondissements are current, unknown codes are not merged
+        assert_eq!(communes.merged_into("75101"), None);
+        assert_eq!(communes.merged_into("99999"), None);
+        assert!(communes.is_known("01004"));
+        assert!(!communes.is_known("1004"));
+    }
+
+    #[test]
+    fn children_inherit_the_parent_department() {
+        let dir = tempfile::tempdir().unwrap();
+        let (communes, _) = references(dir.path());
+        assert_eq!(communes.department_of("01340"), Some("01"));
+        assert_eq!(communes.department_of("14712"), Some("14"));
+        assert_eq!(communes.department_of("75102"), Some("75"));
+        assert_eq!(communes.department_of("69381"), Some("69"));
+        assert_eq!(communes.department_of("2A004"), Some("2A"));
+        assert_eq!(communes.department_of("99999"), None);
+        for d in ["01", "14", "75", "69", "2A"] {
+            assert!(communes.is_department(d), "{d}");
+        }
+        assert!(!communes.is_department("92"));
+    }
+
+    #[test]
+    fn related_spans_the_parent_and_its_children() {
+        let dir = tempfile::tempdir().unwrap();
+        let (communes, _) = references(dir.path());
+        assert_eq!(communes.related("01340"), ["01340", "01015"]);
+        assert_eq!(communes.related("01015"), ["01015", "01340"]);
+        assert_eq!(communes.related("75101"), ["75101", "75056", "75102"]);
+        assert_eq!(sorted(communes.related("75056")), ["75056", "75101", "75102"]);
+        assert_eq!(communes.related("01001"), ["01001"]);
+        assert_eq!(communes.related("99999"), ["99999"]);
+    }
+
+    #[test]
+    fn postal_codes_are_deduplicated_and_padded() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, postal_codes) = references(dir.path());
+        assert_eq!(postal_codes.len(), 9);
+        assert_eq!(postal_codes.postal_codes_of("01015"), ["01300"]);
+        assert_eq!(postal_codes.postal_codes_of("01004"), ["01500"]);
+        assert_eq!(postal_codes.postal_codes_of("2A004"), ["20000", "20090"]);
+        assert!(postal_codes.postal_codes_of("01340").is_empty());
+        assert!(postal_codes.is_known("01500"));
+        assert!(!postal_codes.is_known("1500"));
+    }
+
+    #[test]
+    fn missing_columns_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cog_communes.csv");
+        std::fs::write(&path, "TYPECOM;COM;DEP\nCOM;01001;01\n").unwrap();
+        let err = Communes::load(&path).err().unwrap().to_string();
+        assert!(err.ends_with("missing column comparent"), "{err}");
+    }
+}
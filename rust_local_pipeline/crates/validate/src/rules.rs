@@ -0,0 +1,1180 @@
+//! Descriptor-driven validation: typed parsing, rules and derived columns.
+use anyhow::{anyhow, bail, Context, Result};
+use arrow::array::{
+    Array, ArrayRef, Date32Builder, Decimal128Builder, FixedSizeBinaryBuilder, Float64Builder,
+    Int16Builder, Int32Builder, Int64Builder, StringArray, StringBuilder,
+};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use blake3::Hasher;
+use metadata::{
+    Bound, ColumnSpec, ColumnType, DerivedSpec, Normalize, RuleAction, RuleCheck, RuleSpec,
+    TerritorySpec,
+};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::Arc;
+use time::format_description::{self, OwnedFormatItem};
+use time::{Date, Month};
+
+use crate::geo::{Departments, Territories};
+use crate::referential::{Communes, PostalCodes};
+
+/// One parsed cell. `Invalid` = present but not parseable as the declared type.
+#[derive(Debug, Clone)]
+pub(crate) enum Value<'a> {
+    Null,
+    Invalid,
+    Str(Cow<'a, str>),
+    Int(i64),
+    Float(f64),
+    Decimal(i128),
+    Date(Date),
+    Key([u8; 32]),
+}
+
+impl Value<'_> {
+    fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+}
+
+struct SourceCol {
+    bronze_idx: usize,
+    ty: ColumnType,
+    required: bool,
+    normalize: Option<Normalize>,
+    pad_left: Option<usize>,
+    date_fmt: Option<OwnedFormatItem>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Limit {
+    Num(f64),
+    Date(Date),
+}
+
+enum Check {
+    Required(Vec<usize>),
+    Type(usize),
+    Range {
+        col: usize,
+        min: Option<Limit>,
+        max: Option<Limit>,
+    },
+    Regex(usize, Regex),
+    Enum(usize, HashSet<String>),
+    Paired(Vec<usize>),
+    Territory {
+        lon: usize,
+        lat: usize,
+        dept: usize,
+        territories: Arc<Territories>,
+    },
+    InDepartment {
+        lon: usize,
+        lat: usize,
+        dept: usize,
+        departments: Arc<Departments>,
+        tolerance: f64,
+    },
+    CommuneKnown(usize, Arc<Communes>),
+    CommuneCurrent {
+        commune: usize,
+        old: Option<usize>,
+        communes: Arc<Communes>,
+    },
+    DepartmentKnown(usize, Arc<Communes>),
+    DepartmentCommune {
+        dept: usize,
+        commune: usize,
+        communes: Arc<Communes>,
+    },
+    PostalKnown(usize, Arc<PostalCodes>),
+    PostalCommune {
+        postal: usize,
+        commune: usize,
+        postal_codes: Arc<PostalCodes>,
+        communes: Option<Arc<Communes>>, // to accept the codes of merged communes
+    },
+}
+
+/// Reference data that rules check values against; rules whose reference is
+/// missing are skipped.
+#[derive(Default)]
+pub(crate) struct References {
+    pub(crate) departments: Option<Arc<Departments>>,
+    pub(crate) communes: Option<Arc<Communes>>,
+    pub(crate) postal_codes: Option<Arc<PostalCodes>>,
+}
+
+pub(crate) struct Rule {
+    check: Check,
+    action: RuleAction,
+    pub(crate) code: String,
+    pub(crate) detail: String,
+}
+
+enum Derived {
+    Year(usize),
+    MonthStart(usize),
+    Geohash {
+        lon: usize,
+        lat: usize,
+        precision: usize,
+    },
+    Ratio {
+        num: usize,
+        den: usize,
+        min_den: f64,
+    },
+    DedupKey,
+}
+
+/// Validation plan compiled from the descriptor against one Bronze schema.
+pub(crate) struct Plan {
+    cols: Vec<SourceCol>,
+    rules: Vec<Rule>,
+    derived: Vec<Derived>,
+    key_idx: Vec<usize>,
+    silver_schema: Arc<Schema>,
+}
+
+/// Bronze arrays of one batch, in plan order.
+pub(crate) struct BatchView<'b> {
+    cols: Vec<&'b StringArray>,
+    key_cols: Vec<&'b StringArray>,
+}
+
+impl Plan {
+    pub(crate) fn compile(
+        columns: &[ColumnSpec],
+        rules: &[RuleSpec],
+        derived: &[DerivedSpec],
+        dedup_key_columns: &[String],
+        territories: &[TerritorySpec],
+        refs: &References,
+        bronze: &Schema,
+    ) -> Result<Self> {
+        let bronze_idx = |name: &str| {
+            bronze
+                .index_of(name)
+                .with_context(|| format!("missing column in Bronze: {name}"))
+        };
+        let col_pos = |name: &str| {
+            columns
+                .iter()
+                .position(|c| c.name == name)
+                .ok_or_else(|| anyhow!("unknown column: {name}"))
+        };
+
+        let mut cols = Vec::with_capacity(columns.len());
+        let mut fields = Vec::with_capacity(columns.len() + derived.len());
+        for c in columns {
+            let idx = bronze_idx(&c.name)?;
+            if bronze.field(idx).data_type() != &DataType::Utf8 {
+                bail!("expected Utf8 for Bronze column {}", c.name);
+            }
+            let date_fmt = match c.ty {
+                ColumnType::Date => Some(
+                    format_description::parse_owned::<2>(
+                        c.date_format.as_deref().unwrap_or("[year]-[month]-[day]"),
+                    )
+                    .with_context(|| format!("date_format of {}", c.name))?,
+                ),
+                _ => None,
+            };
+            fields.push(Field::new(&c.name, arrow_type(c.ty), !c.required));
+            cols.push(SourceCol {
+                bronze_idx: idx,
+                ty: c.ty,
+                required: c.required,
+                normalize: c.normalize,
+                pad_left: c.pad_left,
+                date_fmt,
+            });
+        }
+
+        let territories = Arc::new(Territories::new(territories));
+        let mut compiled = Vec::with_capacity(rules.len());
+        for r in rules {
+            let skip = |what: &str| {
+                tracing::warn!(rule = %r.code, "no {what} reference: {} rule skipped", kind(&r.check));
+            };
+            let text = |name: &str| utf8_col(&r.code, &cols, columns, col_pos(name)?);
+            let check = match &r.check {
+                RuleCheck::Required { columns: names } if names.is_empty() => Check::Required(
+                    (0..cols.len()).filter(|&i| cols[i].required).collect(),
+                ),
+                RuleCheck::Required { columns: names } => Check::Required(
+                    names.iter().map(|n| col_pos(n)).collect::<Result<_>>()?,
+                ),
+                RuleCheck::Type { column } => Check::Type(col_pos(column)?),
+                RuleCheck::Range { column, min, max } => {
+                    let col = col_pos(column)?;
+                    let ty = cols[col].ty;
+                    Check::Range {
+                        col,
+                        min: min.as_ref().map(|b| limit(b, ty)).transpose()?,
+                        max: max.as_ref().map(|b| limit(b, ty)).transpose()?,
+                    }
+                }
+                RuleCheck::Regex { column, pattern } => {
+                    let col = text(column)?;
+                    let re = Regex::new(&format!("^(?:{pattern})$"))
+                        .with_context(|| format!("rule {}: invalid regex", r.code))?;
+                    Check::Regex(col, re)
+                }
+                RuleCheck::Enum { column, values } => {
+                    let col = text(column)?;
+                    Check::Enum(col, values.iter().cloned().collect())
+                }
+                RuleCheck::PairedNullability { columns: names } => Check::Paired(
+                    names.iter().map(|n| col_pos(n)).collect::<Result<_>>()?,
+                ),
+                RuleCheck::Territory { lon, lat, department } => Check::Territory {
+                    lon: col_pos(lon)?,
+                    lat: col_pos(lat)?,
+                    dept: text(department)?,
+                    territories: territories.clone(),
+                },
+                RuleCheck::InDepartment { lon, lat, department, tolerance } => {
+                    let Some(departments) = &refs.departments else {
+                        skip("departement");
+                        continue;
+                    };
+                    Check::InDepartment {
+                        lon: col_pos(lon)?,
+                        lat: col_pos(lat)?,
+                        dept: text(department)?,
+                        departments: departments.clone(),
+                        tolerance: *tolerance,
+                    }
+                }
+                check @ (RuleCheck::CommuneKnown { .. }
+                | RuleCheck::CommuneCurrent { .. }
+                | RuleCheck::DepartmentKnown { .. }
+                | RuleCheck::DepartmentCommune { .. }) => {
+                    let Some(communes) = &refs.communes else {
+                        skip("commune (COG)");
+                        continue;
+                    };
+                    let communes = communes.clone();
+                    match check {
+                        RuleCheck::CommuneKnown { commune } => Check::CommuneKnown(text(commune)?, communes),
+                        RuleCheck::CommuneCurrent { commune, old_commune } => Check::CommuneCurrent {
+                            commune: text(commune)?,
+                            old: old_commune.as_deref().map(text).transpose()?,
+                            communes,
+                        },
+                        RuleCheck::DepartmentKnown { department } => Check::DepartmentKnown(text(department)?, communes),
+                        RuleCheck::DepartmentCommune { department, commune } => Check::DepartmentCommune {
+                            dept: text(department)?,
+                            commune: text(commune)?,
+                            communes,
+                        },
+                        _ => unreachable!(),
+                    }
+                }
+                RuleCheck::PostalKnown { postal_code } => {
+                    let Some(postal_codes) = &refs.postal_codes else {
+                        skip("postal code");
+                        continue;
+                    };
+                    Check::PostalKnown(text(postal_code)?, postal_codes.clone())
+                }
+                RuleCheck::PostalCommune { postal_code, commune } => {
+                    let Some(postal_codes) = &refs.postal_codes else {
+                        skip("postal code");
+                        continue;
+                    };
+                    Check::PostalCommune {
+                        postal: text(postal_code)?,
+                        commune: text(commune)?,
+                        postal_codes: postal_codes.clone(),
+                        communes: refs.communes.clone(),
+                    }
+                }
+            };
+            compiled.push(Rule {
+                check,
+                action: r.action,
+                code: r.code.clone(),
+                detail: r.detail.clone().unwrap_or_else(|| r.code.clone()),
+            });
+        }
+
+        let mut derived_plan = Vec::with_capacity(derived.len());
+        for d in derived {
+            let (plan, ty, nullable) = match d {
+                DerivedSpec::Year { column, .. } => {
+                    let c = date_col(&cols, columns, col_pos(column)?)?;
+                    (Derived::Year(c), DataType::Int16, !cols[c].required)
+                }
+                DerivedSpec::MonthStart { column, .. } => {
+                    let c = date_col(&cols, columns, col_pos(column)?)?;
+                    (Derived::MonthStart(c), DataType::Date32, !cols[c].required)
+                }
+                DerivedSpec::Geohash { lon, lat, precision, .. } => (
+                    Derived::Geohash {
+                        lon: col_pos(lon)?,
+                        lat: col_pos(lat)?,
+                        precision: *precision,
+                    },
+                    DataType::Utf8,
+                    true,
+                ),
+                DerivedSpec::Ratio { numerator, denominator, min_denominator, .. } => (
+                    Derived::Ratio {
+                        num: col_pos(numerator)?,
+                        den: col_pos(denominator)?,
+                        min_den: *min_denominator,
+                    },
+                    DataType::Float64,
+                    true,
+                ),
+                DerivedSpec::DedupKey { .. } => {
+                    (Derived::DedupKey, DataType::FixedSizeBinary(32), false)
+                }
+            };
+            fields.push(Field::new(d.name(), ty, nullable));
+            derived_plan.push(plan);
+        }
+
+        // Dedup key: descriptor columns, or every source column
+        let key_idx = if dedup_key_columns.is_empty() {
+            cols.iter().map(|c| c.bronze_idx).collect()
+        } else {
+            dedup_key_columns
+                .iter()
+                .map(|n| bronze_idx(n))
+                .collect::<Result<_>>()?
+        };
+
+        Ok(Self {
+            cols,
+            rules: compiled,
+            derived: derived_plan,
+            key_idx,
+            silver_schema: Arc::new(Schema::new(fields)),
+        })
+    }
+
+    pub(crate) fn silver_schema(&self) -> Arc<Schema> {
+        self.silver_schema.clone()
+    }
+
+    /// Number of Silver columns (source + derived).
+    pub(crate) fn width(&self) -> usize {
+        self.cols.len() + self.derived.len()
+    }
+
+    pub(crate) fn view<'b>(&self, batch: &'b RecordBatch) -> Result<BatchView<'b>> {
+        let utf8 = |i: usize| {
+            batch
+                .column(i)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .ok_or_else(|| anyhow!("expected Utf8 for Bronze column {}", batch.schema().field(i).name()))
+        };
+        Ok(BatchView {
+            cols: self.cols.iter().map(|c| utf8(c.bronze_idx)).collect::<Result<_>>()?,
+            key_cols: self.key_idx.iter().map(|&i| utf8(i)).collect::<Result<_>>()?,
+        })
+    }
+
+    /// Rule codes, in rule order (the indexes `eval_row` puts in `fixed`).
+    pub(crate) fn rule_codes(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|r| r.code.as_str())
+    }
+
+    /// Parses the row into `vals` and applies the rules. Returns the failing rule, if any.
+    /// `fixed` receives the indexes of the rules that nullified or repaired a value of the row.
+    pub(crate) fn eval_row<'b>(
+        &self,
+        view: &BatchView<'b>,
+        row: usize,
+        vals: &mut Vec<Value<'b>>,
+        fixed: &mut Vec<usize>,
+    ) -> Option<&Rule> {
+        vals.clear();
+        fixed.clear();
+        for (c, arr) in self.cols.iter().zip(&view.cols) {
+            vals.push(parse_cell(c, arr, row));
+        }
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            let failed: Vec<usize> = match &rule.check {
+                Check::Required(cols) => {
+                    // blank counts as missing: Silver declares these non-nullable
+                    if cols.iter().any(|&c| vals[c].is_null()) {
+                        return Some(rule);
+                    }
+                    continue;
+                }
+                Check::Type(c) => fail_if(matches!(vals[*c], Value::Invalid), *c),
+                Check::Range { col, min, max } => {
+                    let out = match (as_limit(&vals[*col], self.cols[*col].ty), min, max) {
+                        (Some(v), lo, hi) => {
+                            lo.is_some_and(|lo| lt(v, lo)) || hi.is_some_and(|hi| lt(hi, v))
+                        }
+                        (None, _, _) => false,
+                    };
+                    fail_if(out, *col)
+                }
+                Check::Regex(c, re) => match &vals[*c] {
+                    Value::Str(s) => fail_if(!re.is_match(s), *c),
+                    _ => Vec::new(),
+                },
+                Check::Enum(c, allowed) => match &vals[*c] {
+                    Value::Str(s) => fail_if(!allowed.contains(s.as_ref()), *c),
+                    _ => Vec::new(),
+                },
+                Check::Paired(cols) => {
+                    let present = cols
+                        .iter()
+                        .filter(|&&c| !matches!(vals[c], Value::Null | Value::Invalid))
+                        .count();
+                    if present != 0 && present != cols.len() {
+                        cols.clone()
+                    } else {
+                        Vec::new()
+                    }
+                }
+                Check::Territory { lon, lat, dept, territories } => {
+                    match (as_f64(&vals[*lon], self.cols[*lon].ty), as_f64(&vals[*lat], self.cols[*lat].ty)) {
+                        (Some(x), Some(y)) => {
+                            let d = match &vals[*dept] {
+                                Value::Str(s) => Some(s.as_ref()),
+                                _ => None,
+                            };
+                            fail_if_any(!territories.contains(d, x, y), &[*lon, *lat])
+                        }
+                        _ => Vec::new(),
+                    }
+                }
+                Check::InDepartment { lon, lat, dept, departments, tolerance } => {
+                    match (
+                        as_f64(&vals[*lon], self.cols[*lon].ty),
+                        as_f64(&vals[*lat], self.cols[*lat].ty),
+                        &vals[*dept],
+                    ) {
+                        // departments missing from the reference are not checked
+                        (Some(x), Some(y), Value::Str(d)) => {
+                            let outside = departments.contains(d, x, y, *tolerance) == Some(false);
+                            fail_if_any(outside, &[*lon, *lat])
+                        }
+                        _ => Vec::new(),
+                    }
+                }
+                Check::CommuneKnown(c, communes) => {
+                    fail_if(str_val(&vals[*c]).is_some_and(|v| !communes.is_known(v)), *c)
+                }
+                Check::CommuneCurrent { commune, communes, .. } => {
+                    let merged = str_val(&vals[*commune]).is_some_and(|v| communes.merged_into(v).is_some());
+                    fail_if(merged, *commune)
+                }
+                Check::DepartmentKnown(d, communes) => {
+                    fail_if(str_val(&vals[*d]).is_some_and(|v| !communes.is_department(v)), *d)
+                }
+                Check::DepartmentCommune { dept, commune, communes } => {
+                    // unknown communes are left to `commune_known`
+                    let expected = str_val(&vals[*commune]).and_then(|c| communes.department_of(c));
+                    let actual = str_val(&vals[*dept]);
+                    fail_if(expected.is_some() && actual.is_some() && expected != actual, *dept)
+                }
+                Check::PostalKnown(p, postal_codes) => {
+                    fail_if(str_val(&vals[*p]).is_some_and(|v| !postal_codes.is_known(v)), *p)
+                }
+                Check::PostalCommune { postal, commune, postal_codes, communes } => {
+                    match (str_val(&vals[*postal]), str_val(&vals[*commune])) {
+                        // unknown postal codes are left to `postal_known`, communes absent from the table skipped
+                        (Some(p), Some(c)) if postal_codes.is_known(p) => {
+                            let served = postal_codes_of(postal_codes, communes.as_deref(), c);
+                            fail_if(!served.is_empty() && !served.contains(&p), *postal)
+                        }
+                        _ => Vec::new(),
+                    }
+                }
+            };
+            if failed.is_empty() {
+                continue;
+            }
+            match rule.action {
+                RuleAction::Reject => return Some(rule),
+                RuleAction::Nullify => {
+                    for c in failed {
+                        vals[c] = Value::Null;
+                    }
+                }
+                RuleAction::Repair => {
+                    let repairs = repair(&rule.check, vals);
+                    if repairs.is_empty() {
+                        // the reference does not tell which value is right
+                        for c in failed {
+                            vals[c] = Value::Null;
+                        }
+                    }
+                    for (c, v) in repairs {
+                        vals[c] = Value::Str(Cow::Owned(v));
+                    }
+                }
+            }
+            fixed.push(i);
+        }
+
+        // Unparseable values not covered by a `type` rule become null
+        for v in vals.iter_mut() {
+            if matches!(v, Value::Invalid) {
+                *v = Value::Null;
+            }
+        }
+        None
+    }
+
+    /// BLAKE3 over the trimmed dedup key columns, '|'-separated.
+    pub(crate) fn mutation_key(&self, view: &BatchView<'_>, row: usize) -> [u8; 32] {
+        let mut hasher = Hasher::new();
+        for (k, arr) in view.key_cols.iter().enumerate() {
+            if k > 0 {
+                hasher.update(b"|");
+            }
+            if !arr.is_null(row) {
+                hasher.update(arr.value(row).trim().as_bytes());
+            }
+        }
+        *hasher.finalize().as_bytes()
+    }
+
+    /// Appends the derived columns to a row that passed `eval_row`.
+    pub(crate) fn derive(&self, vals: &mut Vec<Value<'_>>, key: [u8; 32]) {
+        let n = self.cols.len();
+        for d in &self.derived {
+            let v = match d {
+                Derived::Year(c) => match vals[*c] {
+                    Value::Date(dt) => Value::Int(dt.year() as i64),
+                    _ => Value::Null,
+                },
+                Derived::MonthStart(c) => match vals[*c] {
+                    Value::Date(dt) => Date::from_calendar_date(dt.year(), dt.month(), 1)
+                        .map(Value::Date)
+                        .unwrap_or(Value::Null),
+                    _ => Value::Null,
+                },
+                Derived::Geohash { lon, lat, precision } => {
+                    match (as_f64(&vals[*lon], self.cols[*lon].ty), as_f64(&vals[*lat], self.cols[*lat].ty)) {
+                        (Some(x), Some(y)) => geohash::encode(geohash::Coord { x, y }, *precision)
+                            .map(|s| Value::Str(Cow::Owned(s)))
+                            .unwrap_or(Value::Null),
+                        _ => Value::Null,
+                    }
+                }
+                Derived::Ratio { num, den, min_den } => {
+                    match (as_f64(&vals[*num], self.cols[*num].ty), as_f64(&vals[*den], self.cols[*den].ty)) {
+                        (Some(a), Some(b)) if b > *min_den => {
+                            let r = a / b;
+                            if r.is_finite() && r > 0.0 {
+                                Value::Float(r)
+                            } else {
+                                Value::Null
+                            }
+                        }
+                        _ => Value::Null,
+                    }
+                }
+                Derived::DedupKey => Value::Key(key),
+            };
+            vals.push(v);
+        }
+        debug_assert_eq!(vals.len(), n + self.derived.len());
+    }
+}
+
+// ---------- parsing
+
+fn parse_cell<'b>(c: &SourceCol, arr: &'b StringArray, row: usize) -> Value<'b> {
+    if arr.is_null(row) {
+        return Value::Null;
+    }
+    let s = arr.value(row).trim();
+    if s.is_empty() {
+        return Value::Null;
+    }
+    match c.ty {
+        ColumnType::Utf8 => {
+            let mut out = match c.normalize {
+                Some(Normalize::Upper) => Cow::Owned(s.to_uppercase()),
+                Some(Normalize::Lower) => Cow::Owned(s.to_lowercase()),
+                None => Cow::Borrowed(s),
+            };
+            if let Some(w) = c.pad_left
+                && out.chars().count() < w
+            {
+                out = Cow::Owned(left_pad(&out, w));
+            }
+            Value::Str(out)
+        }
+        ColumnType::Int16 => parse_int(s, i16::MIN as i64, i16::MAX as i64),
+        ColumnType::Int32 => parse_int(s, i32::MIN as i64, i32::MAX as i64),
+        ColumnType::Int64 => parse_int(s, i64::MIN, i64::MAX),
+        ColumnType::Float64 => parse_f64(s).map(Value::Float).unwrap_or(Value::Invalid),
+        ColumnType::Decimal { precision, scale } => parse_decimal(s, precision, scale)
+            .map(Value::Decimal)
+            .unwrap_or(Value::Invalid),
+        ColumnType::Date => match c.date_fmt.as_ref().map(|f| Date::parse(s, f)) {
+            Some(Ok(d)) => Value::Date(d),
+            _ => Value::Invalid,
+        },
+    }
+}
+
+fn parse_int(s: &str, min: i64, max: i64) -> Value<'static> {
+    match s.parse::<i64>() {
+        Ok(v) if (min..=max).contains(&v) => Value::Int(v),
+        _ => Value::Invalid,
+    }
+}
+
+pub(crate) fn parse_f64(s: &str) -> Option<f64> {
+    let t = s.trim().replace(',', ".");
+    t.parse::<f64>().ok()
+}
+
+/// Unscaled value of `s` (`-1234,5`, `.75`, ...) at `scale`, rounded half away from zero,
+/// read digit by digit so no precision is lost. `None` when `s` is not a plain decimal or the
+/// value needs more than `precision` digits.
+fn parse_decimal(s: &str, precision: u8, scale: i8) -> Option<i128> {
+    let (neg, t) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let (int, frac) = t.split_once(['.', ',']).unwrap_or((t, ""));
+    let digits = |d: &str| d.bytes().all(|b| b.is_ascii_digit());
+    if (int.is_empty() && frac.is_empty()) || !digits(int) || !digits(frac) {
+        return None;
+    }
+    let accumulate = |v: i128, d: u8| v.checked_mul(10)?.checked_add(i128::from(d - b'0'));
+    let pow10 = |n: u32| 10i128.checked_pow(n);
+
+    let scale = i32::from(scale);
+    let mut v = int.bytes().try_fold(0i128, accumulate)?;
+    let kept = frac.len().min(scale.max(0) as usize);
+    v = frac.as_bytes()[..kept].iter().try_fold(v, |v, &d| accumulate(v, d))?;
+    let next = frac.as_bytes().get(kept).copied();
+    if scale >= 0 {
+        v = v.checked_mul(pow10((scale as usize - kept) as u32)?)?;
+        if next.is_some_and(|d| d >= b'5') {
+            v = v.checked_add(1)?;
+        }
+    } else {
+        // negative scale: whole multiples of 10^-scale; the fraction can't tip the rounding
+        let unit = pow10(scale.unsigned_abs())?;
+        let (q, r) = (v / unit, v % unit);
+        v = if r >= unit - r { q + 1 } else { q };
+    }
+    if pow10(u32::from(precision)).is_some_and(|max| v >= max) {
+        return None;
+    }
+    Some(if neg { -v } else { v })
+}
+
+fn left_pad(s: &str, width: usize) -> String {
+    let mut t = s.to_string();
+    while t.chars().count() < width {
+        t.insert(0, '0');
+    }
+    t
+}
+
+fn parse_iso_date(s: &str) -> Result<Date> {
+    let fmt = format_description::parse("[year]-[month]-[day]")?;
+    Ok(Date::parse(s.trim(), &fmt)?)
+}
+
+// ---------- comparisons
+
+fn limit(b: &Bound, ty: ColumnType) -> Result<Limit> {
+    match (ty, b) {
+        (ColumnType::Date, Bound::Text(s)) => Ok(Limit::Date(
+            parse_iso_date(s).with_context(|| format!("date bound {s}"))?,
+        )),
+        (ColumnType::Utf8 | ColumnType::Date, _) => bail!("range bound {b:?} does not fit type {ty}"),
+        (_, Bound::Number(n)) => Ok(Limit::Num(*n)),
+        (_, Bound::Text(s)) => bail!("numeric range bound expected, got {s:?}"),
+    }
+}
+
+fn as_limit(v: &Value<'_>, ty: ColumnType) -> Option<Limit> {
+    match v {
+        Value::Date(d) => Some(Limit::Date(*d)),
+        _ => as_f64(v, ty).map(Limit::Num),
+    }
+}
+
+fn as_f64(v: &Value<'_>, ty: ColumnType) -> Option<f64> {
+    match (v, ty) {
+        (Value::Int(i), _) => Some(*i as f64),
+        (Value::Float(f), _) => Some(*f),
+        (Value::Decimal(d), ColumnType::Decimal { scale, .. }) => {
+            Some(*d as f64 / 10f64.powi(scale as i32))
+        }
+        _ => None,
+    }
+}
+
+fn lt(a: Limit, b: Limit) -> bool {
+    match (a, b) {
+        (Limit::Num(x), Limit::Num(y)) => x < y,
+        (Limit::Date(x), Limit::Date(y)) => x < y,
+        _ => false,
+    }
+}
+
+fn fail_if(cond: bool, col: usize) -> Vec<usize> {
+    if cond { vec![col] } else { Vec::new() }
+}
+
+/// Values the reference gives for a failed referential check, as `(column, value)`.
+fn repair(check: &Check, vals: &[Value<'_>]) -> Vec<(usize, String)> {
+    match check {
+        Check::CommuneCurrent { commune, old, communes } => {
+            let Some(c) = str_val(&vals[*commune]) else { return Vec::new() };
+            let Some(current) = communes.merged_into(c) else { return Vec::new() };
+            let mut out = vec![(*commune, current.to_string())];
+            if let Some(o) = old
+                && vals[*o].is_null()
+            {
+                out.push((*o, c.to_string()));
+            }
+            out
+        }
+        Check::DepartmentCommune { dept, commune, communes } => str_val(&vals[*commune])
+            .and_then(|c| communes.department_of(c))
+            .map(|d| vec![(*dept, d.to_string())])
+            .unwrap_or_default(),
+        Check::PostalCommune { postal, commune, postal_codes, communes } => {
+            let Some(c) = str_val(&vals[*commune]) else { return Vec::new() };
+            match postal_codes_of(postal_codes, communes.as_deref(), c).as_slice() {
+                [only] => vec![(*postal, only.to_string())],
+                _ => Vec::new(),
+            }
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Postal codes serving commune `c`, or the commune nouvelle / former communes it is part of.
+fn postal_codes_of<'a>(postal_codes: &'a PostalCodes, communes: Option<&'a Communes>, c: &'a str) -> Vec<&'a str> {
+    let related = communes.map(|m| m.related(c)).unwrap_or_else(|| vec![c]);
+    let mut out: Vec<&str> = Vec::new();
+    for r in related {
+        for p in postal_codes.postal_codes_of(r) {
+            if !out.contains(&p.as_str()) {
+                out.push(p);
+            }
+        }
+    }
+    out
+}
+
+fn str_val<'v>(v: &'v Value<'_>) -> Option<&'v str> {
+    match v {
+        Value::Str(s) => Some(s),
+        _ => None,
+    }
+}
+
+/// `rules` with the date bounds of `range` rules resolved against `ingest_date` and the
+/// reference clock's `today`, as `YYYY-MM-DD`.
+pub(crate) fn pin_date_bounds(
+    columns: &[ColumnSpec],
+    rules: &[RuleSpec],
+    ingest_date: Date,
+    today: Date,
+) -> Result<Vec<RuleSpec>> {
+    let mut out = rules.to_vec();
+    for r in &mut out {
+        let RuleCheck::Range { column, min, max } = &mut r.check else { continue };
+        if !columns.iter().any(|c| c.name == *column && c.ty == ColumnType::Date) {
+            continue;
+        }
+        for b in [min, max].into_iter().flatten() {
+            let date = b
+                .resolve_date(ingest_date, today)
+                .with_context(|| format!("rule {}", r.code))?;
+            if let Bound::Text(t) = b
+                && *t != date.to_string()
+            {
+                tracing::info!(rule = %r.code, bound = %t, date = %date, "resolved date bound");
+                *t = date.to_string();
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// The `kind` of a rule, as written in the descriptor.
+fn kind(check: &RuleCheck) -> &'static str {
+    match check {
+        RuleCheck::Required { .. } => "required",
+        RuleCheck::Type { .. } => "type",
+        RuleCheck::Range { .. } => "range",
+        RuleCheck::Regex { .. } => "regex",
+        RuleCheck::Enum { .. } => "enum",
+        RuleCheck::PairedNullability { .. } => "paired_nullability",
+        RuleCheck::Territory { .. } => "territory",
+        RuleCheck::InDepartment { .. } => "in_department",
+        RuleCheck::CommuneKnown { .. } => "commune_known",
+        RuleCheck::CommuneCurrent { .. } => "commune_current",
+        RuleCheck::DepartmentKnown { .. } => "department_known",
+        RuleCheck::DepartmentCommune { .. } => "department_commune",
+        RuleCheck::PostalKnown { .. } => "postal_known",
+        RuleCheck::PostalCommune { .. } => "postal_commune",
+    }
+}
+
+fn fail_if_any(cond: bool, cols: &[usize]) -> Vec<usize> {
+    if cond { cols.to_vec() } else { Vec::new() }
+}
+
+fn utf8_col(code: &str, cols: &[SourceCol], specs: &[ColumnSpec], c: usize) -> Result<usize> {
+    if cols[c].ty != ColumnType::Utf8 {
+        bail!("rule {code}: {} must be a utf8 column, is {}", specs[c].name, cols[c].ty);
+    }
+    Ok(c)
+}
+
+fn date_col(cols: &[SourceCol], specs: &[ColumnSpec], c: usize) -> Result<usize> {
+    if cols[c].ty != ColumnType::Date {
+        bail!("{} must be a date column", specs[c].name);
+    }
+    Ok(c)
+}
+
+// ---------- Silver builders
+
+pub(crate) fn arrow_type(ty: ColumnType) -> DataType {
+    match ty {
+        ColumnType::Utf8 => DataType::Utf8,
+        ColumnType::Int16 => DataType::Int16,
+        ColumnType::Int32 => DataType::Int32,
+        ColumnType::Int64 => DataType::Int64,
+        ColumnType::Float64 => DataType::Float64,
+        ColumnType::Date => DataType::Date32,
+        ColumnType::Decimal { precision, scale } => DataType::Decimal128(precision, scale),
+    }
+}
+
+enum ColBuilder {
+    Utf8(StringBuilder),
+    Int16(Int16Builder),
+    Int32(Int32Builder),
+    Int64(Int64Builder),
+    Float64(Float64Builder),
+    Decimal(Decimal128Builder),
+    Date(Date32Builder),
+    Key(FixedSizeBinaryBuilder),
+}
+
+impl ColBuilder {
+    fn new(ty: &DataType, cap: usize) -> Result<Self> {
+        Ok(match ty {
+            DataType::Utf8 => ColBuilder::Utf8(StringBuilder::with_capacity(cap, cap * 8)),
+            DataType::Int16 => ColBuilder::Int16(Int16Builder::with_capacity(cap)),
+            DataType::Int32 => ColBuilder::Int32(Int32Builder::with_capacity(cap)),
+            DataType::Int64 => ColBuilder::Int64(Int64Builder::with_capacity(cap)),
+            DataType::Float64 => ColBuilder::Float64(Float64Builder::with_capacity(cap)),
+            DataType::Decimal128(p, s) => ColBuilder::Decimal(
+                Decimal128Builder::with_capacity(cap).with_precision_and_scale(*p, *s)?,
+            ),
+            DataType::Date32 => ColBuilder::Date(Date32Builder::with_capacity(cap)),
+            DataType::FixedSizeBinary(32) => {
+                ColBuilder::Key(FixedSizeBinaryBuilder::with_capacity(cap, 32))
+            }
+            other => bail!("unsupported Silver type {other}"),
+        })
+    }
+
+    fn append(&mut self, v: &Value<'_>) -> Result<()> {
+        match (self, v) {
+            (ColBuilder::Utf8(b), Value::Str(s)) => b.append_value(s),
+            (ColBuilder::Int16(b), Value::Int(i)) => b.append_value(*i as i16),
+            (ColBuilder::Int32(b), Value::Int(i)) => b.append_value(*i as i32),
+            (ColBuilder::Int64(b), Value::Int(i)) => b.append_value(*i),
+            (ColBuilder::Float64(b), Value::Float(f)) => b.append_value(*f),
+            (ColBuilder::Decimal(b), Value::Decimal(d)) => b.append_value(*d),
+            (ColBuilder::Date(b), Value::Date(d)) => b.append_value(date32_from_date(*d)),
+            (ColBuilder::Key(b), Value::Key(k)) => b.append_value(k)?,
+            (b, _) => b.append_null(),
+        }
+        Ok(())
+    }
+
+    fn append_null(&mut self) {
+        match self {
+            ColBuilder::Utf8(b) => b.append_null(),
+            ColBuilder::Int16(b) => b.append_null(),
+            ColBuilder::Int32(b) => b.append_null(),
+            ColBuilder::Int64(b) => b.append_null(),
+            ColBuilder::Float64(b) => b.append_null(),
+            ColBuilder::Decimal(b) => b.append_null(),
+            ColBuilder::Date(b) => b.append_null(),
+            ColBuilder::Key(b) => b.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColBuilder::Utf8(b) => Arc::new(b.finish()),
+            ColBuilder::Int16(b) => Arc::new(b.finish()),
+            ColBuilder::Int32(b) => Arc::new(b.finish()),
+            ColBuilder::Int64(b) => Arc::new(b.finish()),
+            ColBuilder::Float64(b) => Arc::new(b.finish()),
+            ColBuilder::Decimal(b) => Arc::new(b.finish()),
+            ColBuilder::Date(b) => Arc::new(b.finish()),
+            ColBuilder::Key(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+pub(crate) struct SilverBuilders {
+    schema: Arc<Schema>,
+    cols: Vec<ColBuilder>,
+}
+
+impl SilverBuilders {
+    pub(crate) fn try_new(schema: Arc<Schema>, cap: usize) -> Result<Self> {
+        let cols = schema
+            .fields()
+            .iter()
+            .map(|f| ColBuilder::new(f.data_type(), cap))
+            .collect::<Result<_>>()?;
+        Ok(Self { schema, cols })
+    }
+
+    pub(crate) fn append_row(&mut self, vals: &[Value<'_>]) -> Result<()> {
+        for (b, v) in self.cols.iter_mut().zip(vals) {
+            if v.is_null() {
+                b.append_null();
+            } else {
+                b.append(v)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish_batch(&mut self) -> Result<RecordBatch> {
+        let cols: Vec<ArrayRef> = self.cols.iter_mut().map(ColBuilder::finish).collect();
+        Ok(RecordBatch::try_new(self.schema.clone(), cols)?)
+    }
+}
+
+pub(crate) fn date32_from_date(d: Date) -> i32 {
+    let epoch = Date::from_calendar_date(1970, Month::January, 1).unwrap();
+    (d - epoch).whole_days() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal_keeps_every_digit() {
+        assert_eq!(parse_decimal("1234567890123456.78", 18, 2), Some(123456789012345678));
+        assert_eq!(parse_decimal("0,1", 12, 2), Some(10));
+        assert_eq!(parse_decimal(".75", 12, 2), Some(75));
+        assert_eq!(parse_decimal("-12", 12, 2), Some(-1200));
+        assert_eq!(parse_decimal("+3.", 12, 2), Some(300));
+    }
+
+    #[test]
+    fn decimal_rounds_half_away_from_zero() {
+        assert_eq!(parse_decimal("1.005", 12, 2), Some(101));
+        assert_eq!(parse_decimal("1.0049", 12, 2), Some(100));
+        assert_eq!(parse_decimal("-1.005", 12, 2), Some(-101));
+        assert_eq!(parse_decimal("1250", 12, -2), Some(13));
+        assert_eq!(parse_decimal("1249.9", 12, -2), Some(12));
+    }
+
+    #[test]
+    fn decimal_rejects_values_beyond_precision() {
+        assert_eq!(parse_decimal("9999999999.99", 12, 2), Some(999999999999));
+        assert_eq!(parse_decimal("10000000000", 12, 2), None);
+        assert_eq!(parse_decimal("9999999999.995", 12, 2), None); // rounds up to 13 digits
+        assert_eq!(parse_decimal(&"9".repeat(60), 38, 0), None);
+    }
+
+    #[test]
+    fn decimal_rejects_malformed_values() {
+        for s in ["", "-", ".", "1.2.3", "1e3", "12a", "1 000", "--1", "١٢"] {
+            assert_eq!(parse_decimal(s, 12, 2), None, "{s:?}");
+        }
+    }
+
+    /// Commune, department and postal code repairs against the `referential` fixture, then
+    /// a `postal_known` reject.
+    fn referential_plan(dir: &std::path::Path) -> Plan {
+        let (communes, postal_codes) = crate::referential::tests::references(dir);
+        let refs = References {
+            departments: None,
+            communes: Some(Arc::new(communes)),
+            postal_codes: Some(Arc::new(postal_codes)),
+        };
+        let columns: Vec<ColumnSpec> = serde_json::from_str(
+            r#"[
+                {"name": "code_postal", "pad_left": 5},
+                {"name": "code_commune"},
+                {"name": "code_departement"},
+                {"name": "ancien_code_commune"}
+            ]"#,
+        )
+        .unwrap();
+        let rules: Vec<RuleSpec> = serde_json::from_str(
+            r#"[
+                {"code": "COMMUNE_MERGED", "kind": "commune_current", "action": "repair",
+                 "commune": "code_commune", "old_commune": "ancien_code_commune"},
+                {"code": "DEPT_MISMATCH", "kind": "department_commune", "action": "repair",
+                 "department": "code_departement", "commune": "code_commune"},
+                {"code": "POSTAL_MISMATCH", "kind": "postal_commune", "action": "repair",
+                 "postal_code": "code_postal", "commune": "code_commune"},
+                {"code": "POSTAL_UNKNOWN", "kind": "postal_known", "postal_code": "code_postal"}
+            ]"#,
+        )
+        .unwrap();
+        let names = columns.iter().map(|c| Field::new(&c.name, DataType::Utf8, true)).collect::<Vec<_>>();
+        Plan::compile(&columns, &rules, &[], &[], &[], &refs, &Schema::new(names)).unwrap()
+    }
+
+    /// Evaluates `row` (postal code, commune, department, old commune): the codes of the rules
+    /// that fixed it and its values after repair, or the code of the rule that rejected it.
+    fn eval(plan: &Plan, row: [Option<&str>; 4]) -> Result<(Vec<String>, [Option<String>; 4]), String> {
+        let cols: Vec<ArrayRef> = row.iter().map(|v| Arc::new(StringArray::from(vec![*v])) as ArrayRef).collect();
+        let batch = RecordBatch::try_new(plan.silver_schema(), cols).unwrap();
+        let view = plan.view(&batch).unwrap();
+        let (mut vals, mut fixed) = (Vec::new(), Vec::new());
+        if let Some(rule) = plan.eval_row(&view, 0, &mut vals, &mut fixed) {
+            return Err(rule.code.clone());
+        }
+        let codes: Vec<&str> = plan.rule_codes().collect();
+        let fixed = fixed.iter().map(|&i| codes[i].to_string()).collect();
+        Ok((fixed, std::array::from_fn(|c| str_val(&vals[c]).map(str::to_string))))
+    }
+
+    fn fixed(codes: &[&str], row: [Option<&str>; 4]) -> Result<(Vec<String>, [Option<String>; 4]), String> {
+        Ok((codes.iter().map(|c| c.to_string()).collect(), row.map(|v| v.map(str::to_string))))
+    }
+
+    #[test]
+    fn merged_communes_are_repaired_to_their_commune_nouvelle() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan = referential_plan(dir.path());
+        // the former code moves to the old commune column when it is empty
+        assert_eq!(
+            eval(&plan, [Some("01300"), Some("01340"), Some("01"), None]),
+            fixed(&["COMMUNE_MERGED"], [Some("01300"), Some("01015"), Some("01"), Some("01340")])
+        );
+        // a COMA listed without a department: the commune nouvelle's, an old commune kept
+        assert_eq!(
+            eval(&plan, [Some("14940"), Some("14712"), Some("14"), Some("14999")]),
+            fixed(&["COMMUNE_MERGED"], [Some("14940"), Some("14666"), Some("14"), Some("14999")])
+        );
+        // a commune nouvelle sharing its code with a commune déléguée is current
+        assert_eq!(
+            eval(&plan, [Some("01300"), Some("01015"), Some("01"), None]),
+            fixed(&[], [Some("01300"), Some("01015"), Some("01"), None])
+        );
+    }
+
+    #[test]
+    fn departments_are_repaired_from_the_commune() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan = referential_plan(dir.path());
+        // arrondissements inherit the department of their commune
+        assert_eq!(
+            eval(&plan, [Some("75002"), Some("75102"), Some("92"), None]),
+            fixed(&["DEPT_MISMATCH"], [Some("75002"), Some("75102"), Some("75"), None])
+        );
+        // checked against the repaired commune
+        assert_eq!(
+            eval(&plan, [Some("14940"), Some("14712"), Some("61"), None]),
+            fixed(
+                &["COMMUNE_MERGED", "DEPT_MISMATCH"],
+                [Some("14940"), Some("14666"), Some("14"), Some("14712")]
+            )
+        );
+        assert_eq!(
+            eval(&plan, [Some("20000"), Some("2A004"), Some("2B"), None]),
+            fixed(&["DEPT_MISMATCH"], [Some("20000"), Some("2A004"), Some("2A"), None])
+        );
+        // unknown communes are left alone
+        assert_eq!(
+            eval(&plan, [Some("01400"), Some("99999"), Some("92"), None]),
+            fixed(&[], [Some("01400"), Some("99999"), Some("92"), None])
+        );
+    }
+
+    #[test]
+    fn postal_codes_are_repaired_when_the_reference_gives_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan = referential_plan(dir.path());
+        assert_eq!(
+            eval(&plan, [Some("01500"), Some("01001"), Some("01"), None]),
+            fixed(&["POSTAL_MISMATCH"], [Some("01400"), Some("01001"), Some("01"), None])
+        );
+        // padded before the check
+        assert_eq!(
+            eval(&plan, [Some("1500"), Some("01004"), Some("01"), None]),
+            fixed(&[], [Some("01500"), Some("01004"), Some("01"), None])
+        );
+        // Lyon arrondissements are absent from the table: the commune's postal code
+        assert_eq!(
+            eval(&plan, [Some("75001"), Some("69381"), Some("69"), None]),
+            fixed(&["POSTAL_MISMATCH"], [Some("69001"), Some("69381"), Some("69"), None])
+        );
+        // Paris is served through its arrondissements
+        assert_eq!(
+            eval(&plan, [Some("75002"), Some("75056"), Some("75"), None]),
+            fixed(&[], [Some("75002"), Some("75056"), Some("75"), None])
+        );
+    }
+
+    #[test]
+    fn ambiguous_postal_codes_are_nullified() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan = referential_plan(dir.path());
+        assert_eq!(
+            eval(&plan, [Some("01400"), Some("2A004"), Some("2A"), None]),
+            fixed(&["POSTAL_MISMATCH"], [None, Some("2A004"), Some("2A"), None])
+        );
+        assert_eq!(
+            eval(&plan, [Some("01400"), Some("75056"), Some("75"), None]),
+            fixed(&["POSTAL_MISMATCH"], [None, Some("75056"), Some("75"), None])
+        );
+    }
+
+    #[test]
+    fn unknown_postal_codes_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan = referential_plan(dir.path());
+        assert_eq!(
+            eval(&plan, [Some("99999"), Some("01001"), Some("01"), None]),
+            Err("POSTAL_UNKNOWN".to_string())
+        );
+    }
+
+    #[test]
+    fn text_rules_name_the_rule_and_the_column() {
+        let columns: Vec<ColumnSpec> = serde_json::from_str(r#"[{"name": "nombre_lots", "type": "int32"}]"#).unwrap();
+        let rules: Vec<RuleSpec> = serde_json::from_str(
+            r#"[{"code": "LOTS_FORMAT", "kind": "regex", "column": "nombre_lots", "pattern": "[0-9]+"}]"#,
+        )
+        .unwrap();
+        let bronze = Schema::new(vec![Field::new("nombre_lots", DataType::Utf8, true)]);
+        let err = Plan::compile(&columns, &rules, &[], &[], &[], &References::default(), &bronze)
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "rule LOTS_FORMAT: nombre_lots must be a utf8 column, is int32");
+    }
+}
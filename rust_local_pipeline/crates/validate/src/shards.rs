@@ -0,0 +1,188 @@
+//! Exact in-ingest dedup, sharded by the first byte of `mutation_key`. A key always lands
+//! in the same shard, so the shards are checked in parallel and each sees its keys in
+//! Bronze order: the first occurrence is kept, as with a single set.
+use rayon::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const SHARDS: usize = 256;
+
+/// A Bronze row: its `source_file` and `row_number` (numbered per source file).
+pub(crate) type RowRef = (Option<Arc<str>>, Option<i64>);
+
+/// The Silver candidates of one Bronze slice, in row order.
+pub(crate) struct SliceKeys<'s> {
+    pub(crate) keys: &'s [[u8; 32]],
+    pub(crate) rows: &'s [RowRef],
+}
+
+pub(crate) struct SeenKeys {
+    shards: Vec<HashMap<[u8; 32], RowRef>>, // key -> the kept row
+}
+
+impl SeenKeys {
+    pub(crate) fn new() -> Self {
+        Self { shards: vec![HashMap::new(); SHARDS] }
+    }
+
+    /// Records the keys of `slices` (consecutive, in Bronze order) and returns, per slice,
+    /// the candidates repeating a key kept earlier in this ingest, with that row.
+    /// Runs on the current rayon pool.
+    pub(crate) fn insert(&mut self, slices: &[SliceKeys<'_>]) -> Vec<Vec<(usize, RowRef)>> {
+        let mut by_shard: Vec<Vec<(u32, u32)>> = vec![Vec::new(); SHARDS];
+        for (s, slice) in slices.iter().enumerate() {
+            for (i, key) in slice.keys.iter().enumerate() {
+                by_shard[key[0] as usize].push((s as u32, i as u32));
+            }
+        }
+        let found: Vec<Vec<(u32, u32, RowRef)>> = self
+            .shards
+            .par_iter_mut()
+            .zip(by_shard)
+            .map(|(seen, positions)| {
+                let mut dups = Vec::new();
+                for (s, i) in positions {
+                    let slice = &slices[s as usize];
+                    match seen.entry(slice.keys[i as usize]) {
+                        Entry::Occupied(first) => dups.push((s, i, first.get().clone())),
+                        Entry::Vacant(e) => {
+                            e.insert(slice.rows[i as usize].clone());
+                        }
+                    }
+                }
+                dups
+            })
+            .collect();
+
+        let mut out: Vec<Vec<(usize, RowRef)>> = vec![Vec::new(); slices.len()];
+        for (s, i, first) in found.into_iter().flatten() {
+            out[s as usize].push((i as usize, first));
+        }
+        for dups in &mut out {
+            dups.sort_unstable_by_key(|d| d.0);
+        }
+        out
+    }
+
+    /// Every kept key, sorted.
+    pub(crate) fn into_sorted(self) -> Vec<[u8; 32]> {
+        let mut keys: Vec<[u8; 32]> = self.shards.into_iter().flat_map(HashMap::into_keys).collect();
+        keys.sort_unstable();
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Repeated rows as `(row index, first row)`, and the kept keys sorted.
+    type Dedup = (Vec<(usize, RowRef)>, Vec<[u8; 32]>);
+
+    /// One pass over one set.
+    fn single_set(keys: &[[u8; 32]], rows: &[RowRef]) -> Dedup {
+        let mut seen: HashMap<[u8; 32], RowRef> = HashMap::new();
+        let mut dups = Vec::new();
+        for (i, key) in keys.iter().enumerate() {
+            match seen.entry(*key) {
+                Entry::Occupied(first) => dups.push((i, first.get().clone())),
+                Entry::Vacant(e) => {
+                    e.insert(rows[i].clone());
+                }
+            }
+        }
+        let mut kept: Vec<[u8; 32]> = seen.into_keys().collect();
+        kept.sort_unstable();
+        (dups, kept)
+    }
+
+    /// The same rows through `SeenKeys`, cut into windows of slices: `windows` lists, per
+    /// `insert` call, the slice lengths.
+    fn sharded(keys: &[[u8; 32]], rows: &[RowRef], windows: &[Vec<usize>]) -> Dedup {
+        let mut seen = SeenKeys::new();
+        let mut dups = Vec::new();
+        let mut start = 0;
+        for window in windows {
+            let mut slices = Vec::new();
+            let mut starts = Vec::new();
+            for &len in window {
+                let range = start..start + len;
+                slices.push(SliceKeys { keys: &keys[range.clone()], rows: &rows[range] });
+                starts.push(start);
+                start += len;
+            }
+            for (s, found) in seen.insert(&slices).into_iter().enumerate() {
+                dups.extend(found.into_iter().map(|(i, first)| (starts[s] + i, first)));
+            }
+        }
+        assert_eq!(start, keys.len());
+        (dups, seen.into_sorted())
+    }
+
+    fn key(shard: u8, id: u8) -> [u8; 32] {
+        let mut k = [0u8; 32];
+        k[0] = shard;
+        k[1] = id;
+        k
+    }
+
+    fn row(file: &str, row_number: Option<i64>) -> RowRef {
+        (Some(Arc::from(file)), row_number)
+    }
+
+    #[test]
+    fn duplicates_across_slices_and_windows_match_a_single_set() {
+        let keys = [key(1, 1), key(2, 1), key(1, 1), key(1, 2), key(2, 1), key(3, 9), key(1, 2), key(3, 9)];
+        // two source files, each numbering its rows from 1
+        let rows: Vec<RowRef> = [Some(1), None, Some(3), Some(4)]
+            .into_iter()
+            .map(|n| row("a.csv", n))
+            .chain((1..=4).map(|n| row("b.csv", Some(n))))
+            .collect();
+        // slices of 2 and 2, then a window of 1, 0 and 3 rows
+        let (dups, kept) = sharded(&keys, &rows, &[vec![2, 2], vec![1, 0, 3]]);
+        assert_eq!(
+            dups,
+            vec![
+                (2, row("a.csv", Some(1))),
+                (4, row("a.csv", None)),
+                (6, row("a.csv", Some(4))),
+                (7, row("b.csv", Some(2))),
+            ]
+        );
+        assert_eq!((dups, kept), single_set(&keys, &rows));
+    }
+
+    #[test]
+    fn random_windows_match_a_single_set() {
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = move || {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (state >> 33) as usize
+        };
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        for _ in 0..20 {
+            let n = 500 + next() % 2_000;
+            // few distinct keys, spread over a few shards, so most rows repeat one
+            let keys: Vec<[u8; 32]> = (0..n).map(|_| key((next() % 5) as u8, (next() % 60) as u8)).collect();
+            // a new source file every 300 rows, numbered from 1
+            let rows: Vec<RowRef> = (0..n)
+                .map(|i| row(&format!("f{}.csv", i / 300), (next() % 10 != 0).then_some((i % 300) as i64 + 1)))
+                .collect();
+            let mut windows = Vec::new();
+            let mut left = n;
+            while left > 0 {
+                let mut window = Vec::new();
+                for _ in 0..1 + next() % 4 {
+                    let len = (next() % 200).min(left);
+                    window.push(len);
+                    left -= len;
+                }
+                windows.push(window);
+            }
+            let got = pool.install(|| sharded(&keys, &rows, &windows));
+            assert_eq!(got, single_set(&keys, &rows));
+        }
+    }
+}
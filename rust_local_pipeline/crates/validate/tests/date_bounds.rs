@@ -0,0 +1,55 @@
+//! DVF date-bound rules on a validated drop, with `today` pinned so that relative bounds
+//! do not depend on the day the tests run.
+use metadata::{Bound, DatasetDescriptor, RuleCheck};
+use std::path::Path;
+use testkit::{Line, column_pairs, descriptor, pairs};
+use validate::ValidationStats;
+
+/// One valid DVF line dated `date_mutation`.
+fn line(mutation: &str, date_mutation: &str) -> Line {
+    Line::sale(mutation).set("date_mutation", date_mutation)
+}
+
+/// Validates `lines` as the drop of 2024-04-01.
+async fn validate(desc: &DatasetDescriptor, root: &Path, lines: &[Line]) -> ValidationStats {
+    testkit::validate(desc, root, "2024-04-01", lines).await
+}
+
+/// `(id_mutation, error_code)` of every reject, sorted.
+fn rejects(stats: &ValidationStats) -> Vec<(String, String)> {
+    let mut rows = column_pairs(&[&stats.rejects_out], "id_mutation", "error_code");
+    rows.sort();
+    rows
+}
+
+#[tokio::test]
+async fn dates_outside_coverage_and_after_the_ingest_are_rejected() {
+    let root = tempfile::tempdir().unwrap();
+    let lines = [
+        line("M1", "2013-12-31"),
+        line("M2", "2014-01-01"),
+        line("M3", "2024-04-01"),
+        line("M4", "2024-04-02"),
+    ];
+    let stats = validate(&descriptor(), root.path(), &lines).await;
+    assert_eq!((stats.rows_in, stats.rows_out), (4, 2));
+    assert_eq!(
+        rejects(&stats),
+        pairs(&[("M1", "DVF_DATE_BEFORE_COVERAGE"), ("M4", "DVF_DATE_AFTER_INGEST")])
+    );
+}
+
+#[tokio::test]
+async fn today_bounds_use_the_configured_clock() {
+    let root = tempfile::tempdir().unwrap();
+    let mut desc = descriptor();
+    // a drop may now hold mutations up to a month before the run, rather than the ingest date
+    for rule in desc.rules.iter_mut().filter(|r| r.code == "DVF_DATE_AFTER_INGEST") {
+        if let RuleCheck::Range { max, .. } = &mut rule.check {
+            *max = Some(Bound::Text("today-1m".to_string()));
+        }
+    }
+    let lines = [line("M1", "2024-09-17"), line("M2", "2024-09-18")];
+    let stats = validate(&desc, root.path(), &lines).await;
+    assert_eq!(rejects(&stats), pairs(&[("M2", "DVF_DATE_AFTER_INGEST")]));
+}